@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use glam::vec3;
+use tracer::{
+    materials::lambertian::Lambertian,
+    objects::{obj::load_obj, object::ObjectType, sphere::Sphere},
+    random::{random_float, seed},
+    utils::bvh::{BvhOptions, BvhTree, SplitMethod},
+    world::physics::Ray,
+    Color, SolidColor,
+};
+
+const RAYS: usize = 10_000;
+
+/// The Stanford bunny on a field of small spheres, seen from the front.
+fn scene() -> Vec<ObjectType> {
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let mut objects = load_obj(
+        Path::new("objs/bunny.obj"),
+        vec3(0.0, -1.0, 0.0),
+        10.0,
+        material.clone(),
+    )
+    .unwrap();
+
+    seed(1);
+    for _ in 0..1000 {
+        let center = vec3(random_float(-6.0, 6.0), -1.2, random_float(-6.0, 2.0));
+        objects.push(Sphere::new(center, 0.2, material.clone()));
+    }
+
+    objects
+}
+
+/// Primary rays from a pinhole camera plus rays starting inside the scene, like
+/// the bounces of a path.
+fn rays() -> Vec<Ray> {
+    seed(2);
+    (0..RAYS)
+        .map(|i| {
+            let origin = if i % 2 == 0 {
+                vec3(0.0, 0.0, 6.0)
+            } else {
+                vec3(random_float(-3.0, 3.0), random_float(-1.0, 1.0), 0.0)
+            };
+            let target = vec3(random_float(-3.0, 3.0), random_float(-1.5, 1.5), 0.0);
+            Ray::new(origin, target - origin + vec3(0.0, 0.0, -0.5))
+        })
+        .collect()
+}
+
+fn traversal(c: &mut Criterion) {
+    let objects = scene();
+    let rays = rays();
+
+    let mut group = c.benchmark_group("bvh traversal");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    for (name, split) in [
+        ("median", SplitMethod::Median),
+        ("sah", SplitMethod::Sah { bins: 16 }),
+    ] {
+        let tree = BvhTree::with_options(&objects, BvhOptions::default().split(split));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &rays, |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| tree.hit(ray, 0.001, f32::MAX).is_some())
+                    .count()
+            })
+        });
+    }
+
+    // The recursive walk the flattened traversal replaced, on the same tree.
+    let tree = BvhTree::new(&objects);
+    group.bench_with_input(
+        BenchmarkId::from_parameter("sah recursive"),
+        &rays,
+        |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| tree.hit_recursive(ray, 0.001, f32::MAX).is_some())
+                    .count()
+            })
+        },
+    );
+
+    // Shadow rays only need to know whether anything is in the way.
+    group.bench_with_input(
+        BenchmarkId::from_parameter("sah occluded"),
+        &rays,
+        |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| tree.occluded(ray, 0.001, f32::MAX))
+                    .count()
+            })
+        },
+    );
+    group.finish();
+}
+
+/// A grid of bunnies, about 320 thousand triangles.
+fn large_scene() -> Vec<ObjectType> {
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let mut objects = Vec::new();
+    for x in 0..8 {
+        for z in 0..8 {
+            let origin = vec3(2.0 * x as f32, 0.0, -2.0 * z as f32);
+            objects.extend(
+                load_obj(Path::new("objs/bunny.obj"), origin, 10.0, material.clone()).unwrap(),
+            );
+        }
+    }
+
+    objects
+}
+
+fn construction(c: &mut Criterion) {
+    let objects = large_scene();
+
+    let mut group = c.benchmark_group("bvh construction");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(objects.len() as u64));
+    for (name, split) in [
+        ("median", SplitMethod::Median),
+        ("sah", SplitMethod::Sah { bins: 16 }),
+    ] {
+        let options = BvhOptions::default().split(split);
+        group.bench_function(name, |b| {
+            b.iter(|| BvhTree::with_options(&objects, options).stats().nodes)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, traversal, construction);
+criterion_main!(benches);
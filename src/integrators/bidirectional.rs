@@ -0,0 +1,578 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+
+use crate::{
+    materials::{
+        material::{Material, ScatterType},
+        microfacet::Bsdf,
+    },
+    objects::object::Geometry,
+    random::{random_distribution, random_int},
+    utils::{
+        bvh::BvhTree,
+        framebuffer::SplatBuffer,
+        sampling::{PdfReady, PDF},
+        Color, BLACK,
+    },
+    world::{
+        camera::Camera,
+        physics::{Intersection, Ray},
+        world::World,
+    },
+};
+
+use super::{
+    integrator::{
+        glossy_mirror, occluded, opaque_hit, Integrator, IntegratorType, Pass,
+        GLOSSY_DIFFUSE_SHARE, SHADOW_EPSILON,
+    },
+    path::Heuristic,
+};
+
+/// Bidirectional path tracing. A path from the camera and a path from a light are
+/// joined at every pair of their vertices, and the light path is also joined to the
+/// lens, which finds caustics and small lights far more often than paths from the
+/// camera alone. Every way of building a path is weighted by multiple importance
+/// sampling. The environment is only reached by camera paths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bidirectional;
+
+impl Bidirectional {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Bidirectional(Bidirectional)
+    }
+}
+
+impl Integrator for Bidirectional {
+    /// Without a camera, light paths can't be joined to the lens and that strategy is
+    /// left out of the weights.
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+        Tracer {
+            world,
+            world_objects,
+            lens: None,
+        }
+        .trace(ray)
+    }
+
+    fn sample(&self, ray: &Ray, world: &World, world_objects: &BvhTree, pass: &Pass) -> Color {
+        Tracer {
+            world,
+            world_objects,
+            lens: Some((pass.camera, pass.splats)),
+        }
+        .trace(ray)
+    }
+}
+
+/// How light leaves a path vertex.
+enum Lobe<'a> {
+    /// The lens, which other vertices are joined to through `Camera::lens_sample`.
+    Camera,
+    /// A point on a light, emitting the same radiance into both hemispheres.
+    Light,
+    /// Diffuse reflection sampled from `pdf`.
+    Diffuse { pdf: PDF<'a>, attenuation: Color },
+    /// A rough surface, which scatters differently depending on where light arrives
+    /// from.
+    Microfacet { bsdf: Bsdf },
+    /// A mirror or refracted direction, nothing can be joined to it.
+    Specular,
+    /// Absorbs everything, like an emitter hit by a path.
+    Black,
+}
+
+struct Vertex<'a> {
+    point: Vec3,
+    /// Geometric normal, zero on the lens.
+    normal: Vec3,
+    hit: Option<Intersection<'a>>,
+    lobe: Lobe<'a>,
+    /// Contribution of the subpath up to here, divided by its density.
+    beta: Color,
+    /// Area density of sampling this vertex from the one before it on its subpath,
+    /// and from the one after it.
+    pdf_fwd: f32,
+    pdf_rev: f32,
+    /// Mirror direction of a glossy camera vertex, which looks up emission alone.
+    glossy: Option<Ray>,
+}
+
+impl<'a> Vertex<'a> {
+    fn new(point: Vec3, normal: Vec3, lobe: Lobe<'a>, beta: Color, pdf_fwd: f32) -> Self {
+        Vertex {
+            point,
+            normal,
+            hit: None,
+            lobe,
+            beta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            glossy: None,
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        matches!(self.lobe, Lobe::Specular)
+    }
+
+    fn is_connectible(&self) -> bool {
+        matches!(
+            self.lobe,
+            Lobe::Camera | Lobe::Light | Lobe::Diffuse { .. } | Lobe::Microfacet { .. }
+        )
+    }
+
+    /// BSDF times the cosine towards `direction`, or for a light the cosine alone.
+    fn f_cos(&self, direction: Vec3) -> Color {
+        match (&self.lobe, &self.hit) {
+            (Lobe::Diffuse { attenuation, .. }, Some(hit)) => {
+                *attenuation
+                    * hit
+                        .material
+                        .scattering_pdf(hit, &Ray::new(self.point, direction))
+            }
+            (Lobe::Microfacet { bsdf }, _) => bsdf.eval(direction),
+            (Lobe::Light, _) => Color::splat(self.normal.dot(direction.normalize()).abs()),
+            _ => BLACK,
+        }
+    }
+
+    /// Solid angle density of the path scattering towards `direction`, having arrived
+    /// along `from` if it didn't come from the vertex before this one on its subpath.
+    fn direction_pdf(&self, from: Option<Vec3>, direction: Vec3) -> f32 {
+        match &self.lobe {
+            Lobe::Diffuse { pdf, .. } => pdf.value(direction),
+            Lobe::Microfacet { bsdf } => match from {
+                Some(from) => bsdf.viewed_from(from).pdf(direction),
+                None => bsdf.pdf(direction),
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Radiance emitted from here, on either side of the surface.
+    fn emitted(&self) -> Color {
+        match &self.hit {
+            Some(hit) => hit.material.emitted(hit.uv, hit.point),
+            None => BLACK,
+        }
+    }
+}
+
+/// Converts a solid angle density at `from` to an area density at `to`.
+fn to_area(pdf: f32, from: &Vertex, to: &Vertex) -> f32 {
+    let direction = to.point - from.point;
+    let distance_squared = direction.length_squared();
+    if distance_squared == 0.0 {
+        return 0.0;
+    }
+    if to.normal == Vec3::ZERO {
+        pdf / distance_squared
+    } else {
+        pdf * to.normal.dot(direction).abs() / (distance_squared * distance_squared.sqrt())
+    }
+}
+
+/// Solid angle density of emitting towards `direction` from a point with `normal`.
+fn emission_pdf(normal: Vec3, direction: Vec3) -> f32 {
+    normal.dot(direction.normalize()).abs() / (2.0 * PI)
+}
+
+/// The densities of one vertex that the weights need, see `Tracer::weight`.
+#[derive(Clone, Copy)]
+struct Densities {
+    pdf_fwd: f32,
+    pdf_rev: f32,
+    delta: bool,
+}
+
+impl From<&Vertex<'_>> for Densities {
+    fn from(vertex: &Vertex) -> Self {
+        Densities {
+            pdf_fwd: vertex.pdf_fwd,
+            pdf_rev: vertex.pdf_rev,
+            delta: vertex.is_delta(),
+        }
+    }
+}
+
+struct Tracer<'a> {
+    world: &'a World,
+    world_objects: &'a BvhTree<'a>,
+    /// Camera to join light paths to and the image their light lands on.
+    lens: Option<(&'a Camera, &'a SplatBuffer)>,
+}
+
+impl<'a> Tracer<'a> {
+    fn trace(&self, ray: &Ray) -> Color {
+        let max_vertices = self.world.bounces.depth() as usize;
+        let mut radiance = BLACK;
+
+        // Vertices on the lens start the camera path.
+        let mut camera_path = vec![Vertex::new(
+            ray.origin,
+            Vec3::ZERO,
+            Lobe::Camera,
+            Color::ONE,
+            1.0,
+        )];
+        let pdf = self.camera_pdf(&camera_path[0], ray.origin + ray.direction);
+        let escaped = self.walk(
+            Ray::new(ray.origin, ray.direction),
+            pdf,
+            &mut camera_path,
+            max_vertices + 1,
+            true,
+        );
+        if let Some((ray, beta)) = escaped {
+            radiance += beta * self.world.skybox.dir_color(ray.direction);
+        }
+        for vertex in &camera_path {
+            if let Some(reflected) = &vertex.glossy {
+                radiance += vertex.beta * glossy_mirror(reflected, self.world, self.world_objects);
+            }
+        }
+
+        let light_path = self.light_path(max_vertices);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 1 > max_vertices {
+                    continue;
+                }
+                if t == 1 {
+                    self.splat(&light_path[..s], &camera_path[0]);
+                } else {
+                    radiance += self.connect(&light_path[..s], &camera_path[..t]);
+                }
+            }
+        }
+
+        radiance
+    }
+
+    /// Extends `path` along `ray`, sampled with solid angle density `pdf`, until it
+    /// escapes, is absorbed or holds `max_vertices`. Returns the ray that escaped
+    /// the scene and its throughput.
+    fn walk(
+        &self,
+        mut ray: Ray,
+        mut pdf: f32,
+        path: &mut Vec<Vertex<'a>>,
+        max_vertices: usize,
+        from_camera: bool,
+    ) -> Option<(Ray, Color)> {
+        let mut beta = path.last().map_or(Color::ONE, |vertex| vertex.beta);
+        if let Some(Lobe::Light) = path.last().map(|vertex| &vertex.lobe) {
+            beta *= path[0].normal.dot(ray.direction.normalize()).abs() / pdf;
+        }
+
+        while path.len() < max_vertices {
+            let hit = match opaque_hit(&ray, f32::MAX, self.world_objects) {
+                Some(hit) => hit,
+                None => return Some((ray, beta)),
+            };
+            let previous = path.len() - 1;
+            let mut vertex = Vertex::new(hit.point, hit.normal, Lobe::Black, beta, 0.0);
+            vertex.pdf_fwd = to_area(pdf, &path[previous], &vertex);
+
+            let (bsdf, attenuation) = match hit.material.scatter(&ray, &hit) {
+                Some(ScatterType::Specular {
+                    specular,
+                    attenuation,
+                }) => {
+                    vertex.lobe = Lobe::Specular;
+                    vertex.hit = Some(hit);
+                    path.push(vertex);
+                    path[previous].pdf_rev = 0.0;
+                    beta *= attenuation;
+                    pdf = 0.0;
+                    ray = specular;
+                    continue;
+                }
+                Some(ScatterType::Scatter { pdf, attenuation }) => (pdf, attenuation),
+                Some(ScatterType::Glossy {
+                    pdf,
+                    attenuation,
+                    specular,
+                }) => {
+                    // Part of a glossy surface is a single mirror lookup, as in the
+                    // path tracer, and only the diffuse part carries the path on.
+                    if from_camera {
+                        vertex.glossy = Some(specular);
+                    }
+                    (pdf, GLOSSY_DIFFUSE_SHARE * attenuation)
+                }
+                // Light paths carry importance back through refractions.
+                Some(ScatterType::Microfacet { bsdf }) => {
+                    let bsdf = if from_camera { bsdf } else { bsdf.adjoint() };
+                    (PDF::microfacet(bsdf), Color::ONE)
+                }
+                None => {
+                    vertex.hit = Some(hit);
+                    path.push(vertex);
+                    return None;
+                }
+            };
+
+            let direction = bsdf.generate();
+            let scattered_pdf = bsdf.value(direction);
+            vertex.lobe = match bsdf {
+                PDF::Microfacet { bsdf } => Lobe::Microfacet { bsdf },
+                pdf => Lobe::Diffuse { pdf, attenuation },
+            };
+            let reverse_pdf = vertex.direction_pdf(Some(direction), -ray.direction);
+            vertex.hit = Some(hit);
+            path[previous].pdf_rev = to_area(reverse_pdf, &vertex, &path[previous]);
+            let f_cos = vertex.f_cos(direction);
+            let point = vertex.point;
+            path.push(vertex);
+
+            if scattered_pdf <= 0.0 {
+                return None;
+            }
+            beta *= f_cos / scattered_pdf;
+            if beta == BLACK {
+                return None;
+            }
+            pdf = scattered_pdf;
+            ray = Ray::new(point, direction);
+        }
+
+        None
+    }
+
+    /// A path starting on a random light, empty when there are none.
+    fn light_path(&self, max_vertices: usize) -> Vec<Vertex<'a>> {
+        let Some((hit, pdf)) = self.world.random_light_point() else {
+            return vec![];
+        };
+        let (point, normal) = (hit.point, hit.normal);
+
+        let mut origin = Vertex::new(point, normal, Lobe::Light, BLACK, pdf);
+        origin.hit = Some(hit);
+        origin.beta = origin.emitted() / pdf;
+
+        let side = if random_distribution() < 0.5 {
+            normal
+        } else {
+            -normal
+        };
+        let direction = PDF::cosine(side).generate();
+        let mut path = vec![origin];
+        self.walk(
+            Ray::new(point, direction),
+            emission_pdf(normal, direction),
+            &mut path,
+            max_vertices,
+            false,
+        );
+
+        path
+    }
+
+    /// Joins the last vertices of both subpaths, or takes the emission found by the
+    /// camera path when `light` is empty.
+    fn connect(&self, light: &[Vertex], camera: &[Vertex]) -> Color {
+        let z = &camera[camera.len() - 1];
+
+        let y = match light {
+            [] => {
+                let emitted = z.emitted();
+                if emitted == BLACK {
+                    return BLACK;
+                }
+                return z.beta * emitted * self.weight(light, camera);
+            }
+            [_] => return self.sample_light(camera),
+            [.., y] => y,
+        };
+
+        if !y.is_connectible() || !z.is_connectible() {
+            return BLACK;
+        }
+        let direction = y.point - z.point;
+        let contribution =
+            z.beta * z.f_cos(direction) * y.f_cos(-direction) * y.beta / direction.length_squared();
+        if contribution == BLACK || !self.visible(z.point, y.point) {
+            return BLACK;
+        }
+
+        contribution * self.weight(light, camera)
+    }
+
+    /// Joins the camera path to a new point on a light. The light is sampled towards
+    /// the camera vertex like the path tracer does, which is far less noisy for small
+    /// spheres than reusing the start of the light path, and is weighted as if it
+    /// were that start.
+    fn sample_light(&self, camera: &[Vertex]) -> Color {
+        let z = &camera[camera.len() - 1];
+        if !z.is_connectible() {
+            return BLACK;
+        }
+
+        let lights = self.world.lights();
+        let light = &lights[random_int(0, lights.len() as u32) as usize];
+        let shadow = Ray::new(z.point, light.random(z.point));
+        let light_pdf = light.pdf_value(shadow.origin, shadow.direction) / lights.len() as f32;
+        if light_pdf <= 0.0 {
+            return BLACK;
+        }
+        let hit = match light.intersects(&shadow, 0.001, f32::MAX) {
+            Some(hit) => hit,
+            None => return BLACK,
+        };
+
+        let mut y = Vertex::new(
+            hit.point,
+            hit.normal,
+            Lobe::Light,
+            BLACK,
+            1.0 / (lights.len() as f32 * light.area()),
+        );
+        y.hit = Some(hit);
+        let contribution = z.beta * z.f_cos(shadow.direction) * y.emitted() / light_pdf;
+        if contribution == BLACK || !self.visible(z.point, y.point) {
+            return BLACK;
+        }
+
+        contribution * self.weight(std::slice::from_ref(&y), camera)
+    }
+
+    /// Joins the light path to a point on the lens and adds its light to the pixel
+    /// that sees it.
+    fn splat(&self, light: &[Vertex], eye: &Vertex) {
+        let Some((camera, splats)) = self.lens else {
+            return;
+        };
+        let y = &light[light.len() - 1];
+        if !y.is_connectible() {
+            return;
+        }
+        let Some(lens) = camera.lens_sample(y.point, splats.width, splats.height) else {
+            return;
+        };
+
+        let direction = lens.point - y.point;
+        let contribution = y.beta * y.f_cos(direction) * lens.pdf / direction.length_squared();
+        if contribution == BLACK || !self.visible(lens.point, y.point) {
+            return;
+        }
+
+        let eye = Vertex::new(lens.point, Vec3::ZERO, Lobe::Camera, eye.beta, 1.0);
+        let color = contribution * self.weight(light, std::slice::from_ref(&eye));
+        if color.is_finite() {
+            splats.add(lens.pixel, color);
+        }
+    }
+
+    /// Multiple importance sampling weight of the path made of `light` followed by
+    /// `camera` in reverse, against every other way of sampling it.
+    fn weight(&self, light: &[Vertex], camera: &[Vertex]) -> f32 {
+        let (s, t) = (light.len(), camera.len());
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let z = &camera[t - 1];
+
+        let mut light_densities: Vec<Densities> = light.iter().map(Densities::from).collect();
+        let mut camera_densities: Vec<Densities> = camera.iter().map(Densities::from).collect();
+
+        // Densities of the joined vertices in the direction they were not sampled.
+        if s == 0 {
+            // Emitters that aren't lights can only be found by the camera path.
+            let Some(index) = z
+                .hit
+                .as_ref()
+                .and_then(|hit| self.world.light_index(hit.object))
+            else {
+                return 1.0;
+            };
+            let lights = self.world.lights();
+            let light = &lights[index];
+            camera_densities[t - 1].pdf_rev = 1.0 / (lights.len() as f32 * light.area());
+            let previous = &camera[t - 2];
+            camera_densities[t - 2].pdf_rev = to_area(
+                emission_pdf(z.normal, previous.point - z.point),
+                z,
+                previous,
+            );
+        } else {
+            let y = &light[s - 1];
+            camera_densities[t - 1].pdf_rev = self.pdf(None, y, z);
+            camera_densities[t - 1].delta = false;
+            light_densities[s - 1].delta = false;
+            if t > 1 {
+                camera_densities[t - 2].pdf_rev = self.pdf(Some(y), z, &camera[t - 2]);
+            }
+            light_densities[s - 1].pdf_rev = self.pdf(None, z, y);
+            if s > 1 {
+                light_densities[s - 2].pdf_rev = self.pdf(Some(z), y, &light[s - 2]);
+            }
+        }
+
+        let remap = |pdf: f32| if pdf != 0.0 { pdf } else { 1.0 };
+        let heuristic = |ratio: f32| match self.world.heuristic {
+            Heuristic::Balance => ratio,
+            Heuristic::Power => ratio * ratio,
+        };
+
+        let mut sum = 0.0;
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap(camera_densities[i].pdf_rev) / remap(camera_densities[i].pdf_fwd);
+            // Joining straight to the lens needs a camera.
+            let lens_strategy = i == 1 && self.lens.is_none();
+            if !camera_densities[i].delta && !camera_densities[i - 1].delta && !lens_strategy {
+                sum += heuristic(ratio);
+            }
+        }
+
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light_densities[i].pdf_rev) / remap(light_densities[i].pdf_fwd);
+            let previous_delta = i > 0 && light_densities[i - 1].delta;
+            if !light_densities[i].delta && !previous_delta {
+                sum += heuristic(ratio);
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+
+    /// Area density with which `from` samples `to` when the path moves on from it,
+    /// having reached it from `previous` or else from its own subpath.
+    fn pdf(&self, previous: Option<&Vertex>, from: &Vertex, to: &Vertex) -> f32 {
+        let direction = to.point - from.point;
+        let pdf = match &from.lobe {
+            Lobe::Camera => return self.camera_pdf(from, to.point) * to_area(1.0, from, to),
+            Lobe::Light => emission_pdf(from.normal, direction),
+            Lobe::Diffuse { .. } | Lobe::Microfacet { .. } => from.direction_pdf(
+                previous.map(|previous| previous.point - from.point),
+                direction,
+            ),
+            Lobe::Specular | Lobe::Black => 0.0,
+        };
+
+        to_area(pdf, from, to)
+    }
+
+    /// Solid angle density with which the camera at `eye` shoots towards `point`.
+    fn camera_pdf(&self, eye: &Vertex, point: Vec3) -> f32 {
+        match self.lens {
+            Some((camera, splats)) => {
+                camera.direction_pdf(point - eye.point, splats.width, splats.height)
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Whether nothing but the boundaries of media lies between `from` and `to`.
+    fn visible(&self, from: Vec3, to: Vec3) -> bool {
+        let shadow = Ray::new(from, to - from);
+        !occluded(&shadow, 1.0 - SHADOW_EPSILON, self.world_objects)
+    }
+}
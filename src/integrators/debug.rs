@@ -0,0 +1,187 @@
+use glam::vec3;
+
+use crate::{
+    materials::material::Material,
+    utils::{bvh::BvhTree, sampling::PDF, Color, BLACK},
+    world::{physics::Ray, world::World},
+};
+
+use super::integrator::{occluded, opaque_hit, Integrator, IntegratorType};
+
+// Views of the scene geometry for diagnosing imports and normals without waiting for
+// a converged render. Rays that miss everything are black.
+
+/// Shading normal at the first hit, mapped from [-1, 1] to [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Normals;
+
+/// Material albedo at the first hit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Albedo;
+
+/// Texture coordinates at the first hit in the red and green channels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Uv;
+
+/// Distance to the first hit, white at the camera fading to black at `far`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Depth {
+    pub far: f32,
+}
+
+/// Nodes and objects the BVH tests to find the first hit, from blue for none through
+/// green to red for `budget` or more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BvhCost {
+    pub budget: usize,
+}
+
+/// Share of the hemisphere above the first hit that is open for at least `distance`,
+/// estimated with `samples` cosine weighted shadow rays. Boundaries of media are
+/// invisible, as in a render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    pub distance: f32,
+    pub samples: u32,
+}
+
+impl Normals {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Normals(Normals)
+    }
+}
+
+impl Albedo {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Albedo(Albedo)
+    }
+}
+
+impl Uv {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Uv(Uv)
+    }
+}
+
+impl Depth {
+    pub fn new(far: f32) -> IntegratorType {
+        IntegratorType::Depth(Depth { far })
+    }
+}
+
+impl Default for Depth {
+    fn default() -> Self {
+        Depth { far: 20.0 }
+    }
+}
+
+impl BvhCost {
+    pub fn new(budget: usize) -> IntegratorType {
+        IntegratorType::BvhCost(BvhCost { budget })
+    }
+}
+
+impl Default for BvhCost {
+    fn default() -> Self {
+        BvhCost { budget: 100 }
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32, samples: u32) -> IntegratorType {
+        IntegratorType::AmbientOcclusion(AmbientOcclusion { distance, samples })
+    }
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            distance: 1.0,
+            samples: 16,
+        }
+    }
+}
+
+impl Integrator for Normals {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        match world_objects.hit(ray, 0.001, f32::MAX) {
+            Some(hit) => 0.5 * hit.normal + 0.5,
+            None => BLACK,
+        }
+    }
+}
+
+impl Integrator for Albedo {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        match world_objects.hit(ray, 0.001, f32::MAX) {
+            Some(hit) => hit.material.albedo(hit.uv, hit.point),
+            None => BLACK,
+        }
+    }
+}
+
+impl Integrator for Uv {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        match world_objects.hit(ray, 0.001, f32::MAX) {
+            Some(hit) => vec3(hit.uv.0, hit.uv.1, 0.0),
+            None => BLACK,
+        }
+    }
+}
+
+impl Integrator for Depth {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        match world_objects.hit(ray, 0.001, f32::MAX) {
+            Some(hit) => {
+                let distance = hit.distance * ray.direction.length();
+                Color::splat((1.0 - distance / self.far).max(0.0))
+            }
+            None => BLACK,
+        }
+    }
+}
+
+impl Integrator for BvhCost {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        let cost = world_objects.hit_cost(ray, 0.001, f32::MAX);
+        let t = ((cost.nodes + cost.objects) as f32 / self.budget as f32).min(1.0);
+
+        let (blue, green, red) = (
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+        );
+        if t < 0.5 {
+            blue.lerp(green, 2.0 * t)
+        } else {
+            green.lerp(red, 2.0 * t - 1.0)
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, ray: &Ray, _world: &World, world_objects: &BvhTree) -> Color {
+        let hit = match opaque_hit(ray, f32::MAX, world_objects) {
+            Some(hit) => hit,
+            None => return BLACK,
+        };
+        if self.samples == 0 {
+            return BLACK;
+        }
+
+        let normal = if ray.front_face(hit.normal) {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let pdf = PDF::cosine(normal);
+        let open = (0..self.samples)
+            .filter(|_| {
+                let shadow = Ray::new(hit.point, pdf.generate());
+                !occluded(&shadow, self.distance, world_objects)
+            })
+            .count();
+
+        Color::splat(open as f32 / self.samples as f32)
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    materials::material::Material,
+    utils::{bvh::BvhTree, framebuffer::SplatBuffer, Color},
+    world::{
+        camera::Camera,
+        physics::{Intersection, Ray},
+        world::World,
+    },
+};
+
+use super::{
+    bidirectional::Bidirectional,
+    debug::{Albedo, AmbientOcclusion, BvhCost, Depth, Normals, Uv},
+    mixture::Mixture,
+    path::PathTracer,
+    photon::{PhotonMap, PhotonMapper},
+};
+
+/// Estimates the radiance arriving along camera rays.
+pub trait Integrator {
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color;
+
+    /// Number of passes over the image that `samples_per_pixel` are split into.
+    /// `prepare` runs before each of them.
+    fn passes(&self, _samples_per_pixel: u32) -> u32 {
+        1
+    }
+
+    /// Photons shared by every pixel of pass `index`, for integrators that trace them.
+    fn prepare(&self, _index: u32, _world: &World, _world_objects: &BvhTree) -> Option<PhotonMap> {
+        None
+    }
+
+    /// `radiance` for a ray from `pass.camera`. Integrators that also join paths to
+    /// the lens from elsewhere in the scene add what reaches other pixels to
+    /// `pass.splats`.
+    fn sample(&self, ray: &Ray, world: &World, world_objects: &BvhTree, _pass: &Pass) -> Color {
+        self.radiance(ray, world, world_objects)
+    }
+}
+
+/// What every pixel of one pass over the image shares.
+pub struct Pass<'a> {
+    pub camera: &'a Camera,
+    pub splats: &'a SplatBuffer,
+    /// Photons traced by `Integrator::prepare` for this pass.
+    pub photons: Option<PhotonMap>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegratorType {
+    Mixture(Mixture),
+    Path(PathTracer),
+    Bidirectional(Bidirectional),
+    PhotonMapper(PhotonMapper),
+    Normals(Normals),
+    Albedo(Albedo),
+    Uv(Uv),
+    Depth(Depth),
+    BvhCost(BvhCost),
+    AmbientOcclusion(AmbientOcclusion),
+}
+
+impl Default for IntegratorType {
+    fn default() -> Self {
+        IntegratorType::Path(PathTracer)
+    }
+}
+
+impl Integrator for IntegratorType {
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+        match self {
+            IntegratorType::Mixture(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::Path(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::Bidirectional(integrator) => {
+                integrator.radiance(ray, world, world_objects)
+            }
+            IntegratorType::PhotonMapper(integrator) => {
+                integrator.radiance(ray, world, world_objects)
+            }
+            IntegratorType::Normals(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::Albedo(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::Uv(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::Depth(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::BvhCost(integrator) => integrator.radiance(ray, world, world_objects),
+            IntegratorType::AmbientOcclusion(integrator) => {
+                integrator.radiance(ray, world, world_objects)
+            }
+        }
+    }
+
+    fn passes(&self, samples_per_pixel: u32) -> u32 {
+        match self {
+            IntegratorType::PhotonMapper(integrator) => integrator.passes(samples_per_pixel),
+            _ => 1,
+        }
+    }
+
+    fn prepare(&self, index: u32, world: &World, world_objects: &BvhTree) -> Option<PhotonMap> {
+        match self {
+            IntegratorType::PhotonMapper(integrator) => {
+                integrator.prepare(index, world, world_objects)
+            }
+            _ => None,
+        }
+    }
+
+    fn sample(&self, ray: &Ray, world: &World, world_objects: &BvhTree, pass: &Pass) -> Color {
+        match self {
+            IntegratorType::Bidirectional(integrator) => {
+                integrator.sample(ray, world, world_objects, pass)
+            }
+            IntegratorType::PhotonMapper(integrator) => {
+                integrator.sample(ray, world, world_objects, pass)
+            }
+            _ => self.radiance(ray, world, world_objects),
+        }
+    }
+}
+
+/// Most bounces of each kind a path may take before it is cut off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounces {
+    pub diffuse: u32,
+    /// Mirror reflections, including the reflected part of dielectrics.
+    pub specular: u32,
+    /// Refractions through dielectrics.
+    pub transmission: u32,
+    /// Bounces every path takes before Russian roulette may end it.
+    pub roulette: u32,
+}
+
+impl Default for Bounces {
+    fn default() -> Self {
+        Bounces {
+            diffuse: 8,
+            specular: 16,
+            transmission: 16,
+            roulette: 3,
+        }
+    }
+}
+
+impl Bounces {
+    /// The same limit for every kind of bounce.
+    pub fn all(limit: u32) -> Self {
+        Bounces {
+            diffuse: limit,
+            specular: limit,
+            transmission: limit,
+            ..Bounces::default()
+        }
+    }
+
+    /// Recursion depth of the `Mixture` integrator, which counts the camera ray too.
+    pub fn depth(&self) -> u32 {
+        1 + self.diffuse.max(self.specular).max(self.transmission)
+    }
+}
+
+/// Shadow rays stop this fraction short of their target so they don't hit it.
+pub(super) const SHADOW_EPSILON: f32 = 1e-3;
+
+/// Share of the light of a glossy surface that scatters diffusely. The rest is a
+/// single mirror reflection, see `glossy_mirror`.
+pub(super) const GLOSSY_DIFFUSE_SHARE: f32 = 0.5;
+
+/// Radiance emitted by whatever `ray` hits first, without following it further.
+fn emission(ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+    match opaque_hit(ray, f32::MAX, world_objects) {
+        Some(hit) => hit.material.emitted(hit.uv, hit.point),
+        None => world.skybox.dir_color(ray.direction),
+    }
+}
+
+/// Light the mirror part of a glossy surface reflects along `reflected`, looked up
+/// without following the ray any further.
+pub(super) fn glossy_mirror(reflected: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+    (1.0 - GLOSSY_DIFFUSE_SHARE) * emission(reflected, world, world_objects)
+}
+
+/// First hit along `ray` before `t_max` that isn't the invisible boundary of a medium,
+/// for integrators that don't render media and see straight through them.
+pub(super) fn opaque_hit<'a>(
+    ray: &Ray,
+    t_max: f32,
+    world_objects: &BvhTree<'a>,
+) -> Option<Intersection<'a>> {
+    let mut t_min = 0.001;
+    loop {
+        let hit = world_objects.hit(ray, t_min, t_max)?;
+        if !hit.material.is_interface() {
+            return Some(hit);
+        }
+        // Carry on right behind the boundary, so surfaces touching it are still found.
+        t_min = hit.distance.next_up();
+    }
+}
+
+/// Whether anything but the boundaries of media blocks `ray` before `t_max`.
+pub(super) fn occluded(ray: &Ray, t_max: f32, world_objects: &BvhTree) -> bool {
+    // Most rays are settled by the cheap query alone.
+    world_objects.occluded(ray, 0.001, t_max) && opaque_hit(ray, t_max, world_objects).is_some()
+}
@@ -0,0 +1,134 @@
+use glam::Vec3;
+
+use crate::{
+    materials::material::{Material, ScatterType},
+    utils::{bvh::BvhTree, sampling::PDF, Color, BLACK},
+    world::{physics::Ray, skybox::Skybox, world::World, WorldLights},
+};
+
+use super::integrator::{Integrator, IntegratorType};
+
+/// The original estimator: one direction per bounce, drawn from an even mix of the
+/// light and BSDF densities.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mixture;
+
+impl Mixture {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Mixture(Mixture)
+    }
+}
+
+impl Integrator for Mixture {
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+        color(
+            ray,
+            world_objects,
+            world.lights(),
+            &world.skybox,
+            world.bounces.depth(),
+        )
+    }
+}
+
+fn color(
+    ray: &Ray,
+    world_objects: &BvhTree,
+    light_objects: &WorldLights,
+    skybox: &Skybox,
+    depth: u32,
+) -> Color {
+    if depth == 0 {
+        return BLACK;
+    }
+
+    match world_objects.hit(ray, 0.001, f32::MAX) {
+        Some(intersection) => {
+            let emitted = intersection
+                .material
+                .emitted(intersection.uv, intersection.point);
+
+            // Light sampling covers the emissive objects and, for image skyboxes,
+            // the bright parts of the environment.
+            let lights_pdf = PDF::lights(light_objects, intersection.point);
+            let environment_pdf = PDF::environment(skybox);
+            let sky_and_lights_pdf = PDF::mixture(&lights_pdf, &environment_pdf);
+            let geo_pdf = match (light_objects.is_empty(), skybox.is_sampled()) {
+                (_, false) => &lights_pdf,
+                (true, true) => &environment_pdf,
+                (false, true) => &sky_and_lights_pdf,
+            };
+
+            match intersection.material.scatter(ray, &intersection) {
+                Some(scatter_type) => match scatter_type {
+                    ScatterType::Specular {
+                        specular,
+                        attenuation,
+                    } => {
+                        attenuation
+                            * color(&specular, world_objects, light_objects, skybox, depth - 1)
+                    }
+                    ScatterType::Scatter { pdf, attenuation } => {
+                        let pdf_func = PDF::mixture(geo_pdf, &pdf);
+                        let scattered = Ray::new(intersection.point, pdf_func.generate());
+                        let pdf_val = pdf_func.value(scattered.direction);
+                        if pdf_val <= 0.0 {
+                            return emitted;
+                        }
+                        let scattering_pdf = intersection
+                            .material
+                            .scattering_pdf(&intersection, &scattered);
+                        emitted
+                            + attenuation
+                                * scattering_pdf
+                                * color(&scattered, world_objects, light_objects, skybox, depth - 1)
+                                / pdf_val
+                    }
+                    ScatterType::Glossy {
+                        pdf,
+                        attenuation,
+                        specular,
+                    } => {
+                        let pdf_func = PDF::mixture(geo_pdf, &pdf);
+                        let scattered = Ray::new(intersection.point, pdf_func.generate());
+                        let pdf_val = pdf_func.value(scattered.direction);
+                        if pdf_val <= 0.0 {
+                            return emitted;
+                        }
+                        let scattering_pdf = intersection
+                            .material
+                            .scattering_pdf(&intersection, &scattered);
+                        (emitted
+                            + attenuation
+                                * scattering_pdf
+                                * color(
+                                    &scattered,
+                                    world_objects,
+                                    light_objects,
+                                    skybox,
+                                    depth - 1,
+                                )
+                                / pdf_val
+                            + color(&specular, world_objects, light_objects, skybox, 1))
+                            / 2.0
+                    }
+                    ScatterType::Microfacet { bsdf } => {
+                        let pdf = PDF::microfacet(bsdf);
+                        let pdf_func = PDF::mixture(geo_pdf, &pdf);
+                        let scattered = Ray::new(intersection.point, pdf_func.generate());
+                        let pdf_val = pdf_func.value(scattered.direction);
+                        if pdf_val <= 0.0 || scattered.direction == Vec3::ZERO {
+                            return emitted;
+                        }
+                        emitted
+                            + bsdf.eval(scattered.direction)
+                                * color(&scattered, world_objects, light_objects, skybox, depth - 1)
+                                / pdf_val
+                    }
+                },
+                None => emitted,
+            }
+        }
+        None => skybox.dir_color(ray.direction),
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bidirectional;
+pub mod debug;
+pub mod integrator;
+pub mod mixture;
+pub mod path;
+pub mod photon;
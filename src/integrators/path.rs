@@ -0,0 +1,432 @@
+use glam::Vec3;
+
+use crate::{
+    materials::material::{Material, ScatterType},
+    media::medium::{Medium, MediumSample, MediumType},
+    objects::object::Geometry,
+    random::{random_distribution, random_int},
+    utils::{
+        bvh::BvhTree,
+        sampling::{PdfReady, PDF},
+        Color, BLACK,
+    },
+    world::{physics::Ray, world::World},
+};
+
+use super::{
+    integrator::{glossy_mirror, Integrator, IntegratorType, GLOSSY_DIFFUSE_SHARE, SHADOW_EPSILON},
+    photon::PhotonMap,
+};
+
+/// Path tracing with a shadow ray towards the lights and the environment at every
+/// diffuse vertex, combined with the BSDF sample by multiple importance sampling.
+/// Paths are followed in a loop and ended early by Russian roulette. Inside media,
+/// paths scatter at distances drawn from the medium and count it as a diffuse bounce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathTracer;
+
+impl PathTracer {
+    pub fn new() -> IntegratorType {
+        IntegratorType::Path(PathTracer)
+    }
+}
+
+/// Weighting between two sampling strategies that can produce the same path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Heuristic {
+    Balance,
+    /// Veach's power heuristic with an exponent of 2, usually a little less noisy.
+    #[default]
+    Power,
+}
+
+impl Heuristic {
+    /// Weight of a sample drawn with density `pdf` when the other strategy would have
+    /// produced it with density `other_pdf`.
+    pub fn weight(&self, pdf: f32, other_pdf: f32) -> f32 {
+        let (a, b) = match self {
+            Heuristic::Balance => (pdf, other_pdf),
+            Heuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+
+        if a + b > 0.0 {
+            a / (a + b)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+        trace(ray, world, world_objects, None)
+    }
+}
+
+/// Path traced radiance along `ray`. With `caustics`, light reaching diffuse surfaces
+/// through mirrors and glass is gathered from the photons instead of being found by
+/// the path hitting a light.
+pub(super) fn trace(
+    ray: &Ray,
+    world: &World,
+    world_objects: &BvhTree,
+    caustics: Option<&PhotonMap>,
+) -> Color {
+    Tracer {
+        world,
+        world_objects,
+        heuristic: world.heuristic,
+        caustics,
+        media: world.has_media(),
+    }
+    .trace(ray)
+}
+
+/// How the ray being traced was generated, which decides how much of the emission it
+/// finds is left to light sampling.
+#[derive(Clone, Copy)]
+enum Vertex {
+    Camera,
+    Specular,
+    /// A specular bounce after a diffuse one, whose light the photons may carry.
+    Caustic,
+    /// Sampled at `point` with solid angle density `pdf`. `diffuse` is false for phase
+    /// functions and rough microfacet surfaces, where photons never land.
+    Bsdf {
+        point: Vec3,
+        pdf: f32,
+        diffuse: bool,
+    },
+}
+
+struct Tracer<'a> {
+    world: &'a World,
+    world_objects: &'a BvhTree<'a>,
+    heuristic: Heuristic,
+    caustics: Option<&'a PhotonMap>,
+    /// Whether shadow rays have to look for media and boundaries on their way.
+    media: bool,
+}
+
+impl<'a> Tracer<'a> {
+    fn trace(&self, camera_ray: &Ray) -> Color {
+        let limits = self.world.bounces;
+        let skybox = &self.world.skybox;
+
+        let mut radiance = BLACK;
+        let mut throughput = Color::ONE;
+        let mut ray = Ray::new(camera_ray.origin, camera_ray.direction);
+        let mut from = Vertex::Camera;
+        let mut medium = self.world.atmosphere.as_ref();
+        let (mut diffuse, mut specular, mut transmission) = (0, 0, 0);
+
+        loop {
+            let hit = self.world_objects.hit(&ray, 0.001, f32::MAX);
+            if let Some(medium) = medium {
+                let t_max = hit.as_ref().map_or(f32::MAX, |hit| hit.distance);
+                match medium.sample(&ray, t_max) {
+                    MediumSample::Scattered {
+                        distance,
+                        weight,
+                        emitted,
+                    } => {
+                        radiance += throughput * emitted;
+                        throughput *= weight;
+                        // Media that only absorb end the path where it would scatter.
+                        if diffuse >= limits.diffuse || throughput.max_element() <= 0.0 {
+                            return radiance;
+                        }
+                        diffuse += 1;
+
+                        let point = ray.at(distance);
+                        let phase = medium.phase();
+                        let pdf = PDF::phase(ray.direction, phase);
+                        let f = |shadow: &Ray| {
+                            Color::splat(phase.value(ray.direction, shadow.direction))
+                        };
+                        let inside = |_: Vec3| Some(medium);
+                        radiance += throughput
+                            * (self.sample_light(point, &pdf, f, inside)
+                                + self.sample_environment(point, &pdf, f, inside));
+
+                        // Phase functions are sampled exactly, so the throughput stays.
+                        let direction = pdf.generate();
+                        from = Vertex::Bsdf {
+                            point,
+                            pdf: pdf.value(direction),
+                            diffuse: false,
+                        };
+                        ray = Ray::new(point, direction);
+                        if !self.survives(&mut throughput, diffuse + specular + transmission) {
+                            return radiance;
+                        }
+                        continue;
+                    }
+                    MediumSample::Passed { weight, emitted } => {
+                        radiance += throughput * emitted;
+                        throughput *= weight;
+                    }
+                    MediumSample::Absorbed { emitted } => return radiance + throughput * emitted,
+                }
+            }
+
+            let intersection = match hit {
+                Some(intersection) => intersection,
+                None => {
+                    let mut sky = skybox.dir_color(ray.direction);
+                    if let (Vertex::Bsdf { pdf, .. }, true) = (from, skybox.is_sampled()) {
+                        sky *= self.heuristic.weight(pdf, skybox.pdf_value(ray.direction));
+                    }
+                    return radiance + throughput * sky;
+                }
+            };
+
+            if intersection.material.is_interface() {
+                // Boundaries of media only change the medium the path is in.
+                medium = self.world.medium_beyond(&intersection, ray.direction);
+                ray = Ray::new(intersection.point, ray.direction);
+                continue;
+            }
+
+            let mut emitted = intersection
+                .material
+                .emitted(intersection.uv, intersection.point);
+            if let (Vertex::Bsdf { point, pdf, .. }, Some(light)) =
+                (from, self.world.light_index(intersection.object))
+            {
+                let lights = self.world.lights();
+                let light_pdf = lights[light].pdf_value(point, ray.direction) / lights.len() as f32;
+                emitted *= self.heuristic.weight(pdf, light_pdf);
+            }
+            if let (Vertex::Caustic, Some(_), Some(_)) = (
+                from,
+                self.caustics,
+                self.world.light_index(intersection.object),
+            ) {
+                emitted = BLACK;
+            }
+
+            let (pdf, attenuation, bsdf) = match intersection.material.scatter(&ray, &intersection)
+            {
+                Some(ScatterType::Specular {
+                    specular: scattered,
+                    attenuation,
+                }) => {
+                    radiance += throughput * emitted;
+
+                    // Refracted rays carry on through the surface.
+                    let normal = intersection.normal;
+                    let transmitted =
+                        ray.direction.dot(normal) * scattered.direction.dot(normal) > 0.0;
+                    let (count, limit) = if transmitted {
+                        (&mut transmission, limits.transmission)
+                    } else {
+                        (&mut specular, limits.specular)
+                    };
+                    if *count >= limit {
+                        return radiance;
+                    }
+                    *count += 1;
+                    if transmitted {
+                        medium = self.world.medium_beyond(&intersection, scattered.direction);
+                    }
+
+                    throughput *= attenuation;
+                    ray = scattered;
+                    // Photons only land on surfaces, so light scattered by a medium on
+                    // its way through glass is left to the path.
+                    from = match from {
+                        Vertex::Caustic | Vertex::Bsdf { diffuse: true, .. } => Vertex::Caustic,
+                        _ => Vertex::Specular,
+                    };
+                    if !self.survives(&mut throughput, diffuse + specular + transmission) {
+                        return radiance;
+                    }
+                    continue;
+                }
+                Some(ScatterType::Scatter { pdf, attenuation }) => {
+                    radiance += throughput * emitted;
+                    (pdf, attenuation, None)
+                }
+                Some(ScatterType::Glossy {
+                    pdf,
+                    attenuation,
+                    specular: reflected,
+                }) => {
+                    // Part of the light is a single mirror lookup, the rest carries on
+                    // as a diffuse path.
+                    if specular < limits.specular {
+                        radiance +=
+                            throughput * glossy_mirror(&reflected, self.world, self.world_objects);
+                    }
+                    throughput *= GLOSSY_DIFFUSE_SHARE;
+                    radiance += throughput * emitted;
+                    (pdf, attenuation, None)
+                }
+                Some(ScatterType::Microfacet { bsdf }) => {
+                    radiance += throughput * emitted;
+                    (PDF::microfacet(bsdf), Color::ONE, Some(bsdf))
+                }
+                None => return radiance + throughput * emitted,
+            };
+
+            // Light found by the next vertex is only counted while there are bounces left.
+            if diffuse >= limits.diffuse {
+                return radiance;
+            }
+            diffuse += 1;
+
+            let f = |shadow: &Ray| match &bsdf {
+                Some(bsdf) => bsdf.eval(shadow.direction),
+                None => attenuation * intersection.material.scattering_pdf(&intersection, shadow),
+            };
+            // Light refracted through rough glass comes from the medium on the far side.
+            let current = medium;
+            let beyond = |direction: Vec3| {
+                let normal = intersection.normal;
+                if ray.direction.dot(normal) * direction.dot(normal) > 0.0 {
+                    self.world.medium_beyond(&intersection, direction)
+                } else {
+                    current
+                }
+            };
+            radiance += throughput
+                * (self.sample_light(intersection.point, &pdf, f, beyond)
+                    + self.sample_environment(intersection.point, &pdf, f, beyond));
+            if let (Some(caustics), None) = (self.caustics, bsdf) {
+                radiance += throughput * caustics.estimate(&ray, &intersection, attenuation);
+            }
+
+            let scattered = Ray::new(intersection.point, pdf.generate());
+            let bsdf_pdf = pdf.value(scattered.direction);
+            if bsdf_pdf <= 0.0 {
+                return radiance;
+            }
+            throughput *= f(&scattered) / bsdf_pdf;
+            medium = beyond(scattered.direction);
+            from = Vertex::Bsdf {
+                point: intersection.point,
+                pdf: bsdf_pdf,
+                diffuse: bsdf.is_none(),
+            };
+            ray = scattered;
+            if !self.survives(&mut throughput, diffuse + specular + transmission) {
+                return radiance;
+            }
+        }
+    }
+
+    /// Russian roulette: past the first few bounces a path carries on with a
+    /// probability that follows its throughput, which is scaled up to stay unbiased.
+    fn survives(&self, throughput: &mut Color, bounces: u32) -> bool {
+        if bounces < self.world.bounces.roulette {
+            return true;
+        }
+
+        let survival = throughput.max_element().min(0.95);
+        if survival <= 0.0 || random_distribution() >= survival {
+            return false;
+        }
+        *throughput /= survival;
+        true
+    }
+
+    /// Share of the light at `ray.at(t_max)` that reaches the origin of `ray`, which
+    /// starts out in `medium`. Only the boundaries of media let light through.
+    fn transmittance(&self, ray: &Ray, t_max: f32, medium: Option<&MediumType>) -> Color {
+        if !self.media {
+            return if self.world_objects.occluded(ray, 0.001, t_max) {
+                BLACK
+            } else {
+                Color::ONE
+            };
+        }
+
+        let mut transmittance = Color::ONE;
+        let (mut ray, mut t_max, mut medium) = (Ray::new(ray.origin, ray.direction), t_max, medium);
+        loop {
+            let hit = self.world_objects.hit(&ray, 0.001, t_max);
+            if let Some(medium) = medium {
+                let distance = hit.as_ref().map_or(t_max, |hit| hit.distance);
+                transmittance *= medium.transmittance(&ray, distance);
+            }
+
+            match hit {
+                None => return transmittance,
+                Some(hit) if hit.material.is_interface() => {
+                    medium = self.world.medium_beyond(&hit, ray.direction);
+                    t_max -= hit.distance;
+                    ray = Ray::new(hit.point, ray.direction);
+                }
+                Some(_) => return BLACK,
+            }
+        }
+    }
+
+    /// Light from a random light reaching `point`, where `f` gives the share scattered
+    /// back along the path from a shadow ray and `medium` the medium it leaves through
+    /// in a direction.
+    fn sample_light<'m>(
+        &self,
+        point: Vec3,
+        bsdf: &PDF,
+        f: impl Fn(&Ray) -> Color,
+        medium: impl Fn(Vec3) -> Option<&'m MediumType>,
+    ) -> Color {
+        let lights = self.world.lights();
+        if lights.is_empty() {
+            return BLACK;
+        }
+
+        let light = &lights[random_int(0, lights.len() as u32) as usize];
+        let shadow = Ray::new(point, light.random(point));
+        let light_pdf = light.pdf_value(shadow.origin, shadow.direction) / lights.len() as f32;
+        let scattered = f(&shadow);
+        if light_pdf <= 0.0 || scattered.max_element() <= 0.0 {
+            return BLACK;
+        }
+
+        let light_hit = match light.intersects(&shadow, 0.001, f32::MAX) {
+            Some(light_hit) => light_hit,
+            None => return BLACK,
+        };
+        let t_max = light_hit.distance * (1.0 - SHADOW_EPSILON);
+        let transmittance = self.transmittance(&shadow, t_max, medium(shadow.direction));
+        if transmittance.max_element() <= 0.0 {
+            return BLACK;
+        }
+
+        let emitted = light_hit.material.emitted(light_hit.uv, light_hit.point);
+        let weight = self
+            .heuristic
+            .weight(light_pdf, bsdf.value(shadow.direction));
+        scattered * transmittance * emitted * weight / light_pdf
+    }
+
+    fn sample_environment<'m>(
+        &self,
+        point: Vec3,
+        bsdf: &PDF,
+        f: impl Fn(&Ray) -> Color,
+        medium: impl Fn(Vec3) -> Option<&'m MediumType>,
+    ) -> Color {
+        let skybox = &self.world.skybox;
+        if !skybox.is_sampled() {
+            return BLACK;
+        }
+
+        let shadow = Ray::new(point, skybox.random());
+        let sky_pdf = skybox.pdf_value(shadow.direction);
+        let scattered = f(&shadow);
+        if sky_pdf <= 0.0 || scattered.max_element() <= 0.0 {
+            return BLACK;
+        }
+        let transmittance = self.transmittance(&shadow, f32::MAX, medium(shadow.direction));
+        if transmittance.max_element() <= 0.0 {
+            return BLACK;
+        }
+
+        let weight = self.heuristic.weight(sky_pdf, bsdf.value(shadow.direction));
+        scattered * transmittance * skybox.dir_color(shadow.direction) * weight / sky_pdf
+    }
+}
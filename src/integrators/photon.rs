@@ -0,0 +1,227 @@
+use std::f32::consts::{FRAC_1_PI, PI};
+
+use glam::Vec3;
+use rayon::prelude::*;
+
+use crate::{
+    materials::material::{Material, ScatterType},
+    media::medium::{Medium, MediumSample},
+    random::{self, random_distribution},
+    utils::{
+        bvh::BvhTree,
+        kdtree::{KdTree, Located},
+        sampling::PDF,
+        Color, BLACK,
+    },
+    world::{
+        physics::{Intersection, Ray},
+        world::World,
+    },
+};
+
+use super::{
+    integrator::{Integrator, IntegratorType, Pass},
+    path,
+};
+
+/// How quickly the gather radius shrinks from one pass to the next, between 0 and 1.
+/// Smaller values shrink it faster, trading noise for less blur.
+const ALPHA: f32 = 2.0 / 3.0;
+
+/// Photons traced by one task, each task seeds its own random generator.
+const PHOTONS_PER_TASK: usize = 4096;
+
+/// Progressive photon mapping for caustics. Every sample per pixel is a separate pass
+/// that traces `photons` photons from the lights through mirrors and glass, and paths
+/// from the camera gather the ones that land within the pass's radius of each
+/// diffuse hit. The radius starts at `radius` and shrinks a little every pass, so the
+/// blur of the density estimate fades as the passes are averaged. Light that doesn't
+/// pass through a specular surface on its way to a diffuse one is path traced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhotonMapper {
+    pub photons: usize,
+    pub radius: f32,
+}
+
+impl PhotonMapper {
+    pub fn new(photons: usize, radius: f32) -> IntegratorType {
+        IntegratorType::PhotonMapper(PhotonMapper { photons, radius })
+    }
+
+    /// Gather radius of pass `index`. Each pass keeps the share `(i + ALPHA) / (i + 1)`
+    /// of the area of the one before, following Knaus and Zwicker's probabilistic
+    /// formulation of progressive photon mapping.
+    pub fn radius(&self, index: u32) -> f32 {
+        let area = (1..=index).fold(1.0, |area, i| area * (i as f32 + ALPHA) / (i as f32 + 1.0));
+
+        self.radius * area.sqrt()
+    }
+}
+
+impl Default for PhotonMapper {
+    fn default() -> Self {
+        PhotonMapper {
+            photons: 100_000,
+            radius: 0.1,
+        }
+    }
+}
+
+impl Integrator for PhotonMapper {
+    /// Without photons, caustics are path traced.
+    fn radiance(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> Color {
+        path::trace(ray, world, world_objects, None)
+    }
+
+    fn passes(&self, samples_per_pixel: u32) -> u32 {
+        samples_per_pixel
+    }
+
+    fn prepare(&self, index: u32, world: &World, world_objects: &BvhTree) -> Option<PhotonMap> {
+        Some(PhotonMap::trace(
+            world,
+            world_objects,
+            self.photons,
+            self.radius(index),
+            index,
+        ))
+    }
+
+    fn sample(&self, ray: &Ray, world: &World, world_objects: &BvhTree, pass: &Pass) -> Color {
+        path::trace(ray, world, world_objects, pass.photons.as_ref())
+    }
+}
+
+/// Light landing on a diffuse surface after at least one specular bounce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Photon {
+    pub position: Vec3,
+    /// Direction the photon was travelling in when it landed.
+    pub direction: Vec3,
+    /// Share of the emitted power carried by this photon.
+    pub power: Color,
+}
+
+impl Located for Photon {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+/// The caustic photons of one pass and the radius they are gathered within.
+pub struct PhotonMap {
+    pub photons: KdTree<Photon>,
+    pub radius: f32,
+}
+
+impl PhotonMap {
+    /// Emits `count` photons from the lights, keeping those that reach a diffuse
+    /// surface through mirrors or glass. `index` picks the random sequence of a
+    /// seeded render.
+    pub fn trace(
+        world: &World,
+        world_objects: &BvhTree,
+        count: usize,
+        radius: f32,
+        index: u32,
+    ) -> Self {
+        let tasks = count.div_ceil(PHOTONS_PER_TASK);
+        let photons = (0..tasks)
+            .into_par_iter()
+            .flat_map_iter(|task| {
+                if let Some(seed) = world.seed {
+                    random::seed(
+                        seed.wrapping_mul(0xD1B5_4A32_D192_ED03)
+                            ^ ((index as u64) << 32 | task as u64),
+                    );
+                }
+                let photons = PHOTONS_PER_TASK.min(count - task * PHOTONS_PER_TASK);
+                (0..photons).filter_map(|_| emit(world, world_objects, count))
+            })
+            .collect();
+
+        PhotonMap {
+            photons: KdTree::new(photons),
+            radius,
+        }
+    }
+
+    /// Radiance reflected along `ray` by the diffuse surface at `hit`, which has
+    /// reflectance `attenuation`, from the photons that landed around it on the same
+    /// side.
+    pub fn estimate(&self, ray: &Ray, hit: &Intersection, attenuation: Color) -> Color {
+        let side = ray.direction.dot(hit.normal);
+        let mut power = BLACK;
+        self.photons.within(hit.point, self.radius, |photon| {
+            if photon.direction.dot(hit.normal) * side > 0.0 {
+                power += photon.power;
+            }
+        });
+
+        attenuation * FRAC_1_PI * power / (PI * self.radius * self.radius)
+    }
+}
+
+/// Traces one of `count` photons, returning it where it lands if it took at least one
+/// specular bounce on the way. Photons scattered by a medium or a rough microfacet
+/// surface are dropped, the path tracer finds that light itself.
+fn emit(world: &World, world_objects: &BvhTree, count: usize) -> Option<Photon> {
+    let (light, pdf) = world.random_light_point()?;
+
+    // Lights emit from both sides, so the direction is cosine weighted around either
+    // side of the surface with density |cos| / 2π.
+    let side = if random_distribution() < 0.5 {
+        light.normal
+    } else {
+        -light.normal
+    };
+    let mut ray = Ray::new(light.point, PDF::cosine(side).generate());
+    let mut power = light.material.emitted(light.uv, light.point) * 2.0 * PI / (pdf * count as f32);
+
+    let limits = world.bounces;
+    let mut medium = world.atmosphere.as_ref();
+    let mut bounce = 0;
+    while bounce < limits.specular + limits.transmission {
+        let hit = world_objects.hit(&ray, 0.001, f32::MAX);
+        if let Some(medium) = medium {
+            let t_max = hit.as_ref().map_or(f32::MAX, |hit| hit.distance);
+            match medium.sample(&ray, t_max) {
+                MediumSample::Scattered { .. } | MediumSample::Absorbed { .. } => return None,
+                MediumSample::Passed { weight, .. } => power *= weight,
+            }
+        }
+
+        let hit = hit?;
+        if hit.material.is_interface() {
+            medium = world.medium_beyond(&hit, ray.direction);
+            ray = Ray::new(hit.point, ray.direction);
+            continue;
+        }
+        match hit.material.scatter(&ray, &hit)? {
+            ScatterType::Specular {
+                specular,
+                attenuation,
+            } => {
+                if ray.direction.dot(hit.outward_normal)
+                    * specular.direction.dot(hit.outward_normal)
+                    > 0.0
+                {
+                    medium = world.medium_beyond(&hit, specular.direction);
+                }
+                power *= attenuation;
+                ray = specular;
+                bounce += 1;
+            }
+            ScatterType::Scatter { .. } | ScatterType::Glossy { .. } => {
+                return (bounce > 0).then_some(Photon {
+                    position: hit.point,
+                    direction: ray.direction,
+                    power,
+                });
+            }
+            ScatterType::Microfacet { .. } => return None,
+        }
+    }
+
+    None
+}
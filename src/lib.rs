@@ -0,0 +1,63 @@
+//! A CPU path tracer with support for multiple materials, textures, bump maps and
+//! OBJ meshes.
+//!
+//! Scenes are built either in code, by adding objects to a [`World`] and rendering
+//! it through a [`Camera`], or from a TOML scene file with [`load_scene`].
+#![allow(dead_code)]
+#![allow(
+    clippy::new_ret_no_self,
+    clippy::upper_case_acronyms,
+    clippy::module_inception
+)]
+
+pub mod random;
+pub mod utils;
+
+pub mod integrators;
+pub mod materials;
+pub mod media;
+pub mod objects;
+pub mod scene;
+pub mod world;
+
+pub use integrators::{
+    integrator::{Bounces, Integrator, IntegratorType},
+    path::Heuristic,
+};
+pub use materials::{
+    material::MaterialType,
+    texture::{CheckerBoard, Image, PixelMap, SolidColor},
+    TexturePtr,
+};
+pub use objects::object::ObjectType;
+pub use scene::{
+    loader::{load_scene, parse_scene},
+    SceneError,
+};
+pub use utils::{
+    aov::Aov,
+    denoise::Denoiser,
+    framebuffer::Framebuffer,
+    tonemap::{Operator, ToneMap},
+    Color, RenderedImage,
+};
+pub use world::{camera::Camera, skybox::Skybox, world::World};
+
+use image::{io::Reader as ImageReader, DynamicImage, ImageResult};
+
+pub enum Rotate {
+    None,
+    R90,
+    R180,
+    R270,
+}
+
+pub fn load_image(image_path: &str, rotation: Rotate) -> ImageResult<DynamicImage> {
+    let img = ImageReader::open(image_path)?.decode()?;
+    Ok(match rotation {
+        Rotate::R90 => img.rotate90(),
+        Rotate::R180 => img.rotate180(),
+        Rotate::R270 => img.rotate270(),
+        _ => img,
+    })
+}
@@ -1,196 +1,366 @@
-#![allow(dead_code)]
-use std::path::Path;
-
-use glam::{vec3, Vec3};
-use materials::{
-    dielectric::Dielectric,
-    emissivediffuse::EmissiveDiffuse,
-    glossy::Glossy,
-    lambertian::Lambertian,
-    material::MaterialType,
-    metal::Metal,
-    texture::{CheckerBoard, Image, PixelMap, SolidColor, TextureType},
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
 };
-use objects::{
-    plane::{Plane, PlaneType},
-    rotated::Axis,
-    sphere::Sphere,
+
+use clap::{Parser, ValueEnum};
+use image::ImageFormat;
+use tracer::{
+    integrators::{
+        bidirectional::Bidirectional,
+        debug::{Albedo, AmbientOcclusion, BvhCost, Depth, Normals, Uv},
+        mixture::Mixture,
+        path::PathTracer,
+        photon::PhotonMapper,
+    },
+    load_scene,
+    utils::bvh::{BvhOptions, SplitMethod},
+    Aov, Denoiser, Heuristic, IntegratorType, Operator,
 };
-use random::{random_distribution, random_float};
-use utils::{Color, BLACK, WHITE};
-use world::{camera::Camera, world::World};
-
-mod random;
-mod utils;
-
-mod materials;
-mod objects;
-mod world;
-
-#[allow(dead_code)]
-pub enum Rotate {
-    None,
-    R90,
-    R180,
-    R270,
+
+#[derive(Parser)]
+#[command(about = "Render a scene description file")]
+struct Args {
+    /// Scene description file (TOML)
+    scene: PathBuf,
+
+    /// Output image path, defaults to renders/<scene name>.png
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output image format, inferred from the output extension when omitted. EXR and
+    /// HDR keep the linear radiance, PNG and JPEG are converted for display
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Image width in pixels, at least 2, overrides the scene file
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(2..))]
+    width: Option<u32>,
+
+    /// Samples per pixel, at least 1, overrides the scene file
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    samples_per_pixel: Option<u32>,
+
+    /// Bounce limit for every kind of bounce, overrides the scene file
+    #[arg(short = 'd', long)]
+    max_depth: Option<u32>,
+
+    /// Most diffuse and glossy bounces of a path, overrides the scene file
+    #[arg(long)]
+    diffuse_bounces: Option<u32>,
+
+    /// Most mirror reflections of a path, overrides the scene file
+    #[arg(long)]
+    specular_bounces: Option<u32>,
+
+    /// Most refractions of a path, overrides the scene file
+    #[arg(long)]
+    transmission_bounces: Option<u32>,
+
+    /// Number of render threads, defaults to one per core
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed for the random generator, makes renders reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Tone mapping operator for PNG and JPEG output, overrides the scene file
+    #[arg(short, long, value_enum)]
+    tone_map: Option<ToneMapOperator>,
+
+    /// Exposure adjustment in stops for PNG and JPEG output, overrides the scene file
+    #[arg(short, long, allow_negative_numbers = true)]
+    exposure: Option<f32>,
+
+    /// Extra passes to render, as layers of an EXR output or as separate
+    /// <output>.<pass>.<ext> files otherwise. Overrides the scene file
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    aovs: Option<Vec<AovPass>>,
+
+    /// Filter the image with the albedo and normal guided denoiser, overrides the
+    /// scene file
+    #[arg(long, overrides_with = "no_denoise")]
+    denoise: bool,
+
+    /// Leave the image unfiltered even if the scene file enables the denoiser
+    #[arg(long, overrides_with = "denoise")]
+    no_denoise: bool,
+
+    /// How the BVH is split, overrides the scene file
+    #[arg(long, value_enum)]
+    bvh: Option<BvhSplit>,
+
+    /// Most objects in a BVH leaf, overrides the scene file
+    #[arg(long)]
+    max_leaf_size: Option<usize>,
+
+    /// Light transport algorithm, overrides the scene file
+    #[arg(short, long, value_enum)]
+    integrator: Option<IntegratorKind>,
+
+    /// Multiple importance sampling weights of the path integrator, overrides the
+    /// scene file
+    #[arg(long, value_enum)]
+    mis: Option<MisHeuristic>,
+
+    /// Photons traced per pass by the photon mapping integrator, overrides the scene
+    /// file. An error with any other integrator
+    #[arg(long)]
+    photons: Option<usize>,
+
+    /// Gather radius of the first photon mapping pass, overrides the scene file. An
+    /// error with any other integrator
+    #[arg(long)]
+    photon_radius: Option<f32>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IntegratorKind {
+    /// Next-event estimation with multiple importance sampling
+    Path,
+    /// One sample per bounce from an even light and BSDF mixture
+    Mixture,
+    /// Camera and light paths joined at every vertex, weighted by MIS
+    #[value(alias = "bdpt")]
+    Bidirectional,
+    /// Path tracing with caustics gathered from progressive photon maps
+    #[value(alias = "ppm")]
+    PhotonMapping,
+    /// Shading normals of the first hit
+    Normals,
+    /// Material albedo of the first hit
+    Albedo,
+    /// Texture coordinates of the first hit
+    Uv,
+    /// Distance to the first hit, fading to black at 20 units
+    Depth,
+    /// Heatmap of the BVH nodes and objects tested per camera ray
+    BvhCost,
+    /// Ambient occlusion within one unit of the first hit
+    #[value(alias = "ao")]
+    AmbientOcclusion,
 }
-use image::{io::Reader as ImageReader, DynamicImage};
 
-#[allow(dead_code)]
-pub fn load_image(image_path: &str, rotation: Rotate) -> DynamicImage {
-    let img = ImageReader::open(image_path).unwrap().decode().unwrap();
-    match rotation {
-        Rotate::R90 => img.rotate90(),
-        Rotate::R180 => img.rotate180(),
-        Rotate::R270 => img.rotate270(),
-        _ => img,
+impl From<IntegratorKind> for IntegratorType {
+    fn from(integrator: IntegratorKind) -> Self {
+        match integrator {
+            IntegratorKind::Path => PathTracer::new(),
+            IntegratorKind::Mixture => Mixture::new(),
+            IntegratorKind::Bidirectional => Bidirectional::new(),
+            IntegratorKind::PhotonMapping => IntegratorType::PhotonMapper(PhotonMapper::default()),
+            IntegratorKind::Normals => Normals::new(),
+            IntegratorKind::Albedo => Albedo::new(),
+            IntegratorKind::Uv => Uv::new(),
+            IntegratorKind::Depth => IntegratorType::Depth(Depth::default()),
+            IntegratorKind::BvhCost => IntegratorType::BvhCost(BvhCost::default()),
+            IntegratorKind::AmbientOcclusion => {
+                IntegratorType::AmbientOcclusion(AmbientOcclusion::default())
+            }
+        }
     }
 }
 
-fn color(r: f32, g: f32, b: f32) -> Color {
-    return Color::new(r, g, b);
+#[derive(Clone, Copy, ValueEnum)]
+enum MisHeuristic {
+    Balance,
+    Power,
+}
+
+impl From<MisHeuristic> for Heuristic {
+    fn from(heuristic: MisHeuristic) -> Self {
+        match heuristic {
+            MisHeuristic::Balance => Heuristic::Balance,
+            MisHeuristic::Power => Heuristic::Power,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BvhSplit {
+    Median,
+    Sah,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Png,
+    Jpeg,
+    Exr,
+    Hdr,
+}
+
+impl From<OutputFormat> for ImageFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Exr => ImageFormat::OpenExr,
+            OutputFormat::Hdr => ImageFormat::Hdr,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ToneMapOperator {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl From<ToneMapOperator> for Operator {
+    fn from(operator: ToneMapOperator) -> Self {
+        match operator {
+            ToneMapOperator::Clamp => Operator::Clamp,
+            ToneMapOperator::Reinhard => Operator::Reinhard,
+            ToneMapOperator::Aces => Operator::Aces,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AovPass {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    Uv,
+    ObjectId,
+    MaterialId,
+    All,
+}
+
+impl AovPass {
+    fn aovs(self) -> Vec<Aov> {
+        match self {
+            AovPass::Albedo => vec![Aov::Albedo],
+            AovPass::Normal => vec![Aov::Normal],
+            AovPass::Depth => vec![Aov::Depth],
+            AovPass::Position => vec![Aov::Position],
+            AovPass::Uv => vec![Aov::Uv],
+            AovPass::ObjectId => vec![Aov::ObjectId],
+            AovPass::MaterialId => vec![Aov::MaterialId],
+            AovPass::All => Aov::ALL.to_vec(),
+        }
+    }
 }
 
 fn main() {
-    let mut world = World::new(PixelMap::from_image(load_image(
-        "./images/brick_factory_02_4k.exr",
-        Rotate::None,
-    )));
-    // let mut world = World::new(PixelMap::from_color(color(0.0, 0.0, 0.0)));
-    // world.add_object(
-    //     Path::new("./objs/monke.obj"),
-    //     vec3(-2.8, 1.0, -2.0),
-    //     1.0,
-    //     Lambertian::new(SolidColor::new(color(1.0, 1.0, 1.0), None)),
-    // );
-
-    world.add_object_rot(
-        Path::new("./objs/bunny.obj"),
-        vec3(-1.2, -0.4, -3.2),
-        13.0,
-        Axis::Y,
-        45.0,
-        Lambertian::new(SolidColor::new(color(1.0, 1.0, 1.0), None)),
-    );
-    world.add_object_rot(
-        Path::new("./objs/bunny.obj"),
-        vec3(0.0, -0.4, -2.0),
-        13.0,
-        Axis::Y,
-        45.0,
-        Metal::new(SolidColor::new(color(0.8, 0.8, 0.8), None), 0.2),
-    );
-    world.add_object_rot(
-        Path::new("./objs/bunny.obj"),
-        vec3(1.2, -0.4, -0.8),
-        13.0,
-        Axis::Y,
-        45.0,
-        Glossy::new(SolidColor::new(color(0.0, 0.0, 0.0), None), 1.0, 0.2),
-    );
-    world.add_object_rot(
-        Path::new("./objs/bunny.obj"),
-        vec3(2.4, -0.4, 0.4),
-        13.0,
-        Axis::Y,
-        45.0,
-        Dielectric::new(1.33),
-    );
-
-    // world.add_object(
-    //     Path::new("./objs/monke.obj"),
-    //     vec3(2.8, 1.0, -2.0),
-    //     1.0,
-    //     Lambertian::new(SolidColor::new(color(0.8, 0.8, 0.8), None)),
-    // );
-
-    // world.add(Plane::new(
-    //     PlaneType::ZX,
-    //     -25.0,
-    //     25.0,
-    //     -25.0,
-    //     25.0,
-    //     0.0,
-    //     // Lambertian::new(SolidColor::new(
-    //     //     color(216.0 / 255.0, 202.0 / 255.0, 168.0 / 255.0),
-    //     //     None,
-    //     // )),
-    //     Glossy::new(
-    //         CheckerBoard::new(color(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0), 20.5),
-    //         0.3,
-    //         0.2,
-    //     ),
-    // ));
-
-    world.add(Sphere::new(
-        vec3(0.0, -1000.0, 0.0),
-        1000.0,
-        Glossy::new(
-            CheckerBoard::new(color(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0), 10.5),
-            0.5,
-            0.1,
-        ),
-    ));
-
-    // glossy sphere in front of the camera
-    // world.add(Sphere::new(
-    //     vec3(0.0, 2.0, -2.0),
-    //     2.0,
-    //     Glossy::new(SolidColor::new(color(0.8, 0.1, 0.3), None), 1.0, 1.0),
-    // ));
-
-    // // sphere near the other sphere
-    // world.add(Sphere::new(
-    //     vec3(2.0, 1.0, -3.0),
-    //     1.0,
-    //     Lambertian::new(SolidColor::new(color(1.0, 0.0, 0.0), None)),
-    // ));
-
-    // add metal sphere
-    // world.add(Sphere::new(
-    //     vec3(-2.0, 1.0, -3.0),
-    //     1.0,
-    //     Metal::new(SolidColor::new(color(0.0, 0.0, 1.0), None), 0.8),
-    // ));
-
-    // world.add(Sphere::new(
-    //     vec3(-1.7, 3.3, 0.0),
-    //     0.14,
-    //     EmissiveDiffuse::new(SolidColor::new(color(8.0, 2.8, 0.04), None)),
-    // ));
-
-    world.add_light(Sphere::new(
-        vec3(5.0, 11.0, -10.0),
-        3.0,
-        EmissiveDiffuse::new(SolidColor::new(color(5.0, 5.0, 5.0), None)),
-    ));
-
-    // let camera = Camera::new(
-    //     vec3(-2.8, 7.0, 9.0),
-    //     vec3(-0.5, 2.0, 0.0),
-    //     vec3(0.0, 1.0, 0.0),
-    //     30.0,
-    //     4.0 / 3.0,
-    //     0.0,
-    //     100.0,
-    // );
-
-    // add 5 emmisive spheres floating in the scene
-
-    let camera = Camera::new(
-        vec3(-5.8, 3.0, 18.0),
-        vec3(-1.2, 1.0, 0.0),
-        vec3(0.0, 1.0, 0.0),
-        20.0,
-        4.0 / 3.0,
-        0.05,
-        13.0,
-    );
-
-    let image = world
-        .samples_per_pixel(256)
-        .max_depth(50)
-        .width(1000)
-        .render(camera);
-    image.save("renders/test.png").unwrap();
+    if let Err(err) = run(Args::parse()) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let output = args.output.unwrap_or_else(|| {
+        let name = args.scene.file_stem().unwrap_or_default();
+        Path::new("renders").join(name).with_extension("png")
+    });
+    let format = match args.format {
+        Some(format) => format.into(),
+        None => ImageFormat::from_path(&output)
+            .map_err(|_| format!("cannot infer an image format for {}", output.display()))?,
+    };
+
+    let (mut world, camera) = load_scene(&args.scene)?;
+
+    if let Some(width) = args.width {
+        world = world.width(width);
+    }
+    if let Some(samples_per_pixel) = args.samples_per_pixel {
+        world = world.samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(max_depth) = args.max_depth {
+        world = world.max_depth(max_depth);
+    }
+    if let Some(diffuse) = args.diffuse_bounces {
+        world.bounces.diffuse = diffuse;
+    }
+    if let Some(specular) = args.specular_bounces {
+        world.bounces.specular = specular;
+    }
+    if let Some(transmission) = args.transmission_bounces {
+        world.bounces.transmission = transmission;
+    }
+    if let Some(seed) = args.seed {
+        world = world.seed(seed);
+    }
+    if let Some(operator) = args.tone_map {
+        world.tone_map.operator = operator.into();
+    }
+    if let Some(exposure) = args.exposure {
+        world.tone_map.exposure = exposure;
+    }
+    if let Some(passes) = args.aovs {
+        let aovs: Vec<Aov> = passes.into_iter().flat_map(AovPass::aovs).collect();
+        world = world.aovs(&aovs);
+    }
+    if args.denoise {
+        world = world.denoise(Denoiser::default());
+    }
+    if args.no_denoise {
+        world.denoiser = None;
+    }
+    if let Some(split) = args.bvh {
+        world.bvh.split = match split {
+            BvhSplit::Median => SplitMethod::Median,
+            BvhSplit::Sah => BvhOptions::default().split,
+        };
+    }
+    if let Some(max_leaf_size) = args.max_leaf_size {
+        world.bvh = world.bvh.max_leaf_size(max_leaf_size);
+    }
+    if let Some(integrator) = args.integrator {
+        world = world.integrator(integrator.into());
+    }
+    if let Some(heuristic) = args.mis {
+        world = world.heuristic(heuristic.into());
+    }
+    // The scene file may pick the photon mapper too, so these can't simply require
+    // `--integrator`.
+    match &mut world.integrator {
+        IntegratorType::PhotonMapper(mapper) => {
+            if let Some(photons) = args.photons {
+                mapper.photons = photons;
+            }
+            if let Some(radius) = args.photon_radius {
+                mapper.radius = radius;
+            }
+        }
+        _ if args.photons.is_some() || args.photon_radius.is_some() => {
+            return Err(
+                "--photons and --photon-radius only apply to the photon-mapping integrator".into(),
+            );
+        }
+        _ => {}
+    }
+
+    let height = camera.height(world.width);
+    if height < 2 {
+        return Err(format!(
+            "a {} pixel wide image at the camera's aspect ratio is {} pixels high, it needs at least 2",
+            world.width, height
+        )
+        .into());
+    }
+
+    let image = world.render(camera);
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    image
+        .save_with_format(&output, format)
+        .map_err(|err| format!("could not save {}: {}", output.display(), err))?;
+
+    Ok(())
 }
@@ -0,0 +1,57 @@
+use glam::Vec3;
+
+use crate::{
+    utils::Color,
+    world::physics::{Intersection, Ray},
+};
+
+use super::{
+    material::{Material, MaterialType, ScatterType},
+    metal::Metal,
+    microfacet::{alpha, schlick, Bsdf},
+    texture::Texture,
+    TexturePtr,
+};
+
+/// A metal with a GGX microfacet surface. The texture gives the share of light
+/// reflected head on, which grows towards white at grazing angles, and `roughness`
+/// between 0 for a mirror and 1 spreads the reflection out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conductor {
+    pub texture: TexturePtr,
+    pub roughness: f32,
+}
+
+impl Conductor {
+    pub fn new(texture: TexturePtr, roughness: f32) -> MaterialType {
+        MaterialType::Conductor(Conductor { texture, roughness })
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let normal = self
+            .texture
+            .adjusted_normal(inter.uv, inter.normal)
+            .normalize();
+        let reflectance = self.texture.get_color_uv(inter.uv, inter.point);
+
+        match alpha(self.roughness) {
+            Some(alpha) => Some(ScatterType::Microfacet {
+                bsdf: Bsdf::conductor(normal, -ray.direction, alpha, reflectance),
+            }),
+            None => {
+                let direction = ray.direction.normalize();
+                let reflected = Metal::reflect(direction, normal);
+                (reflected.dot(normal) > 0.0).then(|| ScatterType::Specular {
+                    specular: Ray::new(inter.point, reflected),
+                    attenuation: schlick(reflectance, direction.dot(normal)),
+                })
+            }
+        }
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.texture.get_color_uv(uv, point)
+    }
+}
@@ -1,27 +1,82 @@
 use glam::Vec3;
 
 use crate::{
+    media::{homogeneous::beer_lambert, medium::MediumType},
     random::random_distribution,
-    utils::WHITE,
+    utils::{Color, BLACK, WHITE},
     world::physics::{Intersection, Ray},
 };
 
 use super::{
     material::{Material, MaterialType, ScatterType},
     metal::Metal,
+    microfacet::{alpha, Bsdf},
+    texture::Texture,
+    TexturePtr,
 };
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Dielectric {
     pub ir: f32,
+    /// Medium inside the glass, which light refracted into it travels through.
+    pub medium: Option<MediumType>,
+    /// Share of each color channel absorbed per unit of distance inside the glass.
+    pub absorption: Color,
+    /// Only the normal map of the texture is used, the glass has no color of its own.
+    pub texture: Option<TexturePtr>,
+    /// Roughness of the surface between 0 for clear glass and 1, see `frosted`.
+    pub roughness: f32,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f32) -> MaterialType {
-        MaterialType::Dielectric(Dielectric {
+        Dielectric::glass(index_of_refraction).into()
+    }
+
+    /// Glass filled with `medium`, such as a glass of milk.
+    pub fn with_medium(index_of_refraction: f32, medium: MediumType) -> MaterialType {
+        Dielectric::glass(index_of_refraction)
+            .filled_with(medium)
+            .into()
+    }
+
+    /// Clear glass, to be tinted or filled before it is turned into a material.
+    pub fn glass(index_of_refraction: f32) -> Self {
+        Dielectric {
             ir: index_of_refraction,
-        })
+            medium: None,
+            absorption: BLACK,
+            texture: None,
+            roughness: 0.0,
+        }
+    }
+
+    /// Tints the glass so that light keeps `color` of itself after travelling one unit
+    /// through it at a `density` of 1. Thick parts of an object look darker than thin
+    /// ones, and denser glass darkens faster.
+    pub fn absorption(mut self, color: Color, density: f32) -> Self {
+        let sigma = |kept: f32| -kept.clamp(1e-6, 1.0).ln() * density.max(0.0);
+        self.absorption = Color::new(sigma(color.x), sigma(color.y), sigma(color.z));
+        self
+    }
+
+    /// Roughens the surface with GGX microfacets, which blur whatever is seen through
+    /// or reflected by the glass.
+    pub fn frosted(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn filled_with(mut self, medium: MediumType) -> Self {
+        self.medium = Some(medium);
+        self
+    }
+
+    /// Bends the surface normal by the bump map of `texture`, like `Metal` and `Glossy`.
+    pub fn normal_map(mut self, texture: TexturePtr) -> Self {
+        self.texture = Some(texture);
+        self
     }
 
     pub fn refract(uv: Vec3, normal: Vec3, etai_over_etat: f32) -> Vec3 {
@@ -39,21 +94,35 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType> {
-        let outward_norm = inter.outward_normal;
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let outward_norm = match &self.texture {
+            Some(texture) => texture
+                .adjusted_normal(inter.uv, inter.outward_normal)
+                .normalize(),
+            None => inter.outward_normal,
+        };
         let normal;
 
-        let attenuation = WHITE;
+        let mut attenuation = WHITE;
         let refraction_r;
 
-        if ray.front_face(outward_norm) {
+        if ray.front_face(inter.outward_normal) {
             refraction_r = 1.0 / self.ir;
             normal = outward_norm;
         } else {
             refraction_r = self.ir;
             normal = -outward_norm;
+            // Hits from inside end a stretch of the path through the glass.
+            let distance = inter.distance * ray.direction.length();
+            attenuation = beer_lambert(self.absorption, distance);
         };
 
+        if let Some(alpha) = alpha(self.roughness) {
+            return Some(ScatterType::Microfacet {
+                bsdf: Bsdf::dielectric(outward_norm, -ray.direction, alpha, self.ir, attenuation),
+            });
+        }
+
         let unit_direction = ray.direction.normalize();
         let cos_theta = (-unit_direction).dot(normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
@@ -72,4 +141,18 @@ impl Material for Dielectric {
             attenuation,
         })
     }
+
+    fn albedo(&self, _uv: (f32, f32), _point: Vec3) -> Color {
+        WHITE
+    }
+
+    fn medium(&self) -> Option<&MediumType> {
+        self.medium.as_ref()
+    }
+}
+
+impl From<Dielectric> for MaterialType {
+    fn from(dielectric: Dielectric) -> Self {
+        MaterialType::Dielectric(dielectric)
+    }
 }
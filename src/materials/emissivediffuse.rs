@@ -5,11 +5,11 @@ use crate::utils::Color;
 use super::{
     material::{Material, MaterialType},
     texture::Texture,
-    PtrExtension, TexturePtr,
+    TexturePtr,
 };
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct EmissiveDiffuse {
     texture: TexturePtr,
 }
@@ -22,10 +22,10 @@ impl EmissiveDiffuse {
 
 impl Material for EmissiveDiffuse {
     fn emitted(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.deref().get_color_uv(uv, point)
+        self.texture.get_color_uv(uv, point)
     }
 
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.deref().get_color_uv(uv, point)
+        self.texture.get_color_uv(uv, point)
     }
 }
@@ -1,7 +1,7 @@
 use glam::Vec3;
 
 use crate::{
-    random::{random_distribution, random_in_unit_disk, random_sphere_distribution},
+    random::{random_in_unit_disk, random_sphere_distribution},
     utils::{sampling::PDF, Color},
     world::physics::{Intersection, Ray},
 };
@@ -10,10 +10,10 @@ use super::{
     material::{Material, MaterialType, ScatterType},
     metal::Metal,
     texture::Texture,
-    PtrExtension, TexturePtr,
+    TexturePtr,
 };
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Glossy {
     texture: TexturePtr,
     sheen: f32,
@@ -31,8 +31,8 @@ impl Glossy {
 }
 
 impl Material for Glossy {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType> {
-        let texture = self.texture.deref();
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let texture = &self.texture;
         let normal = texture.adjusted_normal(inter.uv, inter.normal);
 
         let reflected = Metal::reflect(ray.direction.normalize(), normal);
@@ -61,6 +61,6 @@ impl Material for Glossy {
     }
 
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.deref().get_color_uv(uv, point)
+        self.texture.get_color_uv(uv, point)
     }
 }
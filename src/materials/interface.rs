@@ -0,0 +1,45 @@
+use glam::Vec3;
+
+use crate::{
+    media::medium::MediumType,
+    utils::{Color, WHITE},
+    world::physics::{Intersection, Ray},
+};
+
+use super::material::{Material, MaterialType, ScatterType};
+
+/// An invisible surface around a medium, such as the boundary of a cloud of smoke.
+/// Rays pass straight through it into `medium` and back out into the world's
+/// atmosphere. The object it is attached to should be closed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interface {
+    pub medium: MediumType,
+}
+
+impl Interface {
+    pub fn new(medium: MediumType) -> MaterialType {
+        MaterialType::Interface(Interface { medium })
+    }
+}
+
+impl Material for Interface {
+    /// Integrators that don't render media see through the boundary.
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        Some(ScatterType::Specular {
+            specular: Ray::new(inter.point, ray.direction),
+            attenuation: WHITE,
+        })
+    }
+
+    fn albedo(&self, _uv: (f32, f32), _point: Vec3) -> Color {
+        WHITE
+    }
+
+    fn medium(&self) -> Option<&MediumType> {
+        Some(&self.medium)
+    }
+
+    fn is_interface(&self) -> bool {
+        true
+    }
+}
@@ -8,10 +8,10 @@ use crate::{
 use super::{
     material::{Material, MaterialType, ScatterType},
     texture::Texture,
-    PtrExtension, TexturePtr,
+    TexturePtr,
 };
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Lambertian {
     texture: TexturePtr,
 }
@@ -23,8 +23,8 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, inter: &Intersection) -> Option<ScatterType> {
-        let texture = self.texture.deref();
+    fn scatter(&self, _ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let texture = &self.texture;
         let normal = texture.adjusted_normal(inter.uv, inter.normal);
 
         Some(ScatterType::Scatter {
@@ -34,7 +34,7 @@ impl Material for Lambertian {
     }
 
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.deref().get_color_uv(uv, point)
+        self.texture.get_color_uv(uv, point)
     }
 
     fn scattering_pdf(&self, inter: &Intersection, scattered: &Ray) -> f32 {
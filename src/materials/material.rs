@@ -1,13 +1,15 @@
 use glam::Vec3;
 
 use crate::{
+    media::medium::MediumType,
     utils::{sampling::PDF, Color},
     world::physics::{Intersection, Ray},
 };
 
 use super::{
-    dielectric::Dielectric, emissivediffuse::EmissiveDiffuse, glossy::Glossy,
-    lambertian::Lambertian, metal::Metal,
+    conductor::Conductor, dielectric::Dielectric, emissivediffuse::EmissiveDiffuse, glossy::Glossy,
+    interface::Interface, lambertian::Lambertian, metal::Metal, microfacet::Bsdf,
+    principled::Principled,
 };
 
 pub enum ScatterType<'a> {
@@ -24,19 +26,26 @@ pub enum ScatterType<'a> {
         attenuation: Vec3,
         specular: Ray,
     },
+    /// A rough surface whose BSDF can be evaluated and sampled in any direction.
+    Microfacet {
+        bsdf: Bsdf,
+    },
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum MaterialType {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
     EmissiveDiffuse(EmissiveDiffuse),
     Glossy(Glossy),
+    Interface(Interface),
+    Conductor(Conductor),
+    Principled(Principled),
 }
 
 pub trait Material {
-    fn scatter(&self, _ray: &Ray, _inter: &Intersection) -> Option<ScatterType> {
+    fn scatter(&self, _ray: &Ray, _inter: &Intersection) -> Option<ScatterType<'_>> {
         None
     }
     fn emitted(&self, _uv: (f32, f32), _point: Vec3) -> Color {
@@ -48,16 +57,28 @@ pub trait Material {
     fn scattering_pdf(&self, _inter: &Intersection, _scattered: &Ray) -> f32 {
         1.0
     }
+    /// Medium filling the inside of objects made of this material.
+    fn medium(&self) -> Option<&MediumType> {
+        None
+    }
+    /// Whether this only marks where a medium starts, without reflecting or bending
+    /// light.
+    fn is_interface(&self) -> bool {
+        false
+    }
 }
 
 impl Material for MaterialType {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType> {
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
         match self {
             MaterialType::Lambertian(mat) => mat.scatter(ray, inter),
             MaterialType::Metal(mat) => mat.scatter(ray, inter),
             MaterialType::Dielectric(mat) => mat.scatter(ray, inter),
             MaterialType::EmissiveDiffuse(mat) => mat.scatter(ray, inter),
             MaterialType::Glossy(mat) => mat.scatter(ray, inter),
+            MaterialType::Interface(mat) => mat.scatter(ray, inter),
+            MaterialType::Conductor(mat) => mat.scatter(ray, inter),
+            MaterialType::Principled(mat) => mat.scatter(ray, inter),
         }
     }
 
@@ -68,6 +89,9 @@ impl Material for MaterialType {
             MaterialType::Dielectric(mat) => mat.emitted(uv, point),
             MaterialType::EmissiveDiffuse(mat) => mat.emitted(uv, point),
             MaterialType::Glossy(mat) => mat.emitted(uv, point),
+            MaterialType::Interface(mat) => mat.emitted(uv, point),
+            MaterialType::Conductor(mat) => mat.emitted(uv, point),
+            MaterialType::Principled(mat) => mat.emitted(uv, point),
         }
     }
 
@@ -78,6 +102,9 @@ impl Material for MaterialType {
             MaterialType::Dielectric(mat) => mat.albedo(uv, point),
             MaterialType::EmissiveDiffuse(mat) => mat.albedo(uv, point),
             MaterialType::Glossy(mat) => mat.albedo(uv, point),
+            MaterialType::Interface(mat) => mat.albedo(uv, point),
+            MaterialType::Conductor(mat) => mat.albedo(uv, point),
+            MaterialType::Principled(mat) => mat.albedo(uv, point),
         }
     }
 
@@ -88,6 +115,24 @@ impl Material for MaterialType {
             MaterialType::Dielectric(mat) => mat.scattering_pdf(inter, scattered),
             MaterialType::EmissiveDiffuse(mat) => mat.scattering_pdf(inter, scattered),
             MaterialType::Glossy(mat) => mat.scattering_pdf(inter, scattered),
+            MaterialType::Interface(mat) => mat.scattering_pdf(inter, scattered),
+            MaterialType::Conductor(mat) => mat.scattering_pdf(inter, scattered),
+            MaterialType::Principled(mat) => mat.scattering_pdf(inter, scattered),
+        }
+    }
+
+    fn medium(&self) -> Option<&MediumType> {
+        match self {
+            MaterialType::Dielectric(mat) => mat.medium(),
+            MaterialType::Interface(mat) => mat.medium(),
+            _ => None,
+        }
+    }
+
+    fn is_interface(&self) -> bool {
+        match self {
+            MaterialType::Interface(mat) => mat.is_interface(),
+            _ => false,
         }
     }
 }
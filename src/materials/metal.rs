@@ -9,10 +9,10 @@ use crate::{
 use super::{
     material::{Material, MaterialType, ScatterType},
     texture::Texture,
-    PtrExtension, TexturePtr,
+    TexturePtr,
 };
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Metal {
     pub texture: TexturePtr,
     pub fuzz: f32,
@@ -29,8 +29,8 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType> {
-        let texture = self.texture.deref();
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let texture = &self.texture;
         let normal = texture.adjusted_normal(inter.uv, inter.normal);
 
         let reflected = Metal::reflect(ray.direction.normalize(), normal);
@@ -50,6 +50,6 @@ impl Material for Metal {
     }
 
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.deref().get_color_uv(uv, point)
+        self.texture.get_color_uv(uv, point)
     }
 }
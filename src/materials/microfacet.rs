@@ -0,0 +1,535 @@
+use std::f32::consts::PI;
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    random::random_distribution,
+    utils::{
+        sampling::{ONB, UVW},
+        Color, BLACK,
+    },
+};
+
+/// Surfaces with a GGX width below this are treated as perfectly smooth.
+const SMOOTH: f32 = 1e-3;
+
+/// Width of the GGX distribution for a perceptual `roughness` between 0 and 1, or
+/// `None` when the surface is smooth enough to be a perfect mirror.
+pub fn alpha(roughness: f32) -> Option<f32> {
+    let alpha = roughness.clamp(0.0, 1.0).powi(2);
+    (alpha >= SMOOTH).then_some(alpha)
+}
+
+/// Schlick's approximation of the Fresnel reflectance of a metal that reflects
+/// `reflectance` of the light arriving head on.
+pub fn schlick(reflectance: Color, cosine: f32) -> Color {
+    reflectance + (Color::ONE - reflectance) * (1.0 - cosine.abs().min(1.0)).powi(5)
+}
+
+/// Fresnel reflectance of unpolarized light meeting glass with relative index of
+/// refraction `eta` at an angle with cosine `cos_i`, negative from inside.
+pub fn fresnel(cos_i: f32, eta: f32) -> f32 {
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+/// How light meeting the microfacets leaves them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Lobe {
+    /// Reflected with Schlick's Fresnel.
+    Conductor { ggx: Ggx, reflectance: Color },
+    /// Reflected or refracted into glass with index of refraction `eta`, and tinted
+    /// by `tint`.
+    Dielectric { ggx: Ggx, eta: f32, tint: Color },
+    /// Several lobes layered over each other, see `Bsdf::principled`.
+    Layered(Layered),
+}
+
+/// Parameters of a principled surface at one point, as in Blender's Principled BSDF.
+/// All but `ior` lie between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layers {
+    /// Diffuse color, the reflectance of metals and the tint of glass.
+    pub base_color: Color,
+    /// Blends from a dielectric to a metal.
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Head on reflectance of dielectrics, where 0.5 is 4% like most plastics.
+    pub specular: f32,
+    /// Tints the reflections of dielectrics towards the base color.
+    pub specular_tint: f32,
+    /// Soft reflection at grazing angles like on cloth, half tinted by the base color.
+    pub sheen: f32,
+    /// Strength of a thin, glossy coat of lacquer on top.
+    pub clearcoat: f32,
+    /// Blends from an opaque dielectric to glass.
+    pub transmission: f32,
+    /// Index of refraction of glass.
+    pub ior: f32,
+}
+
+/// Width of the GGX distribution of the clearcoat layer.
+const CLEARCOAT: Ggx = Ggx { alpha: 0.01 };
+
+/// The lobes of a principled surface, weighted following Burley's "Physically Based
+/// Shading at Disney". Metals only keep the specular lobe, and glass replaces the
+/// diffuse and specular lobes with its own reflection and refraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Layered {
+    /// Color of the diffuse lobe, black for metals and glass.
+    diffuse: Color,
+    /// Color of the sheen at grazing angles.
+    sheen: Color,
+    roughness: f32,
+    ggx: Ggx,
+    /// Head on reflectance of the specular lobe.
+    reflectance: Color,
+    /// Weight of the specular lobe.
+    specular: f32,
+    /// Weight of the clearcoat lobe.
+    clearcoat: f32,
+    /// Weight and color of the glass lobe.
+    glass: f32,
+    tint: Color,
+    eta: f32,
+    /// Chances of sampling the diffuse, specular, clearcoat and glass lobes.
+    chances: [f32; 4],
+}
+
+impl Layered {
+    fn new(layers: &Layers) -> Self {
+        let unit = |value: f32| value.clamp(0.0, 1.0);
+        let base = layers.base_color.max(Color::ZERO);
+        let (metallic, transmission) = (unit(layers.metallic), unit(layers.transmission));
+        let glass = (1.0 - metallic) * transmission;
+        let opaque = (1.0 - metallic) * (1.0 - transmission);
+
+        // Hue of the base color without its brightness, which tints the reflections.
+        let luminance = base.dot(vec3(0.2126, 0.7152, 0.0722));
+        let hue = if luminance > 0.0 {
+            base / luminance
+        } else {
+            Color::ONE
+        };
+        let dielectric =
+            0.08 * unit(layers.specular) * Color::ONE.lerp(hue, unit(layers.specular_tint));
+        let reflectance = dielectric.lerp(base, metallic);
+
+        let roughness = unit(layers.roughness);
+        let mut layered = Layered {
+            diffuse: opaque * base,
+            sheen: opaque * unit(layers.sheen) * Color::ONE.lerp(hue, 0.5),
+            roughness,
+            ggx: Ggx {
+                alpha: (roughness * roughness).max(SMOOTH),
+            },
+            reflectance,
+            specular: 1.0 - glass,
+            clearcoat: 0.25 * unit(layers.clearcoat),
+            glass,
+            tint: base,
+            eta: layers.ior.max(1.0),
+            chances: [0.0; 4],
+        };
+
+        // The chances follow how much each lobe reflects head on, and don't depend on
+        // the direction the surface is seen from so that `viewed_from` keeps them right.
+        let mean = |color: Color| color.dot(Vec3::ONE) / 3.0;
+        let chances = [
+            mean(layered.diffuse + layered.sheen),
+            layered.specular * mean(reflectance).max(0.04),
+            layered.clearcoat * 0.04,
+            layered.glass,
+        ];
+        let total: f32 = chances.iter().sum();
+        layered.chances = if total > 0.0 {
+            chances.map(|chance| chance / total)
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        };
+        layered
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, radiance: bool) -> Color {
+        let (o, i) = (opaque_side(wo, wo), opaque_side(wo, wi));
+        let mut f = BLACK;
+        if o.z > 0.0 && i.z > 0.0 {
+            // Burley's diffuse, which grows retroreflective as the surface roughens.
+            let cos_d = i.dot((o + i).normalize());
+            let retro = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let diffuse = (1.0 + (retro - 1.0) * schlick_weight(i.z))
+                * (1.0 + (retro - 1.0) * schlick_weight(o.z));
+            f += (self.diffuse * diffuse / PI + self.sheen * schlick_weight(cos_d)) * i.z;
+        }
+        f += self.specular * self.ggx.reflect(o, i, self.reflectance);
+        f += self.clearcoat * CLEARCOAT.reflect(o, i, Color::splat(0.04));
+        if self.glass > 0.0 {
+            f += self.glass * self.tint * self.ggx.glass(wo, wi, self.eta, radiance);
+        }
+        f
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let (o, i) = (opaque_side(wo, wo), opaque_side(wo, wi));
+        let [diffuse, specular, clearcoat, glass] = self.chances;
+        let mut pdf = 0.0;
+        if o.z > 0.0 && i.z > 0.0 {
+            pdf += diffuse * i.z / PI;
+        }
+        pdf += specular * self.ggx.reflect_pdf(o, i);
+        pdf += clearcoat * CLEARCOAT.reflect_pdf(o, i);
+        if glass > 0.0 {
+            pdf += glass * self.ggx.glass_pdf(wo, wi, self.eta);
+        }
+        pdf
+    }
+
+    fn sample(&self, wo: Vec3) -> Option<Vec3> {
+        let o = opaque_side(wo, wo);
+        let [diffuse, specular, clearcoat, _] = self.chances;
+        let u = random_distribution();
+        if u < diffuse {
+            let radius = random_distribution().sqrt();
+            let phi = 2.0 * PI * random_distribution();
+            let wi = vec3(
+                radius * phi.cos(),
+                radius * phi.sin(),
+                (1.0 - radius * radius).sqrt(),
+            );
+            Some(opaque_side(wo, wi))
+        } else if u < diffuse + specular {
+            self.ggx.sample_reflect(o).map(|wi| opaque_side(wo, wi))
+        } else if u < diffuse + specular + clearcoat {
+            CLEARCOAT.sample_reflect(o).map(|wi| opaque_side(wo, wi))
+        } else {
+            self.ggx.sample_glass(wo, self.eta)
+        }
+    }
+}
+
+/// `w` mirrored through the surface when it is seen from below, as opaque lobes
+/// reflect the same on both sides.
+fn opaque_side(wo: Vec3, w: Vec3) -> Vec3 {
+    if wo.z < 0.0 {
+        vec3(w.x, w.y, -w.z)
+    } else {
+        w
+    }
+}
+
+/// Weight of the grazing part in Schlick's approximation.
+fn schlick_weight(cosine: f32) -> f32 {
+    (1.0 - cosine.abs()).clamp(0.0, 1.0).powi(5)
+}
+
+/// A rough surface made of tiny mirrors whose normals follow the GGX (Trowbridge-Reitz)
+/// distribution, seen from one direction. Directions are drawn from the normals
+/// visible from there and masked by the Smith shadowing function, so unlike `Metal`
+/// and `Glossy` it never reflects more light than it receives and can be weighted
+/// against light sampling.
+///
+/// Directions point away from the surface and are in world space, only their
+/// direction matters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bsdf {
+    /// Tangent, bitangent and shading normal.
+    frame: [Vec3; 3],
+    /// Direction the surface is seen from, in the frame.
+    wo: Vec3,
+    lobe: Lobe,
+    /// Whether light rather than importance is carried towards `wo`, see `adjoint`.
+    radiance: bool,
+}
+
+impl Bsdf {
+    /// A rough metal with the shading `normal`, facing `wo` from either side.
+    pub fn conductor(normal: Vec3, wo: Vec3, alpha: f32, reflectance: Color) -> Self {
+        let normal = if normal.dot(wo) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        let ggx = Ggx { alpha };
+        Bsdf::new(normal, wo, Lobe::Conductor { ggx, reflectance })
+    }
+
+    /// Rough glass with the outward shading `normal`, relative index of refraction
+    /// `eta` and every lobe scaled by `tint`.
+    pub fn dielectric(normal: Vec3, wo: Vec3, alpha: f32, eta: f32, tint: Color) -> Self {
+        let ggx = Ggx { alpha };
+        Bsdf::new(normal, wo, Lobe::Dielectric { ggx, eta, tint })
+    }
+
+    /// A principled surface with the outward shading `normal`, mixing diffuse, sheen,
+    /// specular, clearcoat and glass lobes as `layers` asks.
+    pub fn principled(normal: Vec3, wo: Vec3, layers: &Layers) -> Self {
+        Bsdf::new(normal, wo, Lobe::Layered(Layered::new(layers)))
+    }
+
+    fn new(normal: Vec3, wo: Vec3, lobe: Lobe) -> Self {
+        let onb = ONB::build_from_w(normal);
+        let frame = [onb.u(), onb.v(), onb.w()];
+        Bsdf {
+            frame,
+            wo: to_frame(&frame, wo),
+            lobe,
+            radiance: true,
+        }
+    }
+
+    /// The same surface for paths traced from the lights. Refraction squeezes light
+    /// into a smaller solid angle, which the importance carried by those paths doesn't
+    /// see.
+    pub fn adjoint(mut self) -> Self {
+        self.radiance = false;
+        self
+    }
+
+    /// The same surface seen from `wo` instead.
+    pub fn viewed_from(mut self, wo: Vec3) -> Self {
+        self.wo = to_frame(&self.frame, wo);
+        self
+    }
+
+    /// BSDF times the cosine of the angle between `wi` and the normal.
+    pub fn eval(&self, wi: Vec3) -> Color {
+        let (wo, wi) = (self.wo, to_frame(&self.frame, wi));
+        match &self.lobe {
+            Lobe::Conductor { ggx, reflectance } => ggx.reflect(wo, wi, *reflectance),
+            Lobe::Dielectric { ggx, eta, tint } => *tint * ggx.glass(wo, wi, *eta, self.radiance),
+            Lobe::Layered(layered) => layered.eval(wo, wi, self.radiance),
+        }
+    }
+
+    /// Solid angle density with which `sample` draws `wi`.
+    pub fn pdf(&self, wi: Vec3) -> f32 {
+        let (wo, wi) = (self.wo, to_frame(&self.frame, wi));
+        match &self.lobe {
+            Lobe::Conductor { ggx, .. } => ggx.reflect_pdf(wo, wi),
+            Lobe::Dielectric { ggx, eta, .. } => ggx.glass_pdf(wo, wi, *eta),
+            Lobe::Layered(layered) => layered.pdf(wo, wi),
+        }
+    }
+
+    /// A direction drawn by picking one of the microfacets visible from `wo` and
+    /// reflecting off it, or for glass refracting through it with the Fresnel
+    /// transmittance. `None` when the microfacet reflects light through the surface
+    /// or refracts it back, which loses it.
+    pub fn sample(&self) -> Option<Vec3> {
+        let wi = match &self.lobe {
+            Lobe::Conductor { ggx, .. } => ggx.sample_reflect(self.wo),
+            Lobe::Dielectric { ggx, eta, .. } => ggx.sample_glass(self.wo, *eta),
+            Lobe::Layered(layered) => layered.sample(self.wo),
+        }?;
+        Some(from_frame(&self.frame, wi))
+    }
+}
+
+/// The GGX distribution of microfacet normals around +z with width `alpha`, and the
+/// Smith shadowing that goes with it. Directions are in the frame of the surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    /// Light from `wi` reflected towards `wo` with Schlick's Fresnel for
+    /// `reflectance`, times the cosine of `wi`. Only reflects above the surface.
+    fn reflect(&self, wo: Vec3, wi: Vec3, reflectance: Color) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return BLACK;
+        }
+        let wm = (wo + wi).normalize();
+        schlick(reflectance, wo.dot(wm)) * self.d(wm) * self.g(wo, wi) / (4.0 * wo.z)
+    }
+
+    fn reflect_pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).normalize();
+        self.visible(wo, wm) / (4.0 * wo.dot(wm).abs())
+    }
+
+    fn sample_reflect(&self, wo: Vec3) -> Option<Vec3> {
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let wm = self.sample_visible(wo);
+        let wi = 2.0 * wo.dot(wm) * wm - wo;
+        (wi.z > 0.0).then_some(wi)
+    }
+
+    /// Light from `wi` reflected or refracted towards `wo` by glass with index of
+    /// refraction `eta` below the surface, times the cosine of `wi`.
+    fn glass(&self, wo: Vec3, wi: Vec3, eta: f32, radiance: bool) -> f32 {
+        let Some((wm, etap)) = half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+        let reflectance = fresnel(wo.dot(wm), eta);
+        let (d, g) = (self.d(wm), self.g(wo, wi));
+        if wo.z * wi.z > 0.0 {
+            return d * g * reflectance / (4.0 * wo.z.abs());
+        }
+
+        let denominator = (wi.dot(wm) + wo.dot(wm) / etap).powi(2);
+        let f = d * g * (1.0 - reflectance) * (wi.dot(wm) * wo.dot(wm)).abs()
+            / (wo.z.abs() * denominator);
+        if radiance {
+            f / (etap * etap)
+        } else {
+            f
+        }
+    }
+
+    fn glass_pdf(&self, wo: Vec3, wi: Vec3, eta: f32) -> f32 {
+        let Some((wm, etap)) = half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+        let reflectance = fresnel(wo.dot(wm), eta);
+        if wo.z * wi.z > 0.0 {
+            return self.visible(wo, wm) / (4.0 * wo.dot(wm).abs()) * reflectance;
+        }
+
+        let denominator = (wi.dot(wm) + wo.dot(wm) / etap).powi(2);
+        self.visible(wo, wm) * wi.dot(wm).abs() / denominator * (1.0 - reflectance)
+    }
+
+    /// Reflects off or refracts through a visible microfacet with the Fresnel
+    /// reflectance, `None` when that sends the light to the wrong side.
+    fn sample_glass(&self, wo: Vec3, eta: f32) -> Option<Vec3> {
+        let wm = self.sample_visible(wo);
+        let (wi, transmitted) = if random_distribution() < fresnel(wo.dot(wm), eta) {
+            (2.0 * wo.dot(wm) * wm - wo, false)
+        } else {
+            (refract(wo, wm, eta)?, true)
+        };
+
+        let crossed = wo.z * wi.z < 0.0;
+        (crossed == transmitted && wi.z != 0.0).then_some(wi)
+    }
+
+    /// Density of microfacets facing `wm`.
+    fn d(&self, wm: Vec3) -> f32 {
+        let a2 = self.alpha * self.alpha;
+        a2 / (PI * (wm.z * wm.z * (a2 - 1.0) + 1.0).powi(2))
+    }
+
+    /// Smith's auxiliary function, the area of microfacets hidden from `w` per unit
+    /// of visible area.
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Share of the microfacets visible from `w`.
+    fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Share of the microfacets visible from both `wo` and `wi`.
+    fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of the normals of microfacets visible from `w` facing `wm`.
+    fn visible(&self, w: Vec3, wm: Vec3) -> f32 {
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    /// A normal drawn from those visible from `w`, following Heitz's "Sampling the
+    /// GGX Distribution of Visible Normals": the surface is stretched to a hemisphere,
+    /// where the projected area of visible normals is a disk with one half squashed.
+    fn sample_visible(&self, w: Vec3) -> Vec3 {
+        let mut wh = vec3(self.alpha * w.x, self.alpha * w.y, w.z).normalize();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z < 0.99999 {
+            Vec3::Z.cross(wh).normalize()
+        } else {
+            Vec3::X
+        };
+        let t2 = wh.cross(t1);
+
+        let radius = random_distribution().sqrt();
+        let phi = 2.0 * PI * random_distribution();
+        let (x, mut y) = (radius * phi.cos(), radius * phi.sin());
+        let h = (1.0 - x * x).max(0.0).sqrt();
+        let s = (1.0 + wh.z) / 2.0;
+        y = (1.0 - s) * h + s * y;
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+        let nh = x * t1 + y * t2 + z * wh;
+        vec3(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+/// The microfacet normal that scatters `wo` into `wi`, facing out of the glass, with
+/// the ratio of indices of refraction across it, 1 for a reflection. `None` when no
+/// microfacet visible from both could.
+fn half_vector(wo: Vec3, wi: Vec3, eta: f32) -> Option<(Vec3, f32)> {
+    if wo.z == 0.0 || wi.z == 0.0 {
+        return None;
+    }
+    let etap = match (wo.z * wi.z > 0.0, wo.z > 0.0) {
+        (true, _) => 1.0,
+        (false, true) => eta,
+        (false, false) => 1.0 / eta,
+    };
+
+    let wm = wi * etap + wo;
+    if wm.length_squared() == 0.0 {
+        return None;
+    }
+    let wm = wm.normalize();
+    let wm = if wm.z < 0.0 { -wm } else { wm };
+    if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+        return None;
+    }
+
+    Some((wm, etap))
+}
+
+/// `wo` refracted through a microfacet facing `wm`, from either side.
+fn refract(wo: Vec3, wm: Vec3, eta: f32) -> Option<Vec3> {
+    let mut cos_i = wm.dot(wo);
+    let (eta, wm) = if cos_i < 0.0 {
+        cos_i = -cos_i;
+        (1.0 / eta, -wm)
+    } else {
+        (eta, wm)
+    };
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    Some(-wo / eta + (cos_i / eta - cos_t) * wm)
+}
+
+fn to_frame(frame: &[Vec3; 3], w: Vec3) -> Vec3 {
+    let w = w.normalize_or_zero();
+    vec3(w.dot(frame[0]), w.dot(frame[1]), w.dot(frame[2]))
+}
+
+fn from_frame(frame: &[Vec3; 3], w: Vec3) -> Vec3 {
+    w.x * frame[0] + w.y * frame[1] + w.z * frame[2]
+}
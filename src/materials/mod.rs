@@ -1,20 +1,16 @@
+use std::sync::Arc;
+
 use self::texture::TextureType;
 
+pub mod conductor;
 pub mod emissivediffuse;
 pub mod glossy;
+pub mod interface;
 pub mod lambertian;
 pub mod material;
 pub mod metal;
+pub mod microfacet;
+pub mod principled;
 pub mod texture;
-pub type TexturePtr = usize;
+pub type TexturePtr = Arc<TextureType>;
 pub mod dielectric;
-
-pub trait PtrExtension {
-    fn deref(self) -> &'static TextureType;
-}
-
-impl PtrExtension for TexturePtr {
-    fn deref(self) -> &'static TextureType {
-        unsafe { &(*(self as *const TextureType)) }
-    }
-}
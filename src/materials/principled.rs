@@ -0,0 +1,140 @@
+use glam::Vec3;
+
+use crate::{
+    utils::{Color, BLACK},
+    world::physics::{Intersection, Ray},
+};
+
+use super::{
+    material::{Material, MaterialType, ScatterType},
+    microfacet::{Bsdf, Layers},
+    texture::{SolidColor, Texture},
+    TexturePtr,
+};
+
+/// One material for most surfaces, with the parameters of Blender's Principled BSDF
+/// and glTF's metallic-roughness model. Every parameter but `ior` is read from a
+/// texture, and the single value ones from the mean of its color channels. The
+/// normal map of `base_color` bends the surface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Principled {
+    pub base_color: TexturePtr,
+    pub metallic: TexturePtr,
+    pub roughness: TexturePtr,
+    pub specular: TexturePtr,
+    pub specular_tint: TexturePtr,
+    pub sheen: TexturePtr,
+    pub clearcoat: TexturePtr,
+    pub transmission: TexturePtr,
+    pub ior: f32,
+    pub emission: TexturePtr,
+}
+
+impl Principled {
+    /// A dielectric of `base_color` with Blender's defaults: half rough, a 4% head
+    /// on reflection and no emission.
+    pub fn new(base_color: TexturePtr) -> Self {
+        let value = |value: f32| SolidColor::new(Color::splat(value), None);
+        Principled {
+            base_color,
+            metallic: value(0.0),
+            roughness: value(0.5),
+            specular: value(0.5),
+            specular_tint: value(0.0),
+            sheen: value(0.0),
+            clearcoat: value(0.0),
+            transmission: value(0.0),
+            ior: 1.45,
+            emission: SolidColor::new(BLACK, None),
+        }
+    }
+
+    pub fn metallic(mut self, metallic: TexturePtr) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn roughness(mut self, roughness: TexturePtr) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn specular(mut self, specular: TexturePtr) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn specular_tint(mut self, specular_tint: TexturePtr) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub fn sheen(mut self, sheen: TexturePtr) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: TexturePtr) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn transmission(mut self, transmission: TexturePtr) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn ior(mut self, ior: f32) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    pub fn emission(mut self, emission: TexturePtr) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// The parameters at the point `inter` hit.
+    pub fn layers(&self, inter: &Intersection) -> Layers {
+        let value =
+            |texture: &TexturePtr| texture.get_color_uv(inter.uv, inter.point).dot(Vec3::ONE) / 3.0;
+        Layers {
+            base_color: self.base_color.get_color_uv(inter.uv, inter.point),
+            metallic: value(&self.metallic),
+            roughness: value(&self.roughness),
+            specular: value(&self.specular),
+            specular_tint: value(&self.specular_tint),
+            sheen: value(&self.sheen),
+            clearcoat: value(&self.clearcoat),
+            transmission: value(&self.transmission),
+            ior: self.ior,
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<ScatterType<'_>> {
+        let normal = self
+            .base_color
+            .adjusted_normal(inter.uv, inter.outward_normal)
+            .normalize();
+
+        Some(ScatterType::Microfacet {
+            bsdf: Bsdf::principled(normal, -ray.direction, &self.layers(inter)),
+        })
+    }
+
+    fn emitted(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.emission.get_color_uv(uv, point)
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.base_color.get_color_uv(uv, point)
+    }
+}
+
+impl From<Principled> for MaterialType {
+    fn from(principled: Principled) -> Self {
+        MaterialType::Principled(principled)
+    }
+}
@@ -1,38 +1,22 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, fmt, path::Path, sync::Arc};
 
-use arrayvec::ArrayVec;
 use glam::Vec3;
 
-use crate::utils::Color;
-use image::{DynamicImage, GenericImageView};
+use crate::utils::{tonemap::srgb_eotf, Color};
+use image::{
+    codecs::hdr::HdrDecoder, io::Reader as ImageReader, ColorType, DynamicImage, GenericImageView,
+    ImageFormat, ImageResult,
+};
 
 use super::TexturePtr;
 
-static mut TEXTURES: ArrayVec<TextureType, 1000> = ArrayVec::new_const();
-
+#[derive(Debug, PartialEq)]
 pub enum TextureType {
     SolidColor(SolidColor),
     CheckerBoard(CheckerBoard),
     Image(Image),
 }
 
-impl TextureType {
-    pub fn ptr(&self) -> TexturePtr {
-        self as *const TextureType as usize
-    }
-
-    pub fn ref_ptr(self) -> TexturePtr {
-        if let TextureType::Image(_) = self {
-            panic!("Image Textures cannot use 'ref_ptr'!")
-        }
-
-        unsafe {
-            TEXTURES.push(self);
-            TEXTURES[TEXTURES.len() - 1].ptr()
-        }
-    }
-}
-
 pub trait Texture {
     fn get_color_uv(&self, uv: (f32, f32), point: Vec3) -> Color;
     fn adjusted_normal(&self, uv: (f32, f32), normal: Vec3) -> Vec3;
@@ -56,6 +40,7 @@ impl Texture for TextureType {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SolidColor {
     color: Color,
     bump_map: Option<PixelMap>,
@@ -63,7 +48,7 @@ pub struct SolidColor {
 
 impl SolidColor {
     pub fn new(color: Color, bump_map: Option<PixelMap>) -> TexturePtr {
-        TextureType::SolidColor(SolidColor { color, bump_map }).ref_ptr()
+        Arc::new(TextureType::SolidColor(SolidColor { color, bump_map }))
     }
 }
 
@@ -81,6 +66,7 @@ impl Texture for SolidColor {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Image {
     image: PixelMap,
     bump_map: Option<PixelMap>,
@@ -89,16 +75,29 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new(image: DynamicImage, bump_map: Option<PixelMap>) -> TextureType {
-        let width = image.width();
-        let height = image.height();
+    /// A color map, decoded from sRGB unless it holds floats.
+    pub fn new(image: DynamicImage, bump_map: Option<PixelMap>) -> TexturePtr {
+        Image::from_pixel_map(PixelMap::decode_srgb(image), bump_map)
+    }
 
-        TextureType::Image(Image {
-            image: PixelMap::from_image(image),
+    /// Loads a color map, and optionally a bump map, from any format the `image` crate
+    /// can decode. OpenEXR and Radiance HDR files keep their full float range, other
+    /// color maps are decoded from sRGB while bump maps are read as they are.
+    pub fn load(path: &Path, bump_map: Option<&Path>) -> ImageResult<TexturePtr> {
+        let bump_map = bump_map.map(PixelMap::load).transpose()?;
+        Ok(Image::from_pixel_map(PixelMap::load_srgb(path)?, bump_map))
+    }
+
+    pub fn from_pixel_map(image: PixelMap, bump_map: Option<PixelMap>) -> TexturePtr {
+        let width = image.width;
+        let height = image.height;
+
+        Arc::new(TextureType::Image(Image {
+            image,
             bump_map,
             width,
             height,
-        })
+        }))
     }
 }
 
@@ -133,6 +132,7 @@ impl Texture for Image {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct CheckerBoard {
     color_1: Color,
     color_2: Color,
@@ -141,12 +141,11 @@ pub struct CheckerBoard {
 
 impl CheckerBoard {
     pub fn new(color_1: Color, color_2: Color, scale: f32) -> TexturePtr {
-        TextureType::CheckerBoard(CheckerBoard {
+        Arc::new(TextureType::CheckerBoard(CheckerBoard {
             color_1,
             color_2,
             scale,
-        })
-        .ref_ptr()
+        }))
     }
 }
 
@@ -173,6 +172,24 @@ pub struct PixelMap {
     pub height: u32,
 }
 
+impl fmt::Debug for PixelMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PixelMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for PixelMap {
+    fn eq(&self, other: &Self) -> bool {
+        // Shared textures point at the same pixels, skip comparing large images.
+        (self.width, self.height) == (other.width, other.height)
+            && (std::ptr::eq(self.pixels.as_ptr(), other.pixels.as_ptr())
+                || self.pixels == other.pixels)
+    }
+}
+
 impl PixelMap {
     pub fn from_color(color: Color) -> Self {
         Self {
@@ -182,6 +199,66 @@ impl PixelMap {
         }
     }
 
+    /// Loads an image as it is stored, for data such as bump maps.
+    pub fn load(path: &Path) -> ImageResult<Self> {
+        PixelMap::read(path, false)
+    }
+
+    /// Loads a color image, decoding 8-bit formats from sRGB to linear light.
+    pub fn load_srgb(path: &Path) -> ImageResult<Self> {
+        PixelMap::read(path, true)
+    }
+
+    fn read(path: &Path, srgb: bool) -> ImageResult<Self> {
+        let reader = ImageReader::open(path)?;
+        if reader.format() == Some(ImageFormat::Hdr) {
+            // The generic decoder converts Radiance files to 8-bit, read the floats directly.
+            let decoder = HdrDecoder::new(reader.into_inner())?;
+            let metadata = decoder.metadata();
+
+            return Ok(Self {
+                pixels: decoder
+                    .read_image_hdr()?
+                    .into_iter()
+                    .map(|pixel| Vec3::from(pixel.0))
+                    .collect(),
+                width: metadata.width,
+                height: metadata.height,
+            });
+        }
+
+        let image = reader.decode()?;
+        Ok(if srgb {
+            PixelMap::decode_srgb(image)
+        } else {
+            PixelMap::decode(image)
+        })
+    }
+
+    /// Converts a decoded image, going through `from_image_hdr` for float images so
+    /// that radiance above 1.0 is preserved and through `from_image` otherwise.
+    pub fn decode(image: DynamicImage) -> Self {
+        match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => PixelMap::from_image_hdr(image),
+            _ => PixelMap::from_image(image),
+        }
+    }
+
+    /// Like `decode`, but 8-bit images are taken to be sRGB encoded and converted to
+    /// linear light. Float images are linear already.
+    pub fn decode_srgb(image: DynamicImage) -> Self {
+        match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => PixelMap::from_image_hdr(image),
+            _ => {
+                let mut map = PixelMap::from_image(image);
+                for pixel in &mut map.pixels {
+                    *pixel = Vec3::new(srgb_eotf(pixel.x), srgb_eotf(pixel.y), srgb_eotf(pixel.z));
+                }
+                map
+            }
+        }
+    }
+
     pub fn from_image(image: DynamicImage) -> Self {
         Self {
             pixels: image
@@ -200,10 +277,10 @@ impl PixelMap {
     }
 
     pub fn from_image_hdr(image: DynamicImage) -> Self {
+        let image = image.into_rgb32f();
+
         Self {
             pixels: image
-                .as_rgb32f()
-                .unwrap()
                 .pixels()
                 .map(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]))
                 .collect(),
@@ -218,6 +295,14 @@ impl PixelMap {
         (phi / (2.0 * PI), theta / PI)
     }
 
+    /// Inverse of `surface_uv`, maps a texture coordinate back to a unit direction.
+    pub fn uv_direction(&self, uv: (f32, f32)) -> Vec3 {
+        let phi = 2.0 * PI * uv.0 - PI;
+        let theta = PI * uv.1;
+        let sin_theta = theta.sin();
+        Vec3::new(sin_theta * phi.cos(), -theta.cos(), -sin_theta * phi.sin())
+    }
+
     fn adjusted_normal(&self, uv: (f32, f32), normal: Vec3) -> Vec3 {
         normal + self.get_normal(clamp_uv(uv, self.width, self.height))
     }
@@ -0,0 +1,335 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    materials::interface::Interface,
+    objects::{cube::Cube, object::ObjectType},
+    random::random_distribution,
+    utils::{aabb::Aabb, Color, BLACK},
+    world::physics::Ray,
+};
+
+use super::{
+    medium::{Medium, MediumSample, MediumType},
+    phase::HenyeyGreenstein,
+};
+
+/// Values at the centers of the cells of a regular grid over the unit cube, stored
+/// with x varying fastest, then y, then z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelGrid {
+    pub resolution: [usize; 3],
+    pub values: Vec<f32>,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], values: Vec<f32>) -> Self {
+        assert_eq!(
+            values.len(),
+            resolution.iter().product::<usize>(),
+            "voxel grid has the wrong number of values for its resolution"
+        );
+
+        VoxelGrid { resolution, values }
+    }
+
+    /// Reads a headerless file of little endian 32-bit floats in the order of `values`.
+    pub fn load_raw(path: &Path, resolution: [usize; 3]) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() != 4 * resolution.iter().product::<usize>() {
+            return Err(invalid(format!(
+                "expected {} values for a {}x{}x{} grid, found {} bytes",
+                resolution.iter().product::<usize>(),
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                bytes.len()
+            )));
+        }
+
+        Ok(VoxelGrid::new(resolution, floats(&bytes)))
+    }
+
+    /// Reads a Mitsuba `.vol` file of 32-bit floats, returning the grid and the box it
+    /// was saved with. Channels of multi-channel grids are averaged.
+    pub fn load_vol(path: &Path) -> io::Result<(Self, Aabb)> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 48 || &bytes[..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid("not a version 3 .vol file".to_string()));
+        }
+        let int = |offset: usize| {
+            i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()).max(0) as usize
+        };
+        if int(4) != 1 {
+            return Err(invalid("only float32 .vol files are supported".to_string()));
+        }
+
+        let resolution = [int(8), int(12), int(16)];
+        let channels = int(20).max(1);
+        let header = floats(&bytes[24..48]);
+        let data = floats(&bytes[48..]);
+        let count = resolution.iter().product::<usize>();
+        if data.len() != count * channels {
+            return Err(invalid(format!(
+                "expected {} values, found {}",
+                count * channels,
+                data.len()
+            )));
+        }
+
+        let values = data
+            .chunks(channels)
+            .map(|cell| cell.iter().sum::<f32>() / channels as f32)
+            .collect();
+        let bounds = Aabb {
+            min: vec3(header[0], header[1], header[2]),
+            max: vec3(header[3], header[4], header[5]),
+        };
+
+        Ok((VoxelGrid::new(resolution, values), bounds))
+    }
+
+    /// Trilinearly interpolated value at `point` in the unit cube, zero outside of it.
+    pub fn lookup(&self, point: Vec3) -> f32 {
+        if point.cmplt(Vec3::ZERO).any() || point.cmpgt(Vec3::ONE).any() {
+            return 0.0;
+        }
+
+        let [nx, ny, nz] = self.resolution;
+        let size = vec3(nx as f32, ny as f32, nz as f32);
+        let cell = (point * size - 0.5).max(Vec3::ZERO);
+        let base = cell.floor();
+        let t = cell - base;
+        let index = |x: usize, y: usize, z: usize| {
+            self.values[(z.min(nz - 1) * ny + y.min(ny - 1)) * nx + x.min(nx - 1)]
+        };
+
+        let (x, y, z) = (base.x as usize, base.y as usize, base.z as usize);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(index(x, y, z), index(x + 1, y, z), t.x),
+                lerp(index(x, y + 1, z), index(x + 1, y + 1, z), t.x),
+                t.y,
+            )
+        };
+
+        lerp(plane(z), plane(z + 1), t.z)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Light given off inside a grid medium, in proportion to its absorption.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Emission {
+    /// `color` scaled by the value of the grid.
+    Grid {
+        values: Arc<VoxelGrid>,
+        color: Color,
+    },
+    /// Blackbody radiation at the temperature of the grid in Kelvin, times `scale`.
+    Temperature { kelvin: Arc<VoxelGrid>, scale: f32 },
+}
+
+/// A medium whose density varies over a box, read from a voxel grid, for clouds,
+/// smoke and fire. The coefficients are scaled by the density at each point.
+/// Distances are sampled by delta tracking against the densest voxel, and shadow rays
+/// are attenuated by ratio tracking.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub bounds: Aabb,
+    pub density: Arc<VoxelGrid>,
+    pub sigma_a: Color,
+    pub sigma_s: Color,
+    pub phase: HenyeyGreenstein,
+    pub emission: Option<Emission>,
+    /// Highest extinction coefficient anywhere in the grid.
+    majorant: f32,
+}
+
+impl Grid {
+    pub fn new(bounds: Aabb, density: VoxelGrid, sigma_a: Color, sigma_s: Color, g: f32) -> Self {
+        Grid {
+            bounds,
+            majorant: density.max() * (sigma_a + sigma_s).max_element(),
+            density: Arc::new(density),
+            sigma_a,
+            sigma_s,
+            phase: HenyeyGreenstein::new(g),
+            emission: None,
+        }
+    }
+
+    /// Emits `color` scaled by `values`, which cover the same box as the density.
+    pub fn emission(mut self, values: VoxelGrid, color: Color) -> Self {
+        self.emission = Some(Emission::Grid {
+            values: Arc::new(values),
+            color,
+        });
+        self
+    }
+
+    /// Emits like a blackbody at the temperatures in `kelvin`, which cover the same box
+    /// as the density.
+    pub fn temperature(mut self, kelvin: VoxelGrid, scale: f32) -> Self {
+        self.emission = Some(Emission::Temperature {
+            kelvin: Arc::new(kelvin),
+            scale,
+        });
+        self
+    }
+
+    /// A box around the grid that rays enter the medium through.
+    pub fn volume(self) -> ObjectType {
+        let Aabb { min, max } = self.bounds;
+        Cube::new(min, max, Interface::new(MediumType::Grid(self)))
+    }
+
+    fn local(&self, point: Vec3) -> Vec3 {
+        (point - self.bounds.min) / (self.bounds.max - self.bounds.min)
+    }
+
+    fn emitted(&self, point: Vec3) -> Color {
+        match &self.emission {
+            None => BLACK,
+            Some(Emission::Grid { values, color }) => *color * values.lookup(self.local(point)),
+            Some(Emission::Temperature { kelvin, scale }) => {
+                *scale * blackbody(kelvin.lookup(self.local(point)))
+            }
+        }
+    }
+
+    /// Part of `ray` up to `t_max` that lies inside the grid.
+    fn clip(&self, ray: &Ray, t_max: f32) -> Option<(f32, f32)> {
+        let inverse = ray.direction.recip();
+        let t0 = (self.bounds.min - ray.origin) * inverse;
+        let t1 = (self.bounds.max - ray.origin) * inverse;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element().min(t_max);
+
+        (near < far).then_some((near, far))
+    }
+
+    /// Steps along `ray` from `start` to the next point where a particle of the
+    /// majorant density would be met.
+    fn step(&self, ray: &Ray, start: f32) -> f32 {
+        start - (1.0 - random_distribution()).ln() / (self.majorant * ray.direction.length())
+    }
+}
+
+impl From<Grid> for MediumType {
+    fn from(grid: Grid) -> Self {
+        MediumType::Grid(grid)
+    }
+}
+
+/// Color of a blackbody at `kelvin`, sampled at red, green and blue wavelengths and
+/// scaled so that the peak of its spectrum is 1.
+pub fn blackbody(kelvin: f32) -> Color {
+    if kelvin <= 0.0 {
+        return BLACK;
+    }
+
+    const H: f64 = 6.626_070_15e-34;
+    const C: f64 = 2.997_924_58e8;
+    const K: f64 = 1.380_649e-23;
+    let kelvin = kelvin as f64;
+    let planck = |wavelength: f64| {
+        2.0 * H * C * C / (wavelength.powi(5) * ((H * C / (wavelength * K * kelvin)).exp() - 1.0))
+    };
+    // Wien's displacement law gives the wavelength of the peak.
+    let peak = planck(2.897_771_955e-3 / kelvin);
+
+    vec3(
+        (planck(630e-9) / peak) as f32,
+        (planck(532e-9) / peak) as f32,
+        (planck(465e-9) / peak) as f32,
+    )
+}
+
+impl Medium for Grid {
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        let mut weight = Color::ONE;
+        let mut emitted = BLACK;
+        let (mut t, end) = match self.clip(ray, t_max) {
+            Some(span) if self.majorant > 0.0 => span,
+            _ => return MediumSample::Passed { weight, emitted },
+        };
+
+        // Delta tracking picks absorption, scattering or a null collision with the
+        // channel averages of their coefficients. Weighting each by its own color over
+        // that average keeps colored media unbiased.
+        let mean = |color: Color| color.dot(Vec3::ONE) / 3.0;
+        loop {
+            t = self.step(ray, t);
+            if t >= end {
+                return MediumSample::Passed { weight, emitted };
+            }
+
+            let point = ray.at(t);
+            let density = self.density.lookup(self.local(point));
+            let (sigma_a, sigma_s) = (density * self.sigma_a, density * self.sigma_s);
+            let sigma_n = Color::splat(self.majorant) - sigma_a - sigma_s;
+            emitted += weight * sigma_a * self.emitted(point) / self.majorant;
+
+            let (absorb, scatter, null) = (mean(sigma_a), mean(sigma_s), mean(sigma_n));
+            let event = random_distribution() * self.majorant;
+            // Rounding can leave no null collisions where the density peaks, so the
+            // leftover probability goes to scattering, or to absorption in media that
+            // don't scatter.
+            if event < absorb || (null <= 0.0 && scatter <= 0.0) {
+                return MediumSample::Absorbed { emitted };
+            } else if event < absorb + scatter || null <= 0.0 {
+                return MediumSample::Scattered {
+                    distance: t,
+                    weight: weight * sigma_s / scatter,
+                    emitted,
+                };
+            }
+            weight *= sigma_n / null;
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        let mut transmittance = Color::ONE;
+        let (mut t, end) = match self.clip(ray, t_max) {
+            Some(span) if self.majorant > 0.0 => span,
+            _ => return transmittance,
+        };
+
+        // Ratio tracking: every tentative collision keeps the share of null particles.
+        let sigma_t = self.sigma_a + self.sigma_s;
+        loop {
+            t = self.step(ray, t);
+            if t >= end || transmittance.max_element() <= 0.0 {
+                return transmittance;
+            }
+            let density = self.density.lookup(self.local(ray.at(t)));
+            transmittance *= (Color::ONE - density * sigma_t / self.majorant).max(Color::ZERO);
+        }
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        self.phase
+    }
+}
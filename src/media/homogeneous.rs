@@ -0,0 +1,96 @@
+use glam::Vec3;
+
+use crate::{
+    random::random_distribution,
+    utils::{Color, BLACK},
+    world::physics::Ray,
+};
+
+use super::{
+    medium::{Medium, MediumSample, MediumType},
+    phase::HenyeyGreenstein,
+};
+
+/// A medium with the same density everywhere, like fog or tinted water. The
+/// coefficients are per unit of distance, so a ray crossing `d` units keeps
+/// `exp(-(sigma_a + sigma_s) * d)` of its light.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Homogeneous {
+    /// Absorption coefficient per color channel.
+    pub sigma_a: Color,
+    /// Scattering coefficient per color channel.
+    pub sigma_s: Color,
+    pub phase: HenyeyGreenstein,
+}
+
+impl Homogeneous {
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f32) -> MediumType {
+        MediumType::Homogeneous(Homogeneous {
+            sigma_a,
+            sigma_s,
+            phase: HenyeyGreenstein::new(g),
+        })
+    }
+
+    fn sigma_t(&self) -> Color {
+        self.sigma_a + self.sigma_s
+    }
+}
+
+/// `exp(-sigma_t * distance)` per channel, where a channel that doesn't interact
+/// passes everything even across an endless distance.
+pub fn beer_lambert(sigma_t: Color, distance: f32) -> Color {
+    let channel = |sigma: f32| {
+        if sigma > 0.0 {
+            (-sigma * distance).exp()
+        } else {
+            1.0
+        }
+    };
+
+    Vec3::new(channel(sigma_t.x), channel(sigma_t.y), channel(sigma_t.z))
+}
+
+impl Medium for Homogeneous {
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        // The distance is drawn for one channel picked at random, and weighted by the
+        // density averaged over all three so colored media stay unbiased.
+        let length = ray.direction.length();
+        let sigma_t = self.sigma_t();
+        let channel = ((3.0 * random_distribution()) as usize).min(2);
+        let distance = if sigma_t[channel] > 0.0 {
+            -(1.0 - random_distribution()).ln() / sigma_t[channel]
+        } else {
+            f32::INFINITY
+        };
+
+        if distance < t_max * length {
+            let transmittance = beer_lambert(sigma_t, distance);
+            let pdf = (sigma_t * transmittance).dot(Vec3::ONE) / 3.0;
+            MediumSample::Scattered {
+                distance: distance / length,
+                weight: self.sigma_s * transmittance / pdf,
+                emitted: BLACK,
+            }
+        } else {
+            let transmittance = self.transmittance(ray, t_max);
+            let pdf = transmittance.dot(Vec3::ONE) / 3.0;
+            MediumSample::Passed {
+                weight: if pdf > 0.0 {
+                    transmittance / pdf
+                } else {
+                    BLACK
+                },
+                emitted: BLACK,
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        beer_lambert(self.sigma_t(), t_max * ray.direction.length())
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        self.phase
+    }
+}
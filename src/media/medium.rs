@@ -0,0 +1,60 @@
+use crate::{utils::Color, world::physics::Ray};
+
+use super::{grid::Grid, homogeneous::Homogeneous, phase::HenyeyGreenstein};
+
+/// Where a ray travelling through a medium stopped, see `Medium::sample`. `emitted` is
+/// the light given off by the medium along the way, weighted like `weight`.
+pub enum MediumSample {
+    /// Scattered at `ray.at(distance)`.
+    Scattered {
+        distance: f32,
+        weight: Color,
+        emitted: Color,
+    },
+    /// Reached the surface at the end of the ray.
+    Passed { weight: Color, emitted: Color },
+    /// Absorbed before reaching the surface, which ends the path.
+    Absorbed { emitted: Color },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediumType {
+    Homogeneous(Homogeneous),
+    Grid(Grid),
+}
+
+/// A volume that absorbs and scatters light travelling through it.
+pub trait Medium {
+    /// Samples how far `ray` gets before it scatters, if it does before `ray.at(t_max)`.
+    /// The weight is what the sample contributes, transmittance times the scattering
+    /// coefficient when it scatters, divided by the density of drawing it.
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample;
+
+    /// Share of the light at `ray.at(t_max)` that reaches the origin of `ray`.
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color;
+
+    fn phase(&self) -> HenyeyGreenstein;
+}
+
+impl Medium for MediumType {
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        match self {
+            MediumType::Homogeneous(medium) => medium.sample(ray, t_max),
+            MediumType::Grid(medium) => medium.sample(ray, t_max),
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        match self {
+            MediumType::Homogeneous(medium) => medium.transmittance(ray, t_max),
+            MediumType::Grid(medium) => medium.transmittance(ray, t_max),
+        }
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        match self {
+            MediumType::Homogeneous(medium) => medium.phase(),
+            MediumType::Grid(medium) => medium.phase(),
+        }
+    }
+}
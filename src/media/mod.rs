@@ -0,0 +1,4 @@
+pub mod grid;
+pub mod homogeneous;
+pub mod medium;
+pub mod phase;
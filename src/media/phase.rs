@@ -0,0 +1,49 @@
+use std::f32::consts::PI;
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    random::random_distribution,
+    utils::sampling::{ONB, UVW},
+};
+
+/// The Henyey–Greenstein phase function. `g` is the mean cosine between the directions
+/// before and after scattering: positive values scatter forwards, negative values
+/// backwards and zero evenly in every direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HenyeyGreenstein {
+    pub g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        HenyeyGreenstein {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    /// Density of scattering a ray travelling along `direction` into `scattered`,
+    /// per unit solid angle.
+    pub fn value(&self, direction: Vec3, scattered: Vec3) -> f32 {
+        let g = self.g;
+        let cosine = direction.normalize().dot(scattered.normalize());
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+
+    /// A unit direction scattered from `direction`, distributed like `value`.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let g = self.g;
+        let cosine = if g.abs() < 1e-3 {
+            1.0 - 2.0 * random_distribution()
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * random_distribution());
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_distribution();
+
+        ONB::build_from_w(direction).local(vec3(sine * phi.cos(), sine * phi.sin(), cosine))
+    }
+}
@@ -1,87 +1,175 @@
-// use crate::{
-//     materials::material::MaterialType,
-//     utils::aabb::Aabb,
-//     world::physics::{Intersection, Ray},
-// };
-
-// use glam::Vec3;
-// use std::f32::consts::PI;
-
-// use super::object::{Bounded, Geometry, ObjectType};
-
-// pub struct Cube {
-//     pub material: MaterialType,
-//     pub center: Vec3,
-//     pub radius: f32,
-//     pub node_index: usize,
-// }
-
-// impl Cube {
-//     pub fn new(center: Vec3, radius: f32, material: MaterialType) -> ObjectType {
-//         ObjectType::Sphere(Sphere {
-//             center,
-//             radius,
-//             material,
-//             node_index: 0,
-//         })
-//     }
-// }
-
-// impl Bounded for Cube {
-//     fn bounding_box(&self) -> Option<Aabb> {
-//         Some(Aabb {
-//             min: self.center - Vec3::new(self.radius, self.radius, self.radius),
-//             max: self.center + Vec3::new(self.radius, self.radius, self.radius),
-//         })
-//     }
-// }
-
-// impl Geometry for Cube {
-//     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
-//         let oc = ray.origin - self.center;
-//         let a = ray.direction.length_squared();
-
-//         let half_b = oc.dot(ray.direction);
-//         let c = oc.length_squared() - self.radius * self.radius;
-//         let discriminant = half_b * half_b - a * c;
-
-//         if discriminant < 0.0 {
-//             return None;
-//         }
-
-//         let sqrtd = discriminant.sqrt();
-//         let mut root = (-half_b - sqrtd) / a;
-//         if root < t_min || t_max < root {
-//             root = (-half_b + sqrtd) / a;
-//             if root < t_min || t_max < root {
-//                 return None;
-//             }
-//         }
-
-//         let point = ray.at(root);
-//         let outward_normal = self.outward_normal(point);
-
-//         Some(Intersection::new(
-//             root,
-//             point,
-//             self.surface_normal(point, ray),
-//             outward_normal,
-//             self.material,
-//             self.surface_uv(outward_normal),
-//         ))
-//     }
-
-//     fn surface_normal(&self, p: Vec3, _ray: &Ray) -> Vec3 {
-//         (p - self.center).normalize()
-//     }
-
-//     fn outward_normal(&self, p: Vec3) -> Vec3 {
-//         (p - self.center) / self.radius
-//     }
-
-//     fn surface_uv(&self, outward_normal: Vec3) -> (f32, f32) {
-//         let phi = (-outward_normal.z).atan2(outward_normal.x) + PI;
-//         let theta = (-outward_normal.y).acos();
-//         (phi / (2.0 * PI), theta / PI)
-//     }
-// }
+use crate::{
+    materials::material::MaterialType,
+    random::{random_distribution, random_float},
+    utils::{aabb::Aabb, sampling::PdfReady},
+    world::physics::{Intersection, Ray},
+};
+
+use glam::Vec3;
+
+use super::object::{Bounded, Geometry, ObjectType};
+
+/// An axis aligned box, closed so it can hold a medium.
+#[derive(Clone)]
+pub struct Cube {
+    material: MaterialType,
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Cube {
+    pub fn new(min: Vec3, max: Vec3, material: MaterialType) -> ObjectType {
+        ObjectType::Cube(Cube {
+            material,
+            min: min.min(max),
+            max: min.max(max),
+        })
+    }
+
+    /// Where the line through `ray` enters and leaves the box, in units of the ray.
+    fn slabs(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let inverse = ray.direction.recip();
+        let t0 = (self.min - ray.origin) * inverse;
+        let t1 = (self.max - ray.origin) * inverse;
+        let (near, far) = (t0.min(t1).max_element(), t0.max(t1).min_element());
+
+        (near <= far).then_some((near, far))
+    }
+
+    /// Axis of the face `point` lies on.
+    fn face(&self, point: Vec3) -> usize {
+        let size = self.max - self.min;
+        let offset = ((point - self.min).min(self.max - point) / size).abs();
+        if offset.x <= offset.y && offset.x <= offset.z {
+            0
+        } else if offset.y <= offset.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl Bounded for Cube {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+impl Geometry for Cube {
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>> {
+        let root = self.hit_distance(ray, t_min, t_max)?;
+        let point = ray.at(root);
+        let outward_normal = self.outward_normal(point);
+
+        Some(Intersection::new(
+            root,
+            point,
+            self.surface_normal(point, ray),
+            outward_normal,
+            &self.material,
+            self.surface_uv(point),
+        ))
+    }
+
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let (near, far) = self.slabs(ray)?;
+
+        [near, far]
+            .into_iter()
+            .find(|root| (t_min..=t_max).contains(root))
+    }
+
+    fn surface_normal(&self, p: Vec3, _ray: &Ray) -> Vec3 {
+        self.outward_normal(p)
+    }
+
+    fn outward_normal(&self, p: Vec3) -> Vec3 {
+        let axis = self.face(p);
+        let mut normal = Vec3::ZERO;
+        normal[axis] = if p[axis] - self.min[axis] < self.max[axis] - p[axis] {
+            -1.0
+        } else {
+            1.0
+        };
+        normal
+    }
+
+    fn material(&self) -> &MaterialType {
+        &self.material
+    }
+
+    /// Coordinates across the face `point` lies on, each face covering the unit square.
+    fn surface_uv(&self, point: Vec3) -> (f32, f32) {
+        let axis = self.face(point);
+        let local = (point - self.min) / (self.max - self.min);
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        (local[a], local[b])
+    }
+}
+
+impl PdfReady for Cube {
+    /// Points are spread over the whole surface, so a direction that crosses the box can
+    /// be drawn through either the face it enters or the one it leaves by.
+    fn pdf_value(&self, o: Vec3, v: Vec3) -> f32 {
+        let ray = Ray::new(o, v);
+        let (near, far) = match self.slabs(&ray) {
+            Some(slabs) => slabs,
+            None => return 0.0,
+        };
+
+        [near, far]
+            .into_iter()
+            .filter(|root| *root > 0.001)
+            .map(|root| {
+                let normal = self.outward_normal(ray.at(root));
+                let cosine = normal.dot(v).abs() / v.length();
+                let distance_squared = root.powi(2) * v.length_squared();
+                if cosine > 0.0 {
+                    distance_squared / (cosine * self.area())
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    fn random(&self, o: Vec3) -> Vec3 {
+        self.random_point().0 - o
+    }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        let size = self.max - self.min;
+        let faces = [size.y * size.z, size.z * size.x, size.x * size.y];
+
+        // Pick a face by its area, then a side and a point on it.
+        let mut pick = random_distribution() * (faces[0] + faces[1] + faces[2]);
+        let mut axis = 0;
+        while axis < 2 && pick >= faces[axis] {
+            pick -= faces[axis];
+            axis += 1;
+        }
+        let mut point = Vec3::new(
+            random_float(self.min.x, self.max.x),
+            random_float(self.min.y, self.max.y),
+            random_float(self.min.z, self.max.z),
+        );
+        let mut normal = Vec3::ZERO;
+        if random_distribution() < 0.5 {
+            point[axis] = self.min[axis];
+            normal[axis] = -1.0;
+        } else {
+            point[axis] = self.max[axis];
+            normal[axis] = 1.0;
+        }
+
+        (point, normal)
+    }
+
+    fn area(&self) -> f32 {
+        self.bounding_box().map_or(0.0, |aabb| aabb.surface_area())
+    }
+}
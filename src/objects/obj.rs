@@ -1,13 +1,28 @@
 use super::{object::ObjectType, triangle::Triangle};
-use crate::materials::material::MaterialType;
+use crate::{
+    materials::{
+        material::MaterialType,
+        principled::Principled,
+        texture::{Image, PixelMap, SolidColor},
+    },
+    utils::Color,
+};
 use glam::Vec3;
-use std::path::Path;
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
 
 extern crate tobj;
 
-pub fn load_obj(path: &Path, origin: Vec3, scale: f32, material: MaterialType) -> Vec<ObjectType> {
-    let obj = tobj::load_obj(path, &tobj::LoadOptions::default());
-    let (models, _mtls) = obj.unwrap();
+pub fn load_obj(
+    path: &Path,
+    origin: Vec3,
+    scale: f32,
+    material: MaterialType,
+) -> Result<Vec<ObjectType>, tobj::LoadError> {
+    let (models, _mtls) = tobj::load_obj(path, &tobj::LoadOptions::default())?;
     let mut tris: Vec<ObjectType> = Vec::new();
 
     for m in models.iter() {
@@ -32,11 +47,11 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, material: MaterialType) -
                 (mesh.positions[i2 * 3 + 2] * scale) + origin.z,
             );
 
-            tris.push(Triangle::new(v0, v1, v2, material));
+            tris.push(Triangle::new(v0, v1, v2, material.clone()));
         }
     }
 
-    tris
+    Ok(tris)
 }
 
 pub fn load_obj_spec(
@@ -44,9 +59,8 @@ pub fn load_obj_spec(
     origin: Vec3,
     scale: f32,
     material: MaterialType,
-) -> Vec<ObjectType> {
-    let obj = tobj::load_obj(path, &tobj::LoadOptions::default());
-    let (models, _mtls) = obj.unwrap();
+) -> Result<Vec<ObjectType>, tobj::LoadError> {
+    let (models, _mtls) = tobj::load_obj(path, &tobj::LoadOptions::default())?;
     let mut tris: Vec<ObjectType> = Vec::new();
 
     for m in models.iter() {
@@ -71,9 +85,203 @@ pub fn load_obj_spec(
                 (mesh.positions[i2 * 3 + 1] * scale) + origin.y,
             );
 
-            tris.push(Triangle::new(v0, v1, v2, material));
+            tris.push(Triangle::new(v0, v1, v2, material.clone()));
+        }
+    }
+
+    Ok(tris)
+}
+
+/// Triangles of an OBJ file with the materials of its MTL library.
+pub struct MtlMesh {
+    /// Every triangle with the index of its material.
+    pub triangles: Vec<(ObjectType, usize)>,
+    pub materials: Vec<MaterialType>,
+}
+
+/// Why `load_obj_mtl` couldn't load a mesh.
+#[derive(Debug)]
+pub enum MtlError {
+    Obj(tobj::LoadError),
+    Library(tobj::LoadError),
+    Texture {
+        path: PathBuf,
+        material: String,
+        source: image::ImageError,
+    },
+}
+
+impl fmt::Display for MtlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MtlError::Obj(err) => write!(f, "{}", err),
+            MtlError::Library(err) => write!(f, "MTL library: {}", err),
+            MtlError::Texture {
+                path,
+                material,
+                source,
+            } => write!(
+                f,
+                "texture {} of material {}: {}",
+                path.display(),
+                material,
+                source
+            ),
+        }
+    }
+}
+
+impl Error for MtlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MtlError::Obj(err) | MtlError::Library(err) => Some(err),
+            MtlError::Texture { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Loads an OBJ file with the materials of its MTL library, each turned into a
+/// `Principled` material, splitting polygons into triangles. Faces without a
+/// material are a plain grey. A library or texture that can't be read fails the
+/// whole mesh.
+pub fn load_obj_mtl(path: &Path, origin: Vec3, scale: f32) -> Result<MtlMesh, MtlError> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, mtls) = tobj::load_obj(path, &options).map_err(MtlError::Obj)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials = mtls
+        .map_err(MtlError::Library)?
+        .iter()
+        .map(|mtl| principled(mtl, dir))
+        .collect::<Result<Vec<_>, _>>()?;
+    let fallback = materials.len();
+    materials.push(Principled::new(SolidColor::new(Color::splat(0.8), None)).into());
+
+    let mut tris = Vec::new();
+    for m in models.iter() {
+        let mesh = &m.mesh;
+        let material = mesh
+            .material_id
+            .filter(|&id| id < fallback)
+            .unwrap_or(fallback);
+        let vertex = |i: usize| {
+            let index = mesh.indices[i] as usize;
+            Vec3::new(
+                mesh.positions[index * 3],
+                mesh.positions[index * 3 + 1],
+                mesh.positions[index * 3 + 2],
+            ) * scale
+                + origin
+        };
+        let texcoord = |i: usize| {
+            let index = mesh.texcoord_indices[i] as usize;
+            (mesh.texcoords[index * 2], mesh.texcoords[index * 2 + 1])
+        };
+
+        for f in 0..mesh.indices.len() / 3 {
+            let (v0, v1, v2) = (vertex(3 * f), vertex(3 * f + 1), vertex(3 * f + 2));
+            let tri = if mesh.texcoord_indices.is_empty() {
+                Triangle::new(v0, v1, v2, materials[material].clone())
+            } else {
+                let texcoords = [texcoord(3 * f), texcoord(3 * f + 1), texcoord(3 * f + 2)];
+                Triangle::textured(v0, v1, v2, texcoords, materials[material].clone())
+            };
+            tris.push((tri, material));
         }
     }
 
-    tris
+    Ok(MtlMesh {
+        triangles: tris,
+        materials,
+    })
+}
+
+/// A `Principled` material from an MTL one. The PBR extension (`Pm`, `Pr`, `Ps` for
+/// sheen, `Pc`, `Ke` and their `map_` textures) is read when present, otherwise the
+/// roughness comes from the Phong exponent `Ns` the way Blender writes it. `Ks` or
+/// `map_Ks` is the specular level with its channels averaged, as MTL has nothing for
+/// `specular_tint`, which stays 0. Illumination models 4, 6, 7 and 9 are glass,
+/// transmitting what the dissolve `d` or `map_d` lets through; other models are
+/// opaque, as cutouts aren't supported. Textures are resolved against `dir`. Color
+/// maps are decoded from sRGB while the single value ones are read as stored. Bump
+/// and normal maps are skipped, as they need tangent space shading.
+fn principled(mtl: &tobj::Material, dir: &Path) -> Result<MaterialType, MtlError> {
+    // Options like `-bm 1.0` come before the file name.
+    let file = |map: &str| map.split_whitespace().last().map(|file| dir.join(file));
+    let value = |key: &str| {
+        let values: Vec<f32> = mtl
+            .unknown_param
+            .get(key)?
+            .split_whitespace()
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        match values[..] {
+            [value] => Some(Color::splat(value)),
+            [r, g, b, ..] => Some(Color::new(r, g, b)),
+            _ => None,
+        }
+    };
+    let image = |map: &str, load: fn(&Path) -> image::ImageResult<PixelMap>| {
+        file(map)
+            .map(|path| {
+                load(&path).map_err(|source| MtlError::Texture {
+                    path,
+                    material: mtl.name.clone(),
+                    source,
+                })
+            })
+            .transpose()
+    };
+    let texture = |key: &str, default: Color, load| {
+        let map = match mtl.unknown_param.get(&format!("map_{}", key)) {
+            Some(map) => image(map, load)?,
+            None => None,
+        };
+        Ok(match map {
+            Some(image) => Image::from_pixel_map(image, None),
+            None => SolidColor::new(value(key).unwrap_or(default), None),
+        })
+    };
+
+    let base_color = match image(&mtl.diffuse_texture, PixelMap::load_srgb)? {
+        Some(image) => Image::from_pixel_map(image, None),
+        None => SolidColor::new(Color::from(mtl.diffuse), None),
+    };
+
+    let roughness = 1.0 - (mtl.shininess / 1000.0).clamp(0.0, 1.0).sqrt();
+    let specular = match image(&mtl.specular_texture, PixelMap::load)? {
+        Some(image) => Image::from_pixel_map(image, None),
+        None => SolidColor::new(Color::splat(mtl.specular.iter().sum::<f32>() / 3.0), None),
+    };
+    // `d` is the opacity, so its map is inverted into transmission.
+    let clear = |path: &Path| -> image::ImageResult<PixelMap> {
+        let mut image = image::open(path)?;
+        image.invert();
+        Ok(PixelMap::decode(image))
+    };
+    let transmission = match mtl.illumination_model {
+        Some(4 | 6 | 7 | 9) => match image(&mtl.dissolve_texture, clear)? {
+            Some(image) => Image::from_pixel_map(image, None),
+            None => SolidColor::new(Color::splat(1.0 - mtl.dissolve), None),
+        },
+        _ => SolidColor::new(Color::ZERO, None),
+    };
+    let ior = if mtl.optical_density > 1.0 {
+        mtl.optical_density
+    } else {
+        1.45
+    };
+
+    Ok(Principled::new(base_color)
+        .metallic(texture("Pm", Color::ZERO, PixelMap::load)?)
+        .roughness(texture("Pr", Color::splat(roughness), PixelMap::load)?)
+        .specular(specular)
+        .sheen(texture("Ps", Color::ZERO, PixelMap::load)?)
+        .clearcoat(texture("Pc", Color::ZERO, PixelMap::load)?)
+        .transmission(transmission)
+        .ior(ior)
+        .emission(texture("Ke", Color::ZERO, PixelMap::load_srgb)?)
+        .into())
 }
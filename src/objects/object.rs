@@ -1,11 +1,12 @@
 use glam::Vec3;
 
 use crate::{
+    materials::material::MaterialType,
     utils::{aabb::Aabb, sampling::PdfReady},
     world::physics::{Intersection, Ray},
 };
 
-use super::{plane::Plane, rotated::Rotated, sphere::Sphere, triangle::Triangle};
+use super::{cube::Cube, plane::Plane, rotated::Rotated, sphere::Sphere, triangle::Triangle};
 
 #[derive(Clone)]
 pub enum ObjectType {
@@ -13,13 +14,18 @@ pub enum ObjectType {
     Plane(Plane),
     Triangle(Triangle),
     Rotated(Rotated),
+    Cube(Cube),
 }
 
 pub trait Geometry {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection>;
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>>;
+    /// Distance to the closest hit in `t_min..t_max`, without the rest of the
+    /// `Intersection`. Enough for shadow rays and light pdfs.
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32>;
     fn surface_normal(&self, p: Vec3, r: &Ray) -> Vec3;
     fn surface_uv(&self, outward_normal: Vec3) -> (f32, f32);
     fn outward_normal(&self, p: Vec3) -> Vec3;
+    fn material(&self) -> &MaterialType;
 }
 
 pub trait Bounded {
@@ -27,12 +33,23 @@ pub trait Bounded {
 }
 
 impl Geometry for ObjectType {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>> {
         match self {
             ObjectType::Sphere(obj) => obj.intersects(ray, t_min, t_max),
             ObjectType::Plane(obj) => obj.intersects(ray, t_min, t_max),
             ObjectType::Triangle(obj) => obj.intersects(ray, t_min, t_max),
             ObjectType::Rotated(obj) => obj.intersects(ray, t_min, t_max),
+            ObjectType::Cube(obj) => obj.intersects(ray, t_min, t_max),
+        }
+    }
+
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        match self {
+            ObjectType::Sphere(obj) => obj.hit_distance(ray, t_min, t_max),
+            ObjectType::Plane(obj) => obj.hit_distance(ray, t_min, t_max),
+            ObjectType::Triangle(obj) => obj.hit_distance(ray, t_min, t_max),
+            ObjectType::Rotated(obj) => obj.hit_distance(ray, t_min, t_max),
+            ObjectType::Cube(obj) => obj.hit_distance(ray, t_min, t_max),
         }
     }
 
@@ -42,6 +59,7 @@ impl Geometry for ObjectType {
             ObjectType::Plane(obj) => obj.surface_normal(p, r),
             ObjectType::Triangle(obj) => obj.surface_normal(p, r),
             ObjectType::Rotated(obj) => obj.surface_normal(p, r),
+            ObjectType::Cube(obj) => obj.surface_normal(p, r),
         }
     }
 
@@ -51,6 +69,7 @@ impl Geometry for ObjectType {
             ObjectType::Plane(obj) => obj.surface_uv(outward_normal),
             ObjectType::Triangle(obj) => obj.surface_uv(outward_normal),
             ObjectType::Rotated(obj) => obj.surface_uv(outward_normal),
+            ObjectType::Cube(obj) => obj.surface_uv(outward_normal),
         }
     }
 
@@ -60,6 +79,17 @@ impl Geometry for ObjectType {
             ObjectType::Plane(obj) => obj.outward_normal(p),
             ObjectType::Triangle(obj) => obj.outward_normal(p),
             ObjectType::Rotated(obj) => obj.outward_normal(p),
+            ObjectType::Cube(obj) => obj.outward_normal(p),
+        }
+    }
+
+    fn material(&self) -> &MaterialType {
+        match self {
+            ObjectType::Sphere(obj) => obj.material(),
+            ObjectType::Plane(obj) => obj.material(),
+            ObjectType::Triangle(obj) => obj.material(),
+            ObjectType::Rotated(obj) => obj.material(),
+            ObjectType::Cube(obj) => obj.material(),
         }
     }
 }
@@ -71,6 +101,7 @@ impl Bounded for ObjectType {
             ObjectType::Plane(obj) => obj.bounding_box(),
             ObjectType::Triangle(obj) => obj.bounding_box(),
             ObjectType::Rotated(obj) => obj.bounding_box(),
+            ObjectType::Cube(obj) => obj.bounding_box(),
         }
     }
 }
@@ -82,6 +113,7 @@ impl PdfReady for ObjectType {
             ObjectType::Plane(obj) => obj.pdf_value(o, v),
             ObjectType::Triangle(obj) => obj.pdf_value(o, v),
             ObjectType::Rotated(obj) => obj.pdf_value(o, v),
+            ObjectType::Cube(obj) => obj.pdf_value(o, v),
         }
     }
 
@@ -91,6 +123,27 @@ impl PdfReady for ObjectType {
             ObjectType::Plane(obj) => obj.random(o),
             ObjectType::Triangle(obj) => obj.random(o),
             ObjectType::Rotated(obj) => obj.random(o),
+            ObjectType::Cube(obj) => obj.random(o),
+        }
+    }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        match self {
+            ObjectType::Sphere(obj) => obj.random_point(),
+            ObjectType::Plane(obj) => obj.random_point(),
+            ObjectType::Triangle(obj) => obj.random_point(),
+            ObjectType::Rotated(obj) => obj.random_point(),
+            ObjectType::Cube(obj) => obj.random_point(),
+        }
+    }
+
+    fn area(&self) -> f32 {
+        match self {
+            ObjectType::Sphere(obj) => obj.area(),
+            ObjectType::Plane(obj) => obj.area(),
+            ObjectType::Triangle(obj) => obj.area(),
+            ObjectType::Rotated(obj) => obj.area(),
+            ObjectType::Cube(obj) => obj.area(),
         }
     }
 }
@@ -1,12 +1,12 @@
 use crate::{
     materials::material::MaterialType,
+    random::random_float,
     utils::{aabb::Aabb, sampling::PdfReady},
     world::physics::{Intersection, Ray},
 };
 
 use super::object::{Bounded, Geometry, ObjectType};
 use glam::Vec3;
-use rand::Rng;
 
 #[derive(Clone)]
 
@@ -58,7 +58,21 @@ impl Plane {
 }
 
 impl Geometry for Plane {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>> {
+        let t = self.hit_distance(ray, t_min, t_max)?;
+        let point = ray.at(t);
+
+        Some(Intersection::new(
+            t,
+            point,
+            self.surface_normal(point, ray),
+            point,
+            &self.material,
+            self.surface_uv(point),
+        ))
+    }
+
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
         let (k_axis, a_axis, b_axis) = Plane::get_axis(&self.plane_type);
         let t = (self.k - ray.origin[k_axis]) / ray.direction[k_axis];
 
@@ -70,18 +84,7 @@ impl Geometry for Plane {
             if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
                 None
             } else {
-                let point = ray.at(t);
-                let u = (a - self.a0) / (self.a1 - self.a0);
-                let v = (b - self.b0) / (self.b1 - self.b0);
-
-                Some(Intersection::new(
-                    t,
-                    point,
-                    self.surface_normal(point, ray),
-                    point,
-                    self.material,
-                    (u, v),
-                ))
+                Some(t)
             }
         }
     }
@@ -104,6 +107,10 @@ impl Geometry for Plane {
         point
     }
 
+    fn material(&self) -> &MaterialType {
+        &self.material
+    }
+
     fn surface_uv(&self, point: Vec3) -> (f32, f32) {
         let (_k_axis, a_axis, b_axis) = Plane::get_axis(&self.plane_type);
         (
@@ -134,10 +141,11 @@ impl Bounded for Plane {
 
 impl PdfReady for Plane {
     fn pdf_value(&self, o: Vec3, v: Vec3) -> f32 {
-        if let Some(hit) = self.intersects(&Ray::new(o, v), 0.001, f32::MAX) {
+        if let Some(distance) = self.hit_distance(&Ray::new(o, v), 0.001, f32::MAX) {
+            let (k_axis, _a_axis, _b_axis) = Plane::get_axis(&self.plane_type);
             let area = (self.a1 - self.a0) * (self.b1 - self.b0);
-            let distance_squared = hit.distance.powi(2) * v.length_squared();
-            let cosine = v.dot(hit.normal).abs() / v.length();
+            let distance_squared = distance.powi(2) * v.length_squared();
+            let cosine = v[k_axis].abs() / v.length();
             if cosine != 0.0 {
                 distance_squared / (cosine * area)
             } else {
@@ -149,12 +157,22 @@ impl PdfReady for Plane {
     }
 
     fn random(&self, o: Vec3) -> Vec3 {
-        let mut rng = rand::thread_rng();
         let (k_axis, a_axis, b_axis) = Plane::get_axis(&self.plane_type);
         let mut random_point = Vec3::ZERO;
-        random_point[a_axis] = rng.gen_range(self.a0..self.a1);
-        random_point[b_axis] = rng.gen_range(self.b0..self.b1);
+        random_point[a_axis] = random_float(self.a0, self.a1);
+        random_point[b_axis] = random_float(self.b0, self.b1);
         random_point[k_axis] = self.k;
         random_point - o
     }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        let (k_axis, _a_axis, _b_axis) = Plane::get_axis(&self.plane_type);
+        let mut normal = Vec3::ZERO;
+        normal[k_axis] = 1.0;
+        (self.random(Vec3::ZERO), normal)
+    }
+
+    fn area(&self) -> f32 {
+        (self.a1 - self.a0) * (self.b1 - self.b0)
+    }
 }
@@ -3,6 +3,7 @@ use std::f32::consts::PI;
 use glam::Vec3;
 
 use crate::{
+    materials::material::MaterialType,
     utils::{aabb::Aabb, sampling::PdfReady},
     world::physics::{Intersection, Ray},
 };
@@ -96,9 +97,35 @@ impl Rotated {
     }
 }
 
+impl Rotated {
+    /// Rotates `ray` into the space of the wrapped object.
+    fn to_object(&self, ray: &Ray) -> Ray {
+        let (_, a_axis, b_axis) = self.axis.get_axis();
+        let mut origin = ray.origin;
+        let mut direction = ray.direction;
+        origin[a_axis] = self.cos_theta * ray.origin[a_axis] + self.sin_theta * ray.origin[b_axis];
+        origin[b_axis] = -self.sin_theta * ray.origin[a_axis] + self.cos_theta * ray.origin[b_axis];
+        direction[a_axis] =
+            self.cos_theta * ray.direction[a_axis] + self.sin_theta * ray.direction[b_axis];
+        direction[b_axis] =
+            -self.sin_theta * ray.direction[a_axis] + self.cos_theta * ray.direction[b_axis];
+
+        Ray::new(origin, direction)
+    }
+
+    /// Rotates a point or direction of the wrapped object into world space.
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        let (_, a_axis, b_axis) = self.axis.get_axis();
+        let mut rotated = v;
+        rotated[a_axis] = self.cos_theta * v[a_axis] - self.sin_theta * v[b_axis];
+        rotated[b_axis] = self.sin_theta * v[a_axis] + self.cos_theta * v[b_axis];
+        rotated
+    }
+}
+
 impl Bounded for Rotated {
     fn bounding_box(&self) -> Option<Aabb> {
-        return self.bbox;
+        self.bbox
     }
 }
 
@@ -108,34 +135,20 @@ impl Geometry for Rotated {
         ray: &crate::world::physics::Ray,
         t_min: f32,
         t_max: f32,
-    ) -> Option<Intersection> {
-        let (_, a_axis, b_axis) = self.axis.get_axis();
-        let mut origin = ray.origin;
-        let mut direction = ray.direction;
-        origin[a_axis] = self.cos_theta * ray.origin[a_axis] + self.sin_theta * ray.origin[b_axis];
-        origin[b_axis] = -self.sin_theta * ray.origin[a_axis] + self.cos_theta * ray.origin[b_axis];
-        direction[a_axis] =
-            self.cos_theta * ray.direction[a_axis] + self.sin_theta * ray.direction[b_axis];
-        direction[b_axis] =
-            -self.sin_theta * ray.direction[a_axis] + self.cos_theta * ray.direction[b_axis];
-
+    ) -> Option<Intersection<'_>> {
         self.object
-            .intersects(&Ray::new(origin, direction), t_min, t_max)
+            .intersects(&self.to_object(ray), t_min, t_max)
             .map(|mut hit| {
-                let mut p = hit.point;
-                let mut normal = hit.normal;
-                p[a_axis] = self.cos_theta * hit.point[a_axis] - self.sin_theta * hit.point[b_axis];
-                p[b_axis] = self.sin_theta * hit.point[a_axis] + self.cos_theta * hit.point[b_axis];
-                normal[a_axis] =
-                    self.cos_theta * hit.normal[a_axis] - self.sin_theta * hit.normal[b_axis];
-                normal[b_axis] =
-                    self.sin_theta * hit.normal[a_axis] + self.cos_theta * hit.normal[b_axis];
-                hit.point = p;
-                hit.normal = normal;
+                hit.point = self.to_world(hit.point);
+                hit.normal = self.to_world(hit.normal);
                 hit
             })
     }
 
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        self.object.hit_distance(&self.to_object(ray), t_min, t_max)
+    }
+
     fn surface_normal(&self, p: Vec3, r: &Ray) -> Vec3 {
         self.object.surface_normal(p, r)
     }
@@ -147,6 +160,10 @@ impl Geometry for Rotated {
     fn outward_normal(&self, p: Vec3) -> Vec3 {
         self.object.outward_normal(p)
     }
+
+    fn material(&self) -> &MaterialType {
+        self.object.material()
+    }
 }
 
 impl PdfReady for Rotated {
@@ -157,4 +174,13 @@ impl PdfReady for Rotated {
     fn random(&self, o: Vec3) -> Vec3 {
         self.object.random(o)
     }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        let (point, normal) = self.object.random_point();
+        (self.to_world(point), self.to_world(normal))
+    }
+
+    fn area(&self) -> f32 {
+        self.object.area()
+    }
 }
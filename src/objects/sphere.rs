@@ -1,5 +1,6 @@
 use crate::{
     materials::material::MaterialType,
+    random::{random_distribution, random_sphere_distribution},
     utils::{
         aabb::Aabb,
         sampling::{PdfReady, ONB, UVW},
@@ -8,7 +9,6 @@ use crate::{
 };
 
 use glam::Vec3;
-use rand::Rng;
 use std::f32::consts::PI;
 
 use super::object::{Bounded, Geometry, ObjectType};
@@ -42,7 +42,22 @@ impl Bounded for Sphere {
 }
 
 impl Geometry for Sphere {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>> {
+        let root = self.hit_distance(ray, t_min, t_max)?;
+        let point = ray.at(root);
+        let outward_normal = self.outward_normal(point);
+
+        Some(Intersection::new(
+            root,
+            point,
+            self.surface_normal(point, ray),
+            outward_normal,
+            &self.material,
+            self.surface_uv(outward_normal),
+        ))
+    }
+
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
         let oc = ray.origin - self.center;
         let a = ray.direction.length_squared();
 
@@ -63,17 +78,7 @@ impl Geometry for Sphere {
             }
         }
 
-        let point = ray.at(root);
-        let outward_normal = self.outward_normal(point);
-
-        Some(Intersection::new(
-            root,
-            point,
-            self.surface_normal(point, ray),
-            outward_normal,
-            self.material,
-            self.surface_uv(outward_normal),
-        ))
+        Some(root)
     }
 
     fn surface_normal(&self, p: Vec3, _ray: &Ray) -> Vec3 {
@@ -84,6 +89,10 @@ impl Geometry for Sphere {
         (p - self.center) / self.radius
     }
 
+    fn material(&self) -> &MaterialType {
+        &self.material
+    }
+
     fn surface_uv(&self, outward_normal: Vec3) -> (f32, f32) {
         let phi = (-outward_normal.z).atan2(outward_normal.x) + PI;
         let theta = (-outward_normal.y).acos();
@@ -93,7 +102,10 @@ impl Geometry for Sphere {
 
 impl PdfReady for Sphere {
     fn pdf_value(&self, o: Vec3, v: Vec3) -> f32 {
-        if let Some(_) = self.intersects(&Ray::new(o, v), 0.001, f32::MAX) {
+        if self
+            .hit_distance(&Ray::new(o, v), 0.001, f32::MAX)
+            .is_some()
+        {
             let cos_theta_max =
                 (1.0 - self.radius.powi(2) / (self.center - o).length_squared()).sqrt();
             let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
@@ -108,12 +120,20 @@ impl PdfReady for Sphere {
         let uvw = ONB::build_from_w(direction);
         uvw.local(random_to_sphere(self.radius, direction.length_squared()))
     }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        let normal = random_sphere_distribution().normalize();
+        (self.center + self.radius * normal, normal)
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius.powi(2)
+    }
 }
 
 fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
-    let mut rng = rand::thread_rng();
-    let r1 = rng.gen::<f32>();
-    let r2 = rng.gen::<f32>();
+    let r1 = random_distribution();
+    let r2 = random_distribution();
     let z = 1.0 + r2 * ((1.0 - radius.powi(2) / distance_squared).sqrt() - 1.0);
     let phi = 2.0 * PI * r1;
     let x = phi.cos() * (1.0 - z.powi(2)).sqrt();
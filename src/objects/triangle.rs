@@ -1,11 +1,11 @@
 use crate::{
     materials::material::MaterialType,
+    random::random_distribution,
     utils::{aabb::Aabb, sampling::PdfReady},
     world::physics::{Intersection, Ray},
 };
 
 use glam::Vec3;
-use rand::Rng;
 
 use super::object::{Bounded, Geometry, ObjectType};
 
@@ -15,40 +15,39 @@ pub struct Triangle {
     v1: Vec3,
     v2: Vec3,
     normal: Vec3,
+    /// Texture coordinates at each vertex.
+    texcoords: [(f32, f32); 3],
     material: MaterialType,
 }
 
 impl Triangle {
+    /// A triangle whose texture coordinates are its barycentric coordinates.
     pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: MaterialType) -> ObjectType {
+        Triangle::textured(v0, v1, v2, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], material)
+    }
+
+    /// A triangle with the texture coordinates of a mesh at its vertices.
+    pub fn textured(
+        v0: Vec3,
+        v1: Vec3,
+        v2: Vec3,
+        texcoords: [(f32, f32); 3],
+        material: MaterialType,
+    ) -> ObjectType {
         ObjectType::Triangle(Triangle {
             v0,
             v1,
             v2,
             normal: (v1 - v0).cross(v2 - v0).normalize(),
+            texcoords,
             material,
         })
     }
 }
 
-impl Bounded for Triangle {
-    fn bounding_box(&self) -> Option<Aabb> {
-        Some(Aabb {
-            min: Vec3::new(
-                self.v0.x.min(self.v1.x.min(self.v2.x)),
-                self.v0.y.min(self.v1.y.min(self.v2.y)),
-                self.v0.z.min(self.v1.z.min(self.v2.z)),
-            ),
-            max: Vec3::new(
-                self.v0.x.max(self.v1.x.max(self.v2.x)),
-                self.v0.y.max(self.v1.y.max(self.v2.y)),
-                self.v0.z.max(self.v1.z.max(self.v2.z)),
-            ),
-        })
-    }
-}
-
-impl Geometry for Triangle {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+impl Triangle {
+    /// Möller–Trumbore test, returning the distance and barycentric coordinates.
+    fn barycentric(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
         let v0v1 = self.v1 - self.v0;
         let v0v2 = self.v2 - self.v0;
         let pvec = ray.direction.cross(v0v2);
@@ -61,7 +60,7 @@ impl Geometry for Triangle {
 
         let tvec = ray.origin - self.v0;
         let u = tvec.dot(pvec) * inv_det;
-        if u < 0. || u > 1. {
+        if !(0. ..=1.).contains(&u) {
             return None;
         }
 
@@ -77,26 +76,64 @@ impl Geometry for Triangle {
             return None;
         }
 
+        Some((t, u, v))
+    }
+}
+
+impl Bounded for Triangle {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: Vec3::new(
+                self.v0.x.min(self.v1.x.min(self.v2.x)),
+                self.v0.y.min(self.v1.y.min(self.v2.y)),
+                self.v0.z.min(self.v1.z.min(self.v2.z)),
+            ),
+            max: Vec3::new(
+                self.v0.x.max(self.v1.x.max(self.v2.x)),
+                self.v0.y.max(self.v1.y.max(self.v2.y)),
+                self.v0.z.max(self.v1.z.max(self.v2.z)),
+            ),
+        })
+    }
+}
+
+impl Geometry for Triangle {
+    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'_>> {
+        let (t, u, v) = self.barycentric(ray, t_min, t_max)?;
         let p = ray.at(t);
+        let [t0, t1, t2] = self.texcoords;
+        let w = 1.0 - u - v;
 
-        return Some(Intersection {
+        Some(Intersection {
             distance: t,
             point: p,
             normal: self.normal,
             outward_normal: self.outward_normal(p),
-            uv: (u, v),
-            material: self.material,
-        });
+            uv: (
+                w * t0.0 + u * t1.0 + v * t2.0,
+                w * t0.1 + u * t1.1 + v * t2.1,
+            ),
+            material: &self.material,
+            object: 0,
+        })
+    }
+
+    fn hit_distance(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        self.barycentric(ray, t_min, t_max).map(|(t, _, _)| t)
     }
 
     fn surface_normal(&self, _p: Vec3, _ray: &Ray) -> Vec3 {
         self.normal
     }
 
-    fn outward_normal(&self, p: Vec3) -> Vec3 {
+    fn outward_normal(&self, _p: Vec3) -> Vec3 {
         self.normal
     }
 
+    fn material(&self) -> &MaterialType {
+        &self.material
+    }
+
     fn surface_uv(&self, _outward_normal: Vec3) -> (f32, f32) {
         (0.0, 0.0)
     }
@@ -104,7 +141,10 @@ impl Geometry for Triangle {
 
 impl PdfReady for Triangle {
     fn pdf_value(&self, o: Vec3, v: Vec3) -> f32 {
-        if let Some(_) = self.intersects(&Ray::new(o, v), 0.001, f32::MAX) {
+        if self
+            .hit_distance(&Ray::new(o, v), 0.001, f32::MAX)
+            .is_some()
+        {
             // from https://ieeexplore.ieee.org/stamp/stamp.jsp?tp=&arnumber=4121581
             let r1 = self.v0 - o;
             let r2 = self.v1 - o;
@@ -128,9 +168,8 @@ impl PdfReady for Triangle {
 
     fn random(&self, o: Vec3) -> Vec3 {
         // From https://math.stackexchange.com/questions/18686/uniform-random-point-in-triangle-in-3d
-        let mut rng = rand::thread_rng();
-        let r1 = rng.gen::<f32>();
-        let r2 = rng.gen::<f32>();
+        let r1 = random_distribution();
+        let r2 = random_distribution();
 
         let ca = 1.0 - r1.sqrt();
         let cb = r1.sqrt() * (1.0 - r2);
@@ -138,4 +177,12 @@ impl PdfReady for Triangle {
 
         (self.v0 * ca + self.v1 * cb + self.v2 * cc) - o
     }
+
+    fn random_point(&self) -> (Vec3, Vec3) {
+        (self.random(Vec3::ZERO), self.normal)
+    }
+
+    fn area(&self) -> f32 {
+        0.5 * (self.v1 - self.v0).cross(self.v2 - self.v0).length()
+    }
 }
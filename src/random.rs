@@ -1,33 +1,40 @@
-use std::f32::consts::PI;
+use std::{cell::RefCell, f32::consts::PI};
 
 use glam::Vec3;
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Reseeds the random generator of the calling thread. Every sampling routine in the
+/// tracer draws from this generator, so seeding it before each pixel makes a render
+/// reproducible regardless of how the work is split between threads.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
 
 pub fn random_int(i: u32, j: u32) -> u32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(i..j)
+    RNG.with(|rng| rng.borrow_mut().gen_range(i..j))
 }
 
 pub fn random_float(i: f32, j: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(i..j)
+    RNG.with(|rng| rng.borrow_mut().gen_range(i..j))
 }
 
 pub fn random_distribution() -> f32 {
-    rand::random()
-    // let mut rng = rand::thread_rng();
-    // BETWEEN.sample(&mut rng)
+    RNG.with(|rng| rng.borrow_mut().gen())
 }
 
 pub fn random_sphere_distribution() -> Vec3 {
     let r1 = random_distribution();
     let r2 = random_distribution();
 
-    return Vec3::new(
+    Vec3::new(
         (2.0 * PI * r1).cos() * 2.0 * (r2 * (1.0 - r2)).sqrt(),
         (2.0 * PI * r1).sin() * 2.0 * (r2 * (1.0 - r2)).sqrt(),
         1.0 - (2.0 * r2),
-    );
+    )
 
     // loop {
     //     let p = Vec3::new(
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+pub type Triple = [f32; 3];
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub render: RenderDescription,
+    pub skybox: Spanned<SkyboxDescription>,
+    pub camera: Spanned<CameraDescription>,
+    /// Name of the medium filling the space around objects.
+    pub atmosphere: Option<Spanned<String>>,
+    #[serde(default)]
+    pub media: HashMap<String, Spanned<MediumDescription>>,
+    #[serde(default)]
+    pub textures: HashMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    pub materials: HashMap<String, Spanned<MaterialDescription>>,
+    #[serde(default)]
+    pub spheres: Vec<SphereDescription>,
+    #[serde(default)]
+    pub planes: Vec<PlaneDescription>,
+    #[serde(default)]
+    pub meshes: Vec<MeshDescription>,
+    #[serde(default)]
+    pub volumes: Vec<VolumeDescription>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RenderDescription {
+    pub width: Option<Spanned<u32>>,
+    pub samples_per_pixel: Option<Spanned<u32>>,
+    /// Shorthand for the same limit on every kind of bounce.
+    pub max_depth: Option<u32>,
+    pub diffuse_bounces: Option<u32>,
+    pub specular_bounces: Option<u32>,
+    pub transmission_bounces: Option<u32>,
+    pub tone_map: Option<ToneMapKind>,
+    pub exposure: Option<f32>,
+    #[serde(default)]
+    pub aovs: Vec<AovKind>,
+    #[serde(default)]
+    pub denoise: bool,
+    pub bvh: Option<BvhKind>,
+    pub max_leaf_size: Option<usize>,
+    pub integrator: Option<IntegratorKind>,
+    pub mis: Option<HeuristicKind>,
+    /// Photons traced per pass by the photon mapping integrator.
+    pub photons: Option<usize>,
+    /// Gather radius of the first photon mapping pass.
+    pub photon_radius: Option<f32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    Mixture,
+    Path,
+    Bidirectional,
+    PhotonMapping,
+    Normals,
+    Albedo,
+    Uv,
+    Depth,
+    BvhCost,
+    AmbientOcclusion,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HeuristicKind {
+    Balance,
+    Power,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BvhKind {
+    Median,
+    Sah,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AovKind {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    Uv,
+    ObjectId,
+    MaterialId,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapKind {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SkyboxDescription {
+    pub color: Option<Triple>,
+    pub image: Option<String>,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub look_from: Triple,
+    pub look_at: Triple,
+    #[serde(default = "default_up")]
+    pub up: Triple,
+    pub vfov: f32,
+    #[serde(default = "default_aspect_ratio")]
+    pub aspect_ratio: f32,
+    #[serde(default)]
+    pub aperture: f32,
+    pub focus_distance: Option<f32>,
+}
+
+fn default_up() -> Triple {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_aspect_ratio() -> f32 {
+    4.0 / 3.0
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureKind {
+    Solid,
+    Checker,
+    Image,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TextureDescription {
+    #[serde(rename = "type")]
+    pub kind: TextureKind,
+    pub color: Option<Triple>,
+    pub color_1: Option<Triple>,
+    pub color_2: Option<Triple>,
+    pub scale: Option<f32>,
+    pub path: Option<String>,
+    pub bump_map: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterialKind {
+    Lambertian,
+    Metal,
+    Glossy,
+    Conductor,
+    Dielectric,
+    Emissive,
+    Interface,
+    Principled,
+}
+
+/// A parameter of a principled material, either a number, a color or the name of a
+/// texture. Other materials only take numbers.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ParameterDescription {
+    Value(f32),
+    Color(Triple),
+    Texture(String),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+    #[serde(rename = "type")]
+    pub kind: MaterialKind,
+    /// Color of most materials, and the base color of principled ones.
+    pub texture: Option<Spanned<String>>,
+    pub fuzz: Option<f32>,
+    pub sheen: Option<ParameterDescription>,
+    /// Roughness of glossy, conductor, principled and frosted dielectric materials.
+    pub roughness: Option<ParameterDescription>,
+    pub ior: Option<f32>,
+    pub metallic: Option<ParameterDescription>,
+    pub specular: Option<ParameterDescription>,
+    pub specular_tint: Option<ParameterDescription>,
+    pub clearcoat: Option<ParameterDescription>,
+    pub transmission: Option<ParameterDescription>,
+    pub emission: Option<ParameterDescription>,
+    /// Color a dielectric leaves of light after one unit of distance at `density`.
+    pub absorption: Option<Triple>,
+    pub density: Option<f32>,
+    /// Medium inside a dielectric or interface.
+    pub medium: Option<Spanned<String>>,
+}
+
+/// A medium with coefficients per unit of distance, homogeneous unless it has a
+/// `density` grid to scale them by.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MediumDescription {
+    #[serde(default)]
+    pub sigma_a: Triple,
+    #[serde(default)]
+    pub sigma_s: Triple,
+    #[serde(default)]
+    pub g: f32,
+    /// A `.vol` file, or raw 32-bit floats with the given `resolution`.
+    pub density: Option<String>,
+    pub resolution: Option<[usize; 3]>,
+    /// Corners of the box the grid spans, read from `.vol` files when missing.
+    pub bounds: Option<[Triple; 2]>,
+    pub emission: Option<String>,
+    pub emission_color: Option<Triple>,
+    pub temperature: Option<String>,
+    pub temperature_scale: Option<f32>,
+}
+
+/// A box filled with a medium.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeDescription {
+    pub medium: Spanned<String>,
+    /// Corners of the box, the grid's own bounds when missing.
+    pub bounds: Option<[Triple; 2]>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SphereDescription {
+    pub center: Triple,
+    pub radius: f32,
+    pub material: Spanned<String>,
+    #[serde(default)]
+    pub light: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaneOrientation {
+    Yz,
+    Zx,
+    Xy,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneDescription {
+    pub orientation: PlaneOrientation,
+    pub a: [f32; 2],
+    pub b: [f32; 2],
+    pub k: f32,
+    pub material: Spanned<String>,
+    #[serde(default)]
+    pub light: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MeshDescription {
+    pub path: String,
+    #[serde(default)]
+    pub position: Triple,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub rotate: Option<RotationDescription>,
+    /// Material of the whole mesh, otherwise the materials of its MTL library are
+    /// read as principled ones.
+    pub material: Option<Spanned<String>>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct RotationDescription {
+    pub axis: AxisDescription,
+    pub angle: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDescription {
+    X,
+    Y,
+    Z,
+}
@@ -0,0 +1,631 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use glam::Vec3;
+use toml::Spanned;
+
+use crate::{
+    integrators::{
+        bidirectional::Bidirectional,
+        debug::{Albedo, AmbientOcclusion, BvhCost, Depth, Normals, Uv},
+        integrator::IntegratorType,
+        mixture::Mixture,
+        path::{Heuristic, PathTracer},
+        photon::PhotonMapper,
+    },
+    materials::{
+        conductor::Conductor,
+        dielectric::Dielectric,
+        emissivediffuse::EmissiveDiffuse,
+        glossy::Glossy,
+        interface::Interface,
+        lambertian::Lambertian,
+        material::MaterialType,
+        metal::Metal,
+        principled::Principled,
+        texture::{CheckerBoard, Image, PixelMap, SolidColor},
+        TexturePtr,
+    },
+    media::{
+        grid::{Grid, VoxelGrid},
+        homogeneous::Homogeneous,
+        medium::MediumType,
+    },
+    objects::{
+        cube::Cube,
+        plane::{Plane, PlaneType},
+        rotated::Axis,
+        sphere::Sphere,
+    },
+    utils::{
+        aabb::Aabb,
+        aov::Aov,
+        bvh::{BvhOptions, SplitMethod},
+        denoise::Denoiser,
+        tonemap::{Operator, ToneMap},
+    },
+    world::{camera::Camera, skybox::Skybox, world::World},
+};
+
+use super::{
+    description::{
+        AovKind, AxisDescription, BvhKind, HeuristicKind, IntegratorKind, MaterialDescription,
+        MaterialKind, MediumDescription, ParameterDescription, PlaneOrientation, SceneDescription,
+        SkyboxDescription, TextureDescription, TextureKind, ToneMapKind, Triple,
+    },
+    SceneError,
+};
+
+/// Reads a scene file from disk and builds the `World` and `Camera` it describes.
+/// Relative asset paths are resolved against the directory of the scene file.
+pub fn load_scene(path: &Path) -> Result<(World, Camera), SceneError> {
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    parse_scene(&source, path)
+}
+
+/// Builds a scene from TOML source. `path` is only used for error messages and to
+/// resolve relative asset paths.
+pub fn parse_scene(source: &str, path: &Path) -> Result<(World, Camera), SceneError> {
+    let description: SceneDescription =
+        toml::from_str(source).map_err(|source| SceneError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    SceneLoader {
+        source,
+        path,
+        base_dir: path.parent().unwrap_or(Path::new("")),
+    }
+    .build(description)
+}
+
+struct SceneLoader<'a> {
+    source: &'a str,
+    path: &'a Path,
+    base_dir: &'a Path,
+}
+
+impl<'a> SceneLoader<'a> {
+    fn build(&self, scene: SceneDescription) -> Result<(World, Camera), SceneError> {
+        let mut world = World::new(self.skybox(&scene.skybox)?);
+
+        // The camera maps pixels to the film by dividing by the width and height less
+        // one, so images need at least two of each.
+        if let Some(width) = &scene.render.width {
+            if *width.get_ref() < 2 {
+                return Err(self.invalid(
+                    width.span(),
+                    "`width` must be at least 2 pixels".to_string(),
+                ));
+            }
+            world = world.width(*width.get_ref());
+        }
+        if let Some(samples_per_pixel) = &scene.render.samples_per_pixel {
+            if *samples_per_pixel.get_ref() == 0 {
+                return Err(self.invalid(
+                    samples_per_pixel.span(),
+                    "`samples_per_pixel` must be at least 1".to_string(),
+                ));
+            }
+            world = world.samples_per_pixel(*samples_per_pixel.get_ref());
+        }
+        if let Some(max_depth) = scene.render.max_depth {
+            world = world.max_depth(max_depth);
+        }
+        let mut bounces = world.bounces;
+        if let Some(diffuse) = scene.render.diffuse_bounces {
+            bounces.diffuse = diffuse;
+        }
+        if let Some(specular) = scene.render.specular_bounces {
+            bounces.specular = specular;
+        }
+        if let Some(transmission) = scene.render.transmission_bounces {
+            bounces.transmission = transmission;
+        }
+        world = world.bounces(bounces);
+        let operator = match scene.render.tone_map {
+            Some(ToneMapKind::Clamp) | None => Operator::Clamp,
+            Some(ToneMapKind::Reinhard) => Operator::Reinhard,
+            Some(ToneMapKind::Aces) => Operator::Aces,
+        };
+        world =
+            world.tone_map(ToneMap::new(operator).exposure(scene.render.exposure.unwrap_or(0.0)));
+
+        let aovs: Vec<Aov> = scene
+            .render
+            .aovs
+            .iter()
+            .map(|aov| match aov {
+                AovKind::Albedo => Aov::Albedo,
+                AovKind::Normal => Aov::Normal,
+                AovKind::Depth => Aov::Depth,
+                AovKind::Position => Aov::Position,
+                AovKind::Uv => Aov::Uv,
+                AovKind::ObjectId => Aov::ObjectId,
+                AovKind::MaterialId => Aov::MaterialId,
+            })
+            .collect();
+        world = world.aovs(&aovs);
+        if scene.render.denoise {
+            world = world.denoise(Denoiser::default());
+        }
+        let mut bvh = BvhOptions::default();
+        match scene.render.bvh {
+            Some(BvhKind::Median) => bvh = bvh.split(SplitMethod::Median),
+            Some(BvhKind::Sah) | None => {}
+        }
+        if let Some(max_leaf_size) = scene.render.max_leaf_size {
+            bvh = bvh.max_leaf_size(max_leaf_size);
+        }
+        world = world.bvh(bvh);
+        world = world.integrator(match scene.render.integrator {
+            Some(IntegratorKind::Mixture) => Mixture::new(),
+            Some(IntegratorKind::Path) | None => PathTracer::new(),
+            Some(IntegratorKind::Bidirectional) => Bidirectional::new(),
+            Some(IntegratorKind::PhotonMapping) => {
+                let defaults = PhotonMapper::default();
+                PhotonMapper::new(
+                    scene.render.photons.unwrap_or(defaults.photons),
+                    scene.render.photon_radius.unwrap_or(defaults.radius),
+                )
+            }
+            Some(IntegratorKind::Normals) => Normals::new(),
+            Some(IntegratorKind::Albedo) => Albedo::new(),
+            Some(IntegratorKind::Uv) => Uv::new(),
+            Some(IntegratorKind::Depth) => IntegratorType::Depth(Depth::default()),
+            Some(IntegratorKind::BvhCost) => IntegratorType::BvhCost(BvhCost::default()),
+            Some(IntegratorKind::AmbientOcclusion) => {
+                IntegratorType::AmbientOcclusion(AmbientOcclusion::default())
+            }
+        });
+        match scene.render.mis {
+            Some(HeuristicKind::Balance) => world = world.heuristic(Heuristic::Balance),
+            Some(HeuristicKind::Power) | None => {}
+        }
+
+        let mut textures = HashMap::new();
+        for (name, texture) in &scene.textures {
+            textures.insert(name.as_str(), self.texture(name, texture)?);
+        }
+
+        let mut media = HashMap::new();
+        for (name, medium) in &scene.media {
+            media.insert(name.as_str(), self.medium(name, medium)?);
+        }
+        if let Some(atmosphere) = &scene.atmosphere {
+            world = world.atmosphere(self.lookup(&media, atmosphere, "medium")?);
+        }
+
+        let mut materials = HashMap::new();
+        for (name, material) in &scene.materials {
+            materials.insert(
+                name.as_str(),
+                self.material(name, material, &textures, &media)?,
+            );
+        }
+
+        for sphere in &scene.spheres {
+            let object = Sphere::new(
+                vec(sphere.center),
+                sphere.radius,
+                self.lookup(&materials, &sphere.material, "material")?,
+            );
+
+            if sphere.light {
+                world.add_light(object);
+            } else {
+                world.add(object);
+            }
+        }
+
+        for plane in &scene.planes {
+            let plane_type = match plane.orientation {
+                PlaneOrientation::Yz => PlaneType::YZ,
+                PlaneOrientation::Zx => PlaneType::ZX,
+                PlaneOrientation::Xy => PlaneType::XY,
+            };
+            let object = Plane::new(
+                plane_type,
+                plane.a[0],
+                plane.a[1],
+                plane.b[0],
+                plane.b[1],
+                plane.k,
+                self.lookup(&materials, &plane.material, "material")?,
+            );
+
+            if plane.light {
+                world.add_light(object);
+            } else {
+                world.add(object);
+            }
+        }
+
+        for mesh in &scene.meshes {
+            let path = self.resolve(&mesh.path);
+            let rotation = mesh.rotate.map(|rotation| {
+                let axis = match rotation.axis {
+                    AxisDescription::X => Axis::X,
+                    AxisDescription::Y => Axis::Y,
+                    AxisDescription::Z => Axis::Z,
+                };
+                (axis, rotation.angle)
+            });
+
+            let added = match (&mesh.material, rotation) {
+                (Some(material), Some((axis, angle))) => world
+                    .add_object_rot(
+                        &path,
+                        vec(mesh.position),
+                        mesh.scale,
+                        axis,
+                        angle,
+                        self.lookup(&materials, material, "material")?,
+                    )
+                    .map_err(|err| err.to_string()),
+                (Some(material), None) => world
+                    .add_object(
+                        &path,
+                        vec(mesh.position),
+                        mesh.scale,
+                        self.lookup(&materials, material, "material")?,
+                    )
+                    .map_err(|err| err.to_string()),
+                (None, rotation) => world
+                    .add_object_mtl(&path, vec(mesh.position), mesh.scale, rotation)
+                    .map_err(|err| err.to_string()),
+            };
+            added.map_err(|message| SceneError::Asset { path, message })?;
+        }
+
+        for volume in &scene.volumes {
+            let medium = self.lookup(&media, &volume.medium, "medium")?;
+            let bounds = match (volume.bounds, &medium) {
+                (Some(bounds), _) => aabb(bounds),
+                (None, MediumType::Grid(grid)) => grid.bounds,
+                (None, _) => {
+                    return Err(self.invalid(
+                        volume.medium.span(),
+                        format!(
+                            "volume of homogeneous medium `{}` is missing field `bounds`",
+                            volume.medium.get_ref()
+                        ),
+                    ))
+                }
+            };
+            world.add(Cube::new(bounds.min, bounds.max, Interface::new(medium)));
+        }
+
+        let camera = scene.camera.get_ref();
+        let look_from = vec(camera.look_from);
+        let look_at = vec(camera.look_at);
+        let camera = Camera::new(
+            look_from,
+            look_at,
+            vec(camera.up),
+            camera.vfov,
+            camera.aspect_ratio,
+            camera.aperture,
+            camera
+                .focus_distance
+                .unwrap_or_else(|| (look_from - look_at).length()),
+        );
+        if camera.height(world.width) < 2 {
+            return Err(self.invalid(
+                scene.camera.span(),
+                format!(
+                    "`aspect_ratio` makes a {} pixel wide image less than 2 pixels high",
+                    world.width
+                ),
+            ));
+        }
+
+        Ok((world, camera))
+    }
+
+    fn skybox(&self, skybox: &Spanned<SkyboxDescription>) -> Result<Skybox, SceneError> {
+        let description = skybox.get_ref();
+        let map = match (description.color, &description.image) {
+            (Some(color), None) => PixelMap::from_color(vec(color)),
+            (None, Some(image)) => self.pixel_map(image)?,
+            _ => {
+                return Err(self.invalid(
+                    skybox.span(),
+                    "skybox needs exactly one of `color` or `image`".to_string(),
+                ))
+            }
+        };
+
+        Ok(Skybox::new(map)
+            .intensity(description.intensity)
+            .rotation(description.rotation))
+    }
+
+    fn texture(
+        &self,
+        name: &str,
+        texture: &Spanned<TextureDescription>,
+    ) -> Result<TexturePtr, SceneError> {
+        let span = texture.span();
+        let texture = texture.get_ref();
+        let field = |value: Option<Triple>, field: &str| {
+            value.map(vec).ok_or_else(|| {
+                self.invalid(
+                    span.clone(),
+                    format!("texture `{}` is missing field `{}`", name, field),
+                )
+            })
+        };
+
+        let bump_map = texture
+            .bump_map
+            .as_ref()
+            .map(|bump_map| self.pixel_map(bump_map))
+            .transpose()?;
+
+        Ok(match texture.kind {
+            TextureKind::Solid => SolidColor::new(field(texture.color, "color")?, bump_map),
+            TextureKind::Image => match &texture.path {
+                Some(path) => Image::from_pixel_map(self.color_map(path)?, bump_map),
+                None => {
+                    return Err(
+                        self.invalid(span, format!("texture `{}` is missing field `path`", name))
+                    )
+                }
+            },
+            TextureKind::Checker => CheckerBoard::new(
+                field(texture.color_1, "color_1")?,
+                field(texture.color_2, "color_2")?,
+                texture.scale.ok_or_else(|| {
+                    self.invalid(
+                        span.clone(),
+                        format!("texture `{}` is missing field `scale`", name),
+                    )
+                })?,
+            ),
+        })
+    }
+
+    fn medium(
+        &self,
+        name: &str,
+        medium: &Spanned<MediumDescription>,
+    ) -> Result<MediumType, SceneError> {
+        let span = medium.span();
+        let medium = medium.get_ref();
+        let (sigma_a, sigma_s) = (vec(medium.sigma_a), vec(medium.sigma_s));
+        let density = match &medium.density {
+            Some(density) => density,
+            None => return Ok(Homogeneous::new(sigma_a, sigma_s, medium.g)),
+        };
+        let voxels = |asset: &str| -> Result<(VoxelGrid, Option<Aabb>), SceneError> {
+            let path = self.resolve(asset);
+            let voxels = if path.extension().is_some_and(|extension| extension == "vol") {
+                VoxelGrid::load_vol(&path).map(|(voxels, bounds)| (voxels, Some(bounds)))
+            } else {
+                let resolution = medium.resolution.ok_or_else(|| {
+                    self.invalid(
+                        span.clone(),
+                        format!("medium `{}` is missing field `resolution`", name),
+                    )
+                })?;
+                VoxelGrid::load_raw(&path, resolution).map(|voxels| (voxels, None))
+            };
+
+            voxels.map_err(|err| SceneError::Asset {
+                path,
+                message: err.to_string(),
+            })
+        };
+
+        let (density, saved_bounds) = voxels(density)?;
+        let bounds = medium.bounds.map(aabb).or(saved_bounds).ok_or_else(|| {
+            self.invalid(
+                span.clone(),
+                format!("medium `{}` is missing field `bounds`", name),
+            )
+        })?;
+        let mut grid = Grid::new(bounds, density, sigma_a, sigma_s, medium.g);
+        if let Some(emission) = &medium.emission {
+            let color = medium.emission_color.map_or(Vec3::ONE, vec);
+            grid = grid.emission(voxels(emission)?.0, color);
+        }
+        if let Some(temperature) = &medium.temperature {
+            let scale = medium.temperature_scale.unwrap_or(1.0);
+            grid = grid.temperature(voxels(temperature)?.0, scale);
+        }
+
+        Ok(grid.into())
+    }
+
+    fn material(
+        &self,
+        name: &str,
+        material: &Spanned<MaterialDescription>,
+        textures: &HashMap<&str, TexturePtr>,
+        media: &HashMap<&str, MediumType>,
+    ) -> Result<MaterialType, SceneError> {
+        let span = material.span();
+        let material = material.get_ref();
+        let required = |value: Option<f32>, field: &str| {
+            value.ok_or_else(|| {
+                self.invalid(
+                    span.clone(),
+                    format!("material `{}` is missing field `{}`", name, field),
+                )
+            })
+        };
+        let number = |parameter: &Option<ParameterDescription>, field: &str| match parameter {
+            None => Ok(None),
+            Some(ParameterDescription::Value(value)) => Ok(Some(*value)),
+            Some(_) => Err(self.invalid(
+                span.clone(),
+                format!("material `{}` takes a number for `{}`", name, field),
+            )),
+        };
+        let parameter = |parameter: &ParameterDescription| match parameter {
+            ParameterDescription::Value(value) => Ok(SolidColor::new(Vec3::splat(*value), None)),
+            ParameterDescription::Color(color) => Ok(SolidColor::new(vec(*color), None)),
+            ParameterDescription::Texture(texture) => {
+                textures.get(texture.as_str()).cloned().ok_or_else(|| {
+                    self.invalid(span.clone(), format!("unknown texture `{}`", texture))
+                })
+            }
+        };
+        let texture = || match &material.texture {
+            Some(texture) => self.lookup(textures, texture, "texture"),
+            None => Err(self.invalid(
+                span.clone(),
+                format!("material `{}` is missing field `texture`", name),
+            )),
+        };
+        let medium = material
+            .medium
+            .as_ref()
+            .map(|medium| self.lookup(media, medium, "medium"))
+            .transpose()?;
+        if medium.is_some()
+            && !matches!(
+                material.kind,
+                MaterialKind::Dielectric | MaterialKind::Interface
+            )
+        {
+            return Err(self.invalid(span, format!("material `{}` can't hold a medium", name)));
+        }
+
+        Ok(match material.kind {
+            MaterialKind::Lambertian => Lambertian::new(texture()?),
+            MaterialKind::Metal => Metal::new(texture()?, material.fuzz.unwrap_or(0.0)),
+            MaterialKind::Glossy => Glossy::new(
+                texture()?,
+                required(number(&material.sheen, "sheen")?, "sheen")?,
+                required(number(&material.roughness, "roughness")?, "roughness")?,
+            ),
+            MaterialKind::Conductor => Conductor::new(
+                texture()?,
+                number(&material.roughness, "roughness")?.unwrap_or(0.0),
+            ),
+            MaterialKind::Dielectric => {
+                let mut glass = Dielectric::glass(required(material.ior, "ior")?)
+                    .frosted(number(&material.roughness, "roughness")?.unwrap_or(0.0));
+                if let Some(absorption) = material.absorption {
+                    glass = glass.absorption(vec(absorption), material.density.unwrap_or(1.0));
+                }
+                if let Some(medium) = medium {
+                    glass = glass.filled_with(medium);
+                }
+                // Glass only takes the normal map from its texture.
+                if material.texture.is_some() {
+                    glass = glass.normal_map(texture()?);
+                }
+                glass.into()
+            }
+            MaterialKind::Emissive => EmissiveDiffuse::new(texture()?),
+            MaterialKind::Principled => {
+                let base_color = match material.texture {
+                    Some(_) => texture()?,
+                    None => SolidColor::new(Vec3::splat(0.8), None),
+                };
+                let mut principled = Principled::new(base_color);
+                let setters = [
+                    (&material.metallic, Principled::metallic as fn(_, _) -> _),
+                    (&material.roughness, Principled::roughness),
+                    (&material.specular, Principled::specular),
+                    (&material.specular_tint, Principled::specular_tint),
+                    (&material.sheen, Principled::sheen),
+                    (&material.clearcoat, Principled::clearcoat),
+                    (&material.transmission, Principled::transmission),
+                    (&material.emission, Principled::emission),
+                ];
+                for (value, set) in setters {
+                    if let Some(value) = value {
+                        principled = set(principled, parameter(value)?);
+                    }
+                }
+                if let Some(ior) = material.ior {
+                    principled = principled.ior(ior);
+                }
+                principled.into()
+            }
+            MaterialKind::Interface => match medium {
+                Some(medium) => Interface::new(medium),
+                None => {
+                    return Err(self.invalid(
+                        span,
+                        format!("material `{}` is missing field `medium`", name),
+                    ))
+                }
+            },
+        })
+    }
+
+    fn lookup<T: Clone>(
+        &self,
+        table: &HashMap<&str, T>,
+        name: &Spanned<String>,
+        kind: &str,
+    ) -> Result<T, SceneError> {
+        table.get(name.get_ref().as_str()).cloned().ok_or_else(|| {
+            self.invalid(
+                name.span(),
+                format!("unknown {} `{}`", kind, name.get_ref()),
+            )
+        })
+    }
+
+    fn resolve(&self, asset: &str) -> PathBuf {
+        self.base_dir.join(asset)
+    }
+
+    fn pixel_map(&self, asset: &str) -> Result<PixelMap, SceneError> {
+        let path = self.resolve(asset);
+
+        PixelMap::load(&path).map_err(|err| SceneError::Asset {
+            path,
+            message: err.to_string(),
+        })
+    }
+
+    /// Like `pixel_map`, for images of colors stored in sRGB.
+    fn color_map(&self, asset: &str) -> Result<PixelMap, SceneError> {
+        let path = self.resolve(asset);
+
+        PixelMap::load_srgb(&path).map_err(|err| SceneError::Asset {
+            path,
+            message: err.to_string(),
+        })
+    }
+
+    fn invalid(&self, span: Range<usize>, message: String) -> SceneError {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+
+        SceneError::Invalid {
+            path: self.path.to_path_buf(),
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+fn vec(triple: Triple) -> Vec3 {
+    Vec3::from(triple)
+}
+
+fn aabb([min, max]: [Triple; 2]) -> Aabb {
+    Aabb {
+        min: vec(min).min(vec(max)),
+        max: vec(min).max(vec(max)),
+    }
+}
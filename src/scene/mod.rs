@@ -0,0 +1,58 @@
+use std::{fmt, path::PathBuf};
+
+pub mod description;
+pub mod loader;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Asset {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            SceneError::Parse { path, source } => {
+                write!(f, "in {}: {}", path.display(), source)
+            }
+            SceneError::Invalid {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            SceneError::Asset { path, message } => {
+                write!(f, "could not load {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { source, .. } => Some(source),
+            SceneError::Invalid { .. } | SceneError::Asset { .. } => None,
+        }
+    }
+}
@@ -1,29 +1,53 @@
-use crate::{world::physics::Ray, Vec3};
+use glam::Vec3;
 
-#[derive(Copy, Clone, Debug)]
+use crate::world::physics::Ray;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Aabb {
     pub min: Vec3,
     pub max: Vec3,
 }
 
 impl Aabb {
-    pub fn hit(&self, r: &Ray, mut tmin: f32, mut tmax: f32) -> bool {
+    pub fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> bool {
+        self.hit_inverse(r.origin, r.direction.recip(), tmin, tmax)
+    }
+
+    /// Slab test against a ray given by its origin and the reciprocal of its direction,
+    /// which can be computed once per ray instead of once per box.
+    pub fn hit_inverse(
+        &self,
+        origin: Vec3,
+        inverse_direction: Vec3,
+        mut tmin: f32,
+        mut tmax: f32,
+    ) -> bool {
         for a in 0..3 {
-            let mint = (self.min[a] - r.origin[a]) / r.direction[a];
-            let maxt = (self.max[a] - r.origin[a]) / r.direction[a];
+            let mint = (self.min[a] - origin[a]) * inverse_direction[a];
+            let maxt = (self.max[a] - origin[a]) * inverse_direction[a];
             let t0 = mint.min(maxt);
             let t1 = mint.max(maxt);
 
             tmin = t0.max(tmin);
             tmax = t1.min(tmax);
 
-            if tmax <= tmin {
+            // Flat boxes (axis aligned triangles) still have to be hit on their plane.
+            if tmax < tmin {
                 return false;
             }
         }
 
         true
     }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
 }
 
 pub fn surrounding_box(box_0: &Aabb, box_1: &Aabb) -> Aabb {
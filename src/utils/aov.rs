@@ -0,0 +1,126 @@
+use glam::Vec3;
+
+use super::{tonemap::srgb_oetf, Color, BLACK, WHITE};
+
+/// Auxiliary per-pixel passes that can be rendered next to the beauty image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// Material albedo at the first hit.
+    Albedo,
+    /// World space shading normal at the first hit.
+    Normal,
+    /// Distance from the camera along its viewing direction.
+    Depth,
+    /// World space position of the first hit.
+    Position,
+    /// Surface texture coordinates of the first hit.
+    Uv,
+    /// `World::object_ids` of the first hit, 0 for the background.
+    ObjectId,
+    /// `World::material_ids` of the first hit, 0 for the background.
+    MaterialId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+        }
+    }
+
+    /// Channel names used for the EXR layer, one per meaningful component.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+        }
+    }
+
+    /// Maps a buffer of raw values to colors in [0, 1] for 8-bit output. Depth and
+    /// position are normalized to the range found in the buffer and IDs get a
+    /// distinct color each.
+    pub fn display(&self, values: &[Color]) -> Vec<Color> {
+        let (min, max) = values.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(*v), max.max(*v)),
+        );
+        let range = (max - min).max(Vec3::splat(f32::EPSILON));
+
+        values
+            .iter()
+            .map(|&value| match self {
+                Aov::Albedo => {
+                    let value = value.clamp(BLACK, WHITE);
+                    Color::new(srgb_oetf(value.x), srgb_oetf(value.y), srgb_oetf(value.z))
+                }
+                Aov::Normal => 0.5 * value + 0.5,
+                Aov::Depth => Color::splat(value.x / max.x.max(f32::EPSILON)),
+                Aov::Position => (value - min) / range,
+                Aov::Uv => value.clamp(BLACK, WHITE),
+                Aov::ObjectId | Aov::MaterialId => id_color(value.x as u32),
+            })
+            .collect()
+    }
+}
+
+/// First-hit data for a single camera sample.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AovSample {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f32,
+    pub position: Vec3,
+    pub uv: (f32, f32),
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl AovSample {
+    pub fn get(&self, aov: Aov) -> Color {
+        match aov {
+            Aov::Albedo => self.albedo,
+            Aov::Normal => self.normal,
+            Aov::Depth => Color::splat(self.depth),
+            Aov::Position => self.position,
+            Aov::Uv => Color::new(self.uv.0, self.uv.1, 0.0),
+            Aov::ObjectId => Color::splat(self.object_id as f32),
+            Aov::MaterialId => Color::splat(self.material_id as f32),
+        }
+    }
+}
+
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return BLACK;
+    }
+
+    // Hash the ID so neighbouring IDs end up with clearly different colors.
+    let hash = id
+        .wrapping_mul(0x9E37_79B9)
+        .rotate_left(16)
+        .wrapping_mul(0x85EB_CA6B);
+    Color::new(
+        (hash & 0xFF) as f32,
+        (hash >> 8 & 0xFF) as f32,
+        (hash >> 16 & 0xFF) as f32,
+    ) / 255.0
+}
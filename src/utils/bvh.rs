@@ -1,223 +1,571 @@
-use rand::Rng;
-use std::cmp::Ordering;
-
 use std::fmt;
 
+use glam::Vec3;
+
 use crate::objects::object::{Bounded, Geometry, ObjectType};
 use crate::utils::aabb::surrounding_box;
 use crate::world::physics::{Intersection, Ray};
+use rayon::prelude::*;
 
 use super::aabb::Aabb;
 
+/// Cost of visiting an inner node relative to intersecting one primitive.
+const TRAVERSAL_COST: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// Split at the median along the axis the centroids spread furthest on.
+    Median,
+    /// Binned surface area heuristic, evaluating `bins` candidate planes per axis.
+    Sah { bins: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BvhOptions {
+    pub split: SplitMethod,
+    /// Nodes with more primitives than this are always split.
+    pub max_leaf_size: usize,
+}
+
+impl Default for BvhOptions {
+    fn default() -> Self {
+        BvhOptions {
+            split: SplitMethod::Sah { bins: 16 },
+            max_leaf_size: 4,
+        }
+    }
+}
+
+impl BvhOptions {
+    pub fn split(mut self, split: SplitMethod) -> Self {
+        self.split = split;
+        self
+    }
+
+    pub fn max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.clamp(1, u16::MAX as usize);
+        self
+    }
+}
+
+/// Summary of a built tree, see `BvhTree::stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BvhStats {
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    /// Number of nodes on the longest path from the root to a leaf.
+    pub depth: usize,
+    pub largest_leaf: usize,
+    /// Expected cost of a random ray through the tree, in primitive intersections.
+    pub sah_cost: f32,
+}
+
+/// Work done by a single query, see `BvhTree::hit_cost`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraversalCost {
+    /// Nodes whose bounding box was tested.
+    pub nodes: usize,
+    /// Objects whose intersection test ran.
+    pub objects: usize,
+}
+
+/// Deepest tree the traversal stack can hold. Splits below half of this depth fall back
+/// to median splits, which halve the object count every level.
+const MAX_DEPTH: usize = 64;
+
 pub struct BvhTree<'a> {
-    nodes: Vec<BvhNode<'a>>,
-    root: NodeId,
+    objects: &'a [ObjectType],
+    /// Object indices ordered so every leaf covers a contiguous range.
+    indices: Vec<usize>,
+    /// Nodes in depth first order, the root is the first one.
+    nodes: Vec<BvhNode>,
+    options: BvhOptions,
 }
 
-struct BvhNode<'a> {
-    left: Option<NodeId>,
-    right: Option<NodeId>,
-    aabb: Option<Aabb>,
-    object: Option<&'a ObjectType>,
+/// Node of the flattened tree. The left child of an inner node is stored right after
+/// it, so only the index of the right child is kept.
+#[derive(Clone, Copy)]
+struct BvhNode {
+    aabb: Aabb,
+    /// First entry in `indices` for leaves, index of the right child for inner nodes.
+    offset: u32,
+    /// Number of objects in a leaf, 0 for inner nodes.
+    count: u16,
+    /// Axis the children were split along, used to visit the nearer one first.
+    axis: u8,
 }
 
 type NodeId = usize;
 
 impl<'a> BvhTree<'a> {
-    pub fn intersects_id(
-        &self,
-        id: NodeId,
-        ray: &Ray,
-        t_min: f32,
-        t_max: f32,
-    ) -> Option<Intersection> {
-        let node = &self.nodes[id];
-        if let Some(aabb) = node.aabb {
-            if aabb.hit(ray, t_min, t_max) {
-                if let Some(obj) = node.object {
-                    return obj.intersects(ray, t_min, t_max);
-                }
+    /// Builds the tree over `objects` with the default options.
+    pub fn new(objects: &'a [ObjectType]) -> BvhTree<'a> {
+        BvhTree::with_options(objects, BvhOptions::default())
+    }
 
-                let hit_left = match node.left {
-                    Some(id) => self.intersects_id(id, ray, t_min, t_max),
-                    None => None,
-                };
-
-                let hit_right = match node.right {
-                    Some(id) => self.intersects_id(id, ray, t_min, t_max),
-                    None => None,
-                };
-
-                return match (hit_left, hit_right) {
-                    (None, None) => None,
-                    (None, hit) => hit,
-                    (hit, None) => hit,
-                    (Some(left), Some(right)) => {
-                        if left.distance < right.distance {
-                            Some(left)
-                        } else {
-                            Some(right)
-                        }
-                    }
-                };
+    /// Builds the tree over `objects` without reordering them, so the `object` index of
+    /// an `Intersection` can be used to look up per-object data. Large subtrees are
+    /// built in parallel.
+    pub fn with_options(objects: &'a [ObjectType], options: BvhOptions) -> BvhTree<'a> {
+        let options = options.max_leaf_size(options.max_leaf_size);
+        let mut indices: Vec<usize> = (0..objects.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+
+        if !objects.is_empty() {
+            let boxes: Vec<Aabb> = objects
+                .par_iter()
+                .map(|object| {
+                    object
+                        .bounding_box()
+                        .expect("No bounding box in BvhTree::new")
+                })
+                .collect();
+            let centroids: Vec<Vec3> = boxes.par_iter().map(Aabb::centroid).collect();
+
+            Builder {
+                boxes: &boxes,
+                centroids: &centroids,
+                options,
             }
+            .build(&mut indices, 0, 1, &mut nodes);
         }
 
-        None
+        BvhTree {
+            objects,
+            indices,
+            nodes,
+            options,
+        }
     }
-}
 
-impl<'a> BvhTree<'a> {
-    pub fn new(l: &'a mut Vec<ObjectType>) -> BvhTree<'a> {
-        let mut tree = BvhTree {
-            nodes: Vec::new(),
-            root: 0,
+    pub fn options(&self) -> BvhOptions {
+        self.options
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            primitives: self.objects.len(),
+            nodes: self.nodes.len(),
+            leaves: 0,
+            depth: 0,
+            largest_leaf: 0,
+            sah_cost: 0.0,
         };
-        tree.root = tree.build(l);
+        if !self.nodes.is_empty() {
+            let root_area = self.nodes[0].aabb.surface_area();
+            self.collect_stats(0, 1, root_area, &mut stats);
+        }
 
-        tree
+        stats
     }
 
-    fn build(&mut self, l: &'a mut [ObjectType]) -> NodeId {
-        let axis = rand::thread_rng().gen_range(0..3);
+    fn collect_stats(&self, id: NodeId, depth: usize, root_area: f32, stats: &mut BvhStats) {
+        let node = &self.nodes[id];
+        let probability = if root_area > 0.0 {
+            node.aabb.surface_area() / root_area
+        } else {
+            1.0
+        };
+        stats.depth = stats.depth.max(depth);
 
-        match axis {
-            0 => l.sort_by(|a, b| box_x_compare(a, b)),
-            1 => l.sort_by(|a, b| box_y_compare(a, b)),
-            2 => l.sort_by(|a, b| box_z_compare(a, b)),
-            _ => panic!("Unexpected axis"),
+        if node.count > 0 {
+            stats.leaves += 1;
+            stats.largest_leaf = stats.largest_leaf.max(node.count as usize);
+            stats.sah_cost += probability * node.count as f32;
+        } else {
+            stats.sah_cost += probability * TRAVERSAL_COST;
+            self.collect_stats(id + 1, depth + 1, root_area, stats);
+            self.collect_stats(node.offset as NodeId, depth + 1, root_area, stats);
         }
+    }
 
-        let left: NodeId;
-        let right: NodeId;
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.aabb)
+    }
 
-        if l.len() == 1 {
-            return self.new_leaf(&l[0]);
-        } else if l.len() == 2 {
-            left = self.new_leaf(&l[0]);
-            right = self.new_leaf(&l[1]);
-        } else {
-            let half_len = l.len() / 2;
-            let (left_hitables, right_hitables) = l.split_at_mut(half_len);
+    /// Finds the closest intersection along `ray`. Children are visited front to back
+    /// and every hit shrinks the search interval, so most far subtrees are culled
+    /// without being entered.
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'a>> {
+        let mut closest = None;
+        self.traverse(ray, t_min, t_max, |index, t_max| {
+            if let Some(hit) = self.objects[index].intersects(ray, t_min, *t_max) {
+                *t_max = hit.distance;
+                closest = Some(Intersection {
+                    object: index,
+                    ..hit
+                });
+            }
+            false
+        });
 
-            left = self.build(left_hitables);
-            right = self.build(right_hitables);
+        closest
+    }
+
+    /// Whether anything blocks `ray` between `t_min` and `t_max`. Stops at the first
+    /// hit found and skips shading data, which makes it the cheap query for shadow
+    /// rays.
+    pub fn occluded(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.traverse(ray, t_min, t_max, |index, t_max| {
+            self.objects[index]
+                .hit_distance(ray, t_min, *t_max)
+                .is_some()
+        })
+    }
+
+    /// `hit` with the recursive walk the tree used before it was flattened: both
+    /// children are searched over the full interval and the nearer hit is kept. Only
+    /// useful as a baseline in benchmarks.
+    pub fn hit_recursive(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection<'a>> {
+        if self.nodes.is_empty() {
+            return None;
         }
 
-        if let Some(left_box) = self.nodes[left].aabb {
-            if let Some(right_box) = self.nodes[right].aabb {
-                return self.new_node(
-                    surrounding_box(&left_box, &right_box),
-                    Some(left),
-                    Some(right),
-                );
+        self.intersects_id(0, ray, t_min, t_max)
+    }
+
+    fn intersects_id(
+        &self,
+        id: NodeId,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<Intersection<'a>> {
+        let node = &self.nodes[id];
+        if !node.aabb.hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        if node.count > 0 {
+            let mut closest = None;
+            let mut t_max = t_max;
+            let first = node.offset as usize;
+            for &index in &self.indices[first..first + node.count as usize] {
+                if let Some(hit) = self.objects[index].intersects(ray, t_min, t_max) {
+                    t_max = hit.distance;
+                    closest = Some(Intersection {
+                        object: index,
+                        ..hit
+                    });
+                }
             }
+            return closest;
         }
 
-        panic!("No bounding box in BvhNode::build");
+        let hit_left = self.intersects_id(id + 1, ray, t_min, t_max);
+        let hit_right = self.intersects_id(node.offset as NodeId, ray, t_min, t_max);
+        match (hit_left, hit_right) {
+            (Some(left), Some(right)) if right.distance < left.distance => Some(right),
+            (Some(left), _) => Some(left),
+            (None, hit) => hit,
+        }
     }
 
-    fn new_leaf(&mut self, object: &'a ObjectType) -> NodeId {
-        let next_index = self.nodes.len();
-
-        self.nodes.push(BvhNode {
-            left: None,
-            right: None,
-            aabb: object.bounding_box(),
-            object: Some(object),
+    /// Nodes and objects tested by the closest hit query for `ray`, for cost heatmaps.
+    pub fn hit_cost(&self, ray: &Ray, t_min: f32, t_max: f32) -> TraversalCost {
+        let mut cost = TraversalCost::default();
+        self.traverse_counting(ray, t_min, t_max, &mut cost.nodes, |index, t_max| {
+            cost.objects += 1;
+            if let Some(distance) = self.objects[index].hit_distance(ray, t_min, *t_max) {
+                *t_max = distance;
+            }
+            false
         });
 
-        return next_index;
+        cost
     }
 
-    fn new_node(&mut self, aabb: Aabb, left: Option<NodeId>, right: Option<NodeId>) -> NodeId {
-        let next_index = self.nodes.len();
+    /// Calls `visit` for every object in the leaves `ray` passes through, nearest
+    /// subtrees first. `visit` may shrink `t_max` to cull farther nodes, or return
+    /// `true` to stop the walk, which is then reported as the result.
+    fn traverse(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        visit: impl FnMut(usize, &mut f32) -> bool,
+    ) -> bool {
+        self.traverse_counting(ray, t_min, t_max, &mut 0, visit)
+    }
 
-        self.nodes.push(BvhNode {
-            left,
-            right,
-            aabb: Some(aabb),
-            object: None,
-        });
+    /// `traverse`, adding the number of nodes whose box was tested to `nodes`.
+    fn traverse_counting(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        nodes: &mut usize,
+        mut visit: impl FnMut(usize, &mut f32) -> bool,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inverse_direction = ray.direction.recip();
+        let negative = [
+            ray.direction.x < 0.0,
+            ray.direction.y < 0.0,
+            ray.direction.z < 0.0,
+        ];
+
+        let mut stack = [0; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut id = 0;
+
+        loop {
+            let node = &self.nodes[id];
+            *nodes += 1;
+            if node
+                .aabb
+                .hit_inverse(ray.origin, inverse_direction, t_min, t_max)
+            {
+                if node.count == 0 {
+                    // Descend into the near child and come back for the far one.
+                    let (near, far) = if negative[node.axis as usize] {
+                        (node.offset as NodeId, id + 1)
+                    } else {
+                        (id + 1, node.offset as NodeId)
+                    };
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    id = near;
+                    continue;
+                }
+
+                let first = node.offset as usize;
+                for &index in &self.indices[first..first + node.count as usize] {
+                    if visit(index, &mut t_max) {
+                        return true;
+                    }
+                }
+            }
 
-        return next_index;
+            if stack_size == 0 {
+                return false;
+            }
+            stack_size -= 1;
+            id = stack[stack_size];
+        }
     }
+}
 
-    fn number_hittables(&self, id: NodeId) -> usize {
-        let node = &self.nodes[id];
-        let local_hitable = if node.object.is_some() { 1 } else { 0 };
-        let count_left = if let Some(left_index) = node.left {
-            self.number_hittables(left_index)
-        } else {
-            0
-        };
-        let count_right = if let Some(right_index) = node.right {
-            self.number_hittables(right_index)
-        } else {
-            0
+/// Nodes with at least this many objects build their two subtrees on separate threads.
+const PARALLEL_THRESHOLD: usize = 4096;
+
+struct Builder<'b> {
+    boxes: &'b [Aabb],
+    centroids: &'b [Vec3],
+    options: BvhOptions,
+}
+
+impl<'b> Builder<'b> {
+    /// Appends the subtree over `indices` to `nodes` in depth first order. `first` is
+    /// the position of `indices` in the full index list.
+    fn build(&self, indices: &mut [usize], first: usize, depth: usize, nodes: &mut Vec<BvhNode>) {
+        let count = indices.len();
+        let aabb = indices
+            .iter()
+            .map(|&index| self.boxes[index])
+            .reduce(|a, b| surrounding_box(&a, &b))
+            .expect("Empty BvhNode::build");
+
+        let split = match self.options.split {
+            SplitMethod::Sah { bins } if depth < MAX_DEPTH / 2 => {
+                self.sah_split(indices, &aabb, bins)
+            }
+            _ => self.median_split(indices),
         };
 
-        local_hitable + count_left + count_right
-    }
+        let id = nodes.len();
+        nodes.push(BvhNode {
+            aabb,
+            offset: first as u32,
+            count: count as u16,
+            axis: 0,
+        });
 
-    fn bounding_box(&self) -> Option<Aabb> {
-        self.nodes[self.root].aabb
+        if let Some((middle, axis)) = split {
+            let (left, right) = indices.split_at_mut(middle);
+            let right_id = if count >= PARALLEL_THRESHOLD {
+                let (left, right) = rayon::join(
+                    || self.subtree(left, first, depth + 1),
+                    || self.subtree(right, first + middle, depth + 1),
+                );
+                append(nodes, left);
+                let right_id = nodes.len();
+                append(nodes, right);
+                right_id
+            } else {
+                self.build(left, first, depth + 1, nodes);
+                let right_id = nodes.len();
+                self.build(right, first + middle, depth + 1, nodes);
+                right_id
+            };
+
+            nodes[id] = BvhNode {
+                aabb,
+                offset: right_id as u32,
+                count: 0,
+                axis: axis as u8,
+            };
+        }
     }
 
-    pub fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<Intersection> {
-        self.intersects_id(self.root, r, tmin, tmax)
+    fn subtree(&self, indices: &mut [usize], first: usize, depth: usize) -> Vec<BvhNode> {
+        let mut nodes = Vec::with_capacity(2 * indices.len());
+        self.build(indices, first, depth, &mut nodes);
+        nodes
     }
-}
 
-impl<'a> fmt::Display for BvhTree<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "BVH with {:?} hitables and {:?} nodes",
-            self.number_hittables(self.root),
-            self.nodes.len()
+    /// Smallest and largest centroid coordinates of the objects in `indices`.
+    fn centroid_bounds(&self, indices: &[usize]) -> (Vec3, Vec3) {
+        indices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &index| {
+                let centroid = self.centroids[index];
+                (min.min(centroid), max.max(centroid))
+            },
         )
     }
-}
 
-fn box_x_compare(a: &ObjectType, b: &ObjectType) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
-            if let Some(cmp) = box_left.min.x.partial_cmp(&box_right.min.x) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
-            }
+    fn median_split(&self, indices: &mut [usize]) -> Option<(usize, usize)> {
+        let count = indices.len();
+        if count <= self.options.max_leaf_size {
+            return None;
         }
+
+        // Split along the axis the centroids spread furthest on, so the tree doesn't
+        // depend on which thread builds it. Only the median has to end up in place,
+        // not the full order.
+        let (centroid_min, centroid_max) = self.centroid_bounds(indices);
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        indices.select_nth_unstable_by(count / 2, |&a, &b| {
+            self.centroids[a][axis]
+                .partial_cmp(&self.centroids[b][axis])
+                .expect("Can't compare")
+        });
+
+        Some((count / 2, axis))
     }
 
-    panic!("No bounding box in BvhNode::new");
-}
+    /// Picks the bin boundary with the lowest surface area heuristic cost, or `None`
+    /// when a leaf is cheaper and allowed.
+    fn sah_split(&self, indices: &mut [usize], aabb: &Aabb, bins: usize) -> Option<(usize, usize)> {
+        let count = indices.len();
+        if count == 1 {
+            return None;
+        }
 
-fn box_y_compare(a: &ObjectType, b: &ObjectType) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
-            if let Some(cmp) = box_left.min.y.partial_cmp(&box_right.min.y) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
+        let bins = bins.max(2);
+        let (centroid_min, centroid_max) = self.centroid_bounds(indices);
+        let extent = centroid_max - centroid_min;
+        let bin_of = |index: usize, axis: usize| {
+            let offset = (self.centroids[index][axis] - centroid_min[axis]) / extent[axis];
+            ((offset * bins as f32) as usize).min(bins - 1)
+        };
+
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+
+            let mut counts = vec![0; bins];
+            let mut bounds: Vec<Option<Aabb>> = vec![None; bins];
+            for &index in indices.iter() {
+                let bin = bin_of(index, axis);
+                counts[bin] += 1;
+                bounds[bin] = Some(merge(bounds[bin], &self.boxes[index]));
+            }
+
+            // Area times primitive count of everything right of each boundary.
+            let mut right_cost = vec![0.0; bins];
+            let (mut right_box, mut right_count) = (None, 0);
+            for bin in (1..bins).rev() {
+                if let Some(bin_box) = &bounds[bin] {
+                    right_box = Some(merge(right_box, bin_box));
+                }
+                right_count += counts[bin];
+                right_cost[bin] = right_box.map_or(0.0, |b| b.surface_area()) * right_count as f32;
+            }
+
+            let (mut left_box, mut left_count) = (None, 0);
+            for bin in 0..bins - 1 {
+                if let Some(bin_box) = &bounds[bin] {
+                    left_box = Some(merge(left_box, bin_box));
+                }
+                left_count += counts[bin];
+                if left_count == 0 || left_count == count {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + (left_box.map_or(0.0, |b| b.surface_area()) * left_count as f32
+                        + right_cost[bin + 1])
+                        / aabb.surface_area();
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, bin));
+                }
             }
         }
-    }
 
-    panic!("No bounding box in BvhNode::new");
+        let must_split = count > self.options.max_leaf_size;
+        match best {
+            Some((cost, axis, bin)) if must_split || cost < count as f32 => {
+                let mut middle = 0;
+                for i in 0..count {
+                    if bin_of(indices[i], axis) <= bin {
+                        indices.swap(i, middle);
+                        middle += 1;
+                    }
+                }
+                Some((middle, axis))
+            }
+            // All centroids coincide, any split is as good as another.
+            None if must_split => Some((count / 2, 0)),
+            _ => None,
+        }
+    }
 }
 
-fn box_z_compare(a: &ObjectType, b: &ObjectType) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
-            if let Some(cmp) = box_left.min.z.partial_cmp(&box_right.min.z) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
-            }
+/// Moves the nodes of a separately built subtree to the end of `nodes`.
+fn append(nodes: &mut Vec<BvhNode>, subtree: Vec<BvhNode>) {
+    let base = nodes.len() as u32;
+    nodes.extend(subtree.into_iter().map(|mut node| {
+        if node.count == 0 {
+            node.offset += base;
         }
+        node
+    }));
+}
+
+impl<'a> fmt::Display for BvhTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stats = self.stats();
+        write!(
+            f,
+            "BVH with {:?} hitables and {:?} nodes ({} leaves of up to {} hitables), depth {}, SAH cost {:.2}",
+            stats.primitives,
+            stats.nodes,
+            stats.leaves,
+            stats.largest_leaf,
+            stats.depth,
+            stats.sah_cost
+        )
     }
+}
 
-    panic!("No bounding box in BvhNode::new");
+fn merge(a: Option<Aabb>, b: &Aabb) -> Aabb {
+    match a {
+        Some(a) => surrounding_box(&a, b),
+        None => *b,
+    }
 }
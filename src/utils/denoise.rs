@@ -0,0 +1,124 @@
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{aov::Aov, framebuffer::Framebuffer, Color, WHITE};
+
+/// Joint bilateral filter for the linear framebuffer.
+///
+/// Neighbouring pixels are averaged when they are close on screen and look alike in
+/// the albedo and normal passes, so noise is smoothed out without blurring across
+/// geometric or texture edges. Every sigma is at least `MIN_SIGMA`, smaller ones
+/// (zero and NaN included) are raised to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    /// Half size of the filter window in pixels.
+    pub radius: u32,
+    /// Standard deviation of the screen space falloff, in pixels.
+    pub sigma_spatial: f32,
+    /// How different the (compressed) colors of two pixels may be.
+    pub sigma_color: f32,
+    pub sigma_albedo: f32,
+    /// Applied to `1 - cos` of the angle between two normals.
+    pub sigma_normal: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            radius: 6,
+            sigma_spatial: 3.0,
+            sigma_color: 0.6,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Feature passes the filter is guided by.
+    pub const FEATURES: [Aov; 2] = [Aov::Albedo, Aov::Normal];
+
+    /// Smallest sigma the filter divides by, which all but stops pixels that differ
+    /// at all from being averaged.
+    pub const MIN_SIGMA: f32 = 1e-3;
+
+    pub fn radius(mut self, radius: u32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn sigma_spatial(mut self, sigma: f32) -> Self {
+        self.sigma_spatial = sigma;
+        self
+    }
+
+    pub fn sigma_color(mut self, sigma: f32) -> Self {
+        self.sigma_color = sigma;
+        self
+    }
+
+    pub fn sigma_albedo(mut self, sigma: f32) -> Self {
+        self.sigma_albedo = sigma;
+        self
+    }
+
+    pub fn sigma_normal(mut self, sigma: f32) -> Self {
+        self.sigma_normal = sigma;
+        self
+    }
+
+    /// Filters the beauty pass of `image` in place. Missing feature passes are treated
+    /// as constant, which degrades the filter to a plain bilateral one.
+    pub fn denoise(&self, image: &mut Framebuffer) {
+        let (width, height) = image.dimensions();
+        let count = (width * height) as usize;
+        let albedo = image
+            .aov(Aov::Albedo)
+            .map_or_else(|| vec![WHITE; count], <[Color]>::to_vec);
+        let normal = image
+            .aov(Aov::Normal)
+            .map_or_else(|| vec![Color::Z; count], <[Color]>::to_vec);
+
+        // Compare colors after a Reinhard curve so a few fireflies don't decide the weights.
+        let noisy = image.pixels();
+        let compressed: Vec<Color> = noisy.iter().map(|c| *c / (WHITE + *c)).collect();
+
+        let radius = self.radius as i32;
+        let sigma = |sigma: f32| sigma.max(Denoiser::MIN_SIGMA);
+        let spatial = -0.5 / sigma(self.sigma_spatial).powi(2);
+        let color = -0.5 / sigma(self.sigma_color).powi(2);
+        let albedo_weight = -0.5 / sigma(self.sigma_albedo).powi(2);
+        let normal_weight = -1.0 / sigma(self.sigma_normal);
+
+        let filtered: Vec<Color> = (0..count)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i as u32 % width) as i32, (i as u32 / width) as i32);
+                let mut sum = Color::ZERO;
+                let mut total = 0.0;
+
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (qx, qy) = (x + dx, y + dy);
+                        if qx < 0 || qy < 0 || qx >= width as i32 || qy >= height as i32 {
+                            continue;
+                        }
+                        let j = (qy as u32 * width + qx as u32) as usize;
+
+                        let exponent = spatial * (dx * dx + dy * dy) as f32
+                            + color * (compressed[i] - compressed[j]).length_squared()
+                            + albedo_weight * (albedo[i] - albedo[j]).length_squared()
+                            + normal_weight * (1.0 - normal[i].dot(normal[j])).max(0.0);
+                        let weight = exponent.exp();
+
+                        sum += weight * noisy[j];
+                        total += weight;
+                    }
+                }
+
+                sum / total
+            })
+            .collect();
+
+        image.pixels_mut().copy_from_slice(&filtered);
+    }
+}
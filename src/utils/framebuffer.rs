@@ -0,0 +1,242 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult, Rgb, Rgb32FImage};
+
+use super::{aov::Aov, tonemap::ToneMap, Color, RenderedImage, Vec3Extension, BLACK};
+
+/// Radiance that any render thread can add to any pixel, for light that reaches the
+/// image through another pixel than the camera sample that found it.
+#[derive(Debug)]
+pub struct SplatBuffer {
+    pub width: u32,
+    pub height: u32,
+    /// Bits of an `f32` per channel, three per pixel.
+    values: Vec<AtomicU32>,
+}
+
+impl SplatBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        SplatBuffer {
+            width,
+            height,
+            values: (0..3 * width * height).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    pub fn add(&self, pixel: usize, color: Color) {
+        for (value, channel) in self.values[3 * pixel..3 * pixel + 3]
+            .iter()
+            .zip(color.to_array())
+        {
+            // Only fails when the closure returns `None`, which it never does.
+            let _ = value.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f32::from_bits(bits) + channel).to_bits())
+            });
+        }
+    }
+
+    pub fn get(&self, pixel: usize) -> Color {
+        let channel = |i: usize| f32::from_bits(self.values[3 * pixel + i].load(Ordering::Relaxed));
+        Color::new(channel(0), channel(1), channel(2))
+    }
+}
+
+/// Linear scene-referred radiance, one `Color` per pixel in row-major order with the
+/// top row first. Values are not clamped, so highlights keep their full range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    /// Display transform used for 8-bit output, can be changed after rendering.
+    pub tone_map: ToneMap,
+    pixels: Vec<Color>,
+    aovs: Vec<(Aov, Vec<Color>)>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            tone_map: ToneMap::default(),
+            pixels: vec![BLACK; (width * height) as usize],
+            aovs: vec![],
+        }
+    }
+
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Allocates a buffer for each of `aovs`, duplicates are ignored.
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> Self {
+        for &aov in aovs {
+            if self.aov(aov).is_none() {
+                self.aovs.push((aov, self.pixels.clone()));
+            }
+        }
+        self
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// Drops every AOV that isn't in `aovs`.
+    pub fn retain_aovs(&mut self, aovs: &[Aov]) {
+        self.aovs.retain(|(aov, _)| aovs.contains(aov));
+    }
+
+    /// The AOVs this framebuffer holds, in the order they were requested.
+    pub fn aovs(&self) -> impl Iterator<Item = Aov> + '_ {
+        self.aovs.iter().map(|(aov, _)| *aov)
+    }
+
+    /// Raw values of an AOV, single channel passes are repeated in all three components.
+    pub fn aov(&self, aov: Aov) -> Option<&[Color]> {
+        self.aovs
+            .iter()
+            .find(|(other, _)| *other == aov)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    pub fn aov_mut(&mut self, aov: Aov) -> Option<&mut [Color]> {
+        self.aovs
+            .iter_mut()
+            .find(|(other, _)| *other == aov)
+            .map(|(_, values)| values.as_mut_slice())
+    }
+
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.get_pixel(x, y).to_array())
+        })
+    }
+
+    /// Converts to 8-bit sRGB for display through `tone_map`.
+    pub fn to_rgb8(&self) -> RenderedImage {
+        RenderedImage::from_fn(self.width, self.height, |x, y| {
+            self.tone_map.apply(self.get_pixel(x, y)).to_rgb()
+        })
+    }
+
+    /// Writes the image to `path`. OpenEXR and Radiance HDR keep the linear values,
+    /// every other format is tone mapped to 8-bit with `to_rgb8`.
+    ///
+    /// AOVs are stored as extra layers of an OpenEXR file. Other formats write each
+    /// AOV to its own file next to `path`, see `aov_path`.
+    pub fn save_with_format(&self, path: impl AsRef<Path>, format: ImageFormat) -> ImageResult<()> {
+        let path = path.as_ref();
+        match format {
+            ImageFormat::OpenExr if !self.aovs.is_empty() => self.save_layered_exr(path),
+            ImageFormat::OpenExr => {
+                DynamicImage::ImageRgb32F(self.to_rgb32f()).save_with_format(path, format)
+            }
+            ImageFormat::Hdr => {
+                self.save_hdr(path, &self.pixels)?;
+                for (aov, values) in &self.aovs {
+                    self.save_hdr(aov_path(path, *aov), values)?;
+                }
+                Ok(())
+            }
+            _ => {
+                self.to_rgb8().save_with_format(path, format)?;
+                for (aov, values) in &self.aovs {
+                    let display = aov.display(values);
+                    RenderedImage::from_fn(self.width, self.height, |x, y| {
+                        display[(y * self.width + x) as usize].to_rgb()
+                    })
+                    .save_with_format(aov_path(path, *aov), format)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        self.save_with_format(path, ImageFormat::from_path(path)?)
+    }
+
+    fn save_hdr(&self, path: impl AsRef<Path>, values: &[Color]) -> ImageResult<()> {
+        let pixels: Vec<Rgb<f32>> = values
+            .iter()
+            .map(|pixel| Rgb(pixel.max(BLACK).to_array()))
+            .collect();
+
+        image::codecs::hdr::HdrEncoder::new(BufWriter::new(File::create(path)?)).encode(
+            &pixels,
+            self.width as usize,
+            self.height as usize,
+        )
+    }
+
+    /// Writes the beauty pass as the `R`, `G` and `B` channels and every AOV as
+    /// `<aov>.<channel>`, the naming compositors expect for layers.
+    fn save_layered_exr(&self, path: &Path) -> ImageResult<()> {
+        use exr::prelude::*;
+
+        let channel = |name: &str, values: &[Color], component: usize| {
+            AnyChannel::new(
+                name,
+                FlatSamples::F32(values.iter().map(|value| value[component]).collect()),
+            )
+        };
+
+        let mut channels = SmallVec::new();
+        for (component, name) in ["R", "G", "B"].iter().enumerate() {
+            channels.push(channel(name, &self.pixels, component));
+        }
+        for (aov, values) in &self.aovs {
+            for (component, name) in aov.channels().iter().enumerate() {
+                channels.push(channel(
+                    &format!("{}.{}", aov.name(), name),
+                    values,
+                    component,
+                ));
+            }
+        }
+
+        let layer = Layer::new(
+            (self.width as usize, self.height as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|err| ImageError::IoError(std::io::Error::other(err)))
+    }
+}
+
+/// Where a separately saved AOV goes: `renders/scene.png` becomes
+/// `renders/scene.albedo.png`.
+pub fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, aov.name(), extension.to_string_lossy()),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+
+    path.with_file_name(name)
+}
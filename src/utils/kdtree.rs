@@ -0,0 +1,89 @@
+use glam::Vec3;
+
+/// Anything with a position that can be stored in a `KdTree`.
+pub trait Located {
+    fn position(&self) -> Vec3;
+}
+
+/// A balanced kd-tree kept implicitly in one array: every range of items is split at
+/// its median along the axis where it is widest, and the two halves are stored on
+/// either side of it.
+pub struct KdTree<T> {
+    items: Vec<T>,
+    /// Split axis of the item at the same index.
+    axes: Vec<u8>,
+}
+
+impl<T: Located> KdTree<T> {
+    pub fn new(mut items: Vec<T>) -> Self {
+        let mut axes = vec![0; items.len()];
+        build(&mut items, &mut axes);
+
+        KdTree { items, axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Calls `visit` for every item closer to `point` than `radius`.
+    pub fn within(&self, point: Vec3, radius: f32, mut visit: impl FnMut(&T)) {
+        let radius_squared = radius * radius;
+        let mut stack = vec![(0, self.items.len())];
+
+        while let Some((start, end)) = stack.pop() {
+            if start >= end {
+                continue;
+            }
+            let middle = start + (end - start) / 2;
+            let item = &self.items[middle];
+            let position = item.position();
+            if position.distance_squared(point) < radius_squared {
+                visit(item);
+            }
+
+            let axis = self.axes[middle] as usize;
+            let offset = point[axis] - position[axis];
+            if offset <= 0.0 || offset * offset < radius_squared {
+                stack.push((start, middle));
+            }
+            if offset >= 0.0 || offset * offset < radius_squared {
+                stack.push((middle + 1, end));
+            }
+        }
+    }
+}
+
+fn build<T: Located>(items: &mut [T], axes: &mut [u8]) {
+    if items.is_empty() {
+        return;
+    }
+
+    let (min, max) = items.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), item| (min.min(item.position()), max.max(item.position())),
+    );
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |a, b| {
+        a.position()[axis].total_cmp(&b.position()[axis])
+    });
+    axes[middle] = axis as u8;
+
+    let (left, right) = items.split_at_mut(middle);
+    let (left_axes, right_axes) = axes.split_at_mut(middle);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
@@ -2,10 +2,16 @@ use glam::Vec3;
 use image::{ImageBuffer, Rgb};
 
 pub mod aabb;
+pub mod aov;
 pub mod bvh;
+pub mod denoise;
+pub mod framebuffer;
+pub mod kdtree;
 pub mod sampling;
+pub mod tonemap;
 
 pub type Color = glam::Vec3;
+/// 8-bit display image, see `Framebuffer::to_rgb8`.
 pub type RenderedImage = ImageBuffer<image::Rgb<u8>, Vec<u8>>;
 pub const BLACK: glam::Vec3 = Color::ZERO;
 pub const WHITE: glam::Vec3 = Color::ONE;
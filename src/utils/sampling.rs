@@ -1,11 +1,13 @@
-use std::f32::consts::PI;
-
 use arrayvec::ArrayVec;
 
 use glam::Vec3;
-use rand::Rng;
 
-use crate::{materials::texture::PixelMap, world::WorldLights};
+use crate::{
+    materials::microfacet::Bsdf,
+    media::phase::HenyeyGreenstein,
+    random::random_distribution,
+    world::{skybox::Skybox, WorldLights},
+};
 
 pub type ONB = ArrayVec<Vec3, 3>;
 
@@ -58,6 +60,14 @@ pub trait PdfReady {
     fn random(&self, _o: Vec3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    /// Point spread uniformly over the surface, with the outward normal there. Light
+    /// paths start from these.
+    fn random_point(&self) -> (Vec3, Vec3) {
+        (Vec3::ZERO, Vec3::Z)
+    }
+    fn area(&self) -> f32 {
+        0.0
+    }
 }
 
 pub enum PDF<'a> {
@@ -68,13 +78,22 @@ pub enum PDF<'a> {
         origin: Vec3,
         objects: &'a WorldLights,
     },
+    Environment {
+        skybox: &'a Skybox,
+    },
     Mixture {
         p: &'a PDF<'a>,
         q: &'a PDF<'a>,
-    }, // Image {
-       //     map: &'a PixelMap,
-       //     pUDist: Vec<f32>,
-       // }
+    },
+    /// Scattering in a medium of a ray travelling along `direction`.
+    Phase {
+        direction: Vec3,
+        phase: HenyeyGreenstein,
+    },
+    /// Sampling a rough surface by its visible microfacets.
+    Microfacet {
+        bsdf: Bsdf,
+    },
 }
 
 impl<'a> PDF<'a> {
@@ -91,38 +110,24 @@ impl<'a> PDF<'a> {
     }
 
     #[inline(always)]
-    pub fn mixture(p: &'a PDF, q: &'a PDF) -> Self {
-        PDF::Mixture { p, q }
+    pub fn environment(skybox: &'a Skybox) -> Self {
+        PDF::Environment { skybox }
     }
 
-    // #[inline(always)]
-    // // function generates a PDF from an image
-    // pub fn image(map: &'a PixelMap) -> Self {
-    //     // generate a marginal cdf
-    //     let mut pUDist = Vec::with_capacity(map.width as usize);
-    //     let mut pBuffer = Vec::with_capacity(map.width as usize);
-    //     let mut m_pData = Vec::with_capacity(map.width as usize * map.height as usize);
-
-    //     let mut sum = 0.0;
-    //     for y in 0..map.height {
-    //         for x in 0..map.width {
-    //             let pixel = map.get_pixel((x, y));
-    //             let luminance = 0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z;
-    //             sum += luminance;
-    //             m_pData.push(luminance);
-    //         }
-    //     }
-
-    //     for i in 0..map.width as usize {
-    //         pBuffer.push(m_pData[i]);
-    //     }
+    #[inline(always)]
+    pub fn phase(direction: Vec3, phase: HenyeyGreenstein) -> Self {
+        PDF::Phase { direction, phase }
+    }
 
-    //     for i in 0..map.width as usize {
-    //         pUDist.push(pBuffer[i] / sum);
-    //     }
+    #[inline(always)]
+    pub fn microfacet(bsdf: Bsdf) -> Self {
+        PDF::Microfacet { bsdf }
+    }
 
-    //     PDF::Image { map, pUDist }
-    // }
+    #[inline(always)]
+    pub fn mixture(p: &'a PDF, q: &'a PDF) -> Self {
+        PDF::Mixture { p, q }
+    }
 
     pub fn value(&self, direction: Vec3) -> f32 {
         match self {
@@ -131,18 +136,17 @@ impl<'a> PDF<'a> {
                 if cosine > 0.0 {
                     cosine / std::f32::consts::PI
                 } else {
-                    1.0
+                    0.0
                 }
             }
             PDF::Lights { origin, objects } => objects.pdf_value(*origin, direction),
-            PDF::Mixture { p, q } => 0.5 * p.value(direction) + 0.5 * q.value(direction), // PDF::Image { map, pUDist } => {
-                                                                                          //     let (u, v) = map.surface_uv(direction.normalize());
-                                                                                          //     let uIndex = (u * (map.width - 1) as f32) as usize;
-                                                                                          //     let vIndex = (v * (map.height - 1) as f32) as usize;
-                                                                                          //     let uDist = pUDist[uIndex];
-                                                                                          //     let vDist = pUDist[vIndex];
-                                                                                          //     uDist * vDist
-                                                                                          // }
+            PDF::Environment { skybox } => skybox.pdf_value(direction),
+            PDF::Mixture { p, q } => 0.5 * p.value(direction) + 0.5 * q.value(direction),
+            PDF::Phase {
+                direction: incoming,
+                phase,
+            } => phase.value(*incoming, direction),
+            PDF::Microfacet { bsdf } => bsdf.pdf(direction),
         }
     }
 
@@ -150,52 +154,135 @@ impl<'a> PDF<'a> {
         match self {
             PDF::Cosine { uvw } => uvw.local(random_cosine_direction()),
             PDF::Lights { origin, objects } => objects.random(*origin),
+            PDF::Environment { skybox } => skybox.random(),
             PDF::Mixture { p, q } => {
-                let mut rng = rand::thread_rng();
-                if rng.gen::<bool>() {
+                if random_distribution() < 0.5 {
                     p.generate()
                 } else {
                     q.generate()
                 }
-            } // PDF::Image { map, pUDist } => {
-              //     let mut rng = rand::thread_rng();
-              //     let u = rng.gen_range(0.0..1.0);
-              //     let v = rng.gen_range(0.0..1.0);
-              //     let uIndex = (u * (map.width - 1) as f32) as usize;
-              //     let vIndex = (v * (map.height - 1) as f32) as usize;
-              //     let uDist = pUDist[uIndex];
-              //     let vDist = pUDist[vIndex];
-              //     let u = u * (map.width - 1) as f32;
-              //     let v = v * (map.height - 1) as f32;
-              //     let u = u / (map.width - 1) as f32;
-              //     let v = v / (map.height - 1) as f32;
-              //     let u = u * uDist;
-              //     let v = v * vDist;
-              //     let u = u * (map.width - 1) as f32;
-              //     let v = v * (map.height - 1) as f32;
-              //     let u = u as usize;
-              //     let v = v as usize;
-              //     let pixel = map.get_pixel((u as u32, v as u32));
-              //     Vec3::new(pixel.x, pixel.y, pixel.z)
-              // }
+            }
+            PDF::Phase { direction, phase } => phase.sample(*direction),
+            // Lost samples have no density.
+            PDF::Microfacet { bsdf } => bsdf.sample().unwrap_or(Vec3::ZERO),
         }
     }
 }
 
+/// A piecewise-constant distribution over [0, 1) built from `n` non-negative weights.
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    pub integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for i in 0..n {
+            cdf.push(cdf[i] + func[i] / n as f32);
+        }
+
+        let integral = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate().skip(1) {
+            *value = if integral > 0.0 {
+                *value / integral
+            } else {
+                i as f32 / n as f32
+            };
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Maps a uniform sample to a point in [0, 1), returning it with its density and
+    /// the index of the bin it fell into.
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        let index = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.count() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+
+        (
+            ((index as f32 + offset) / self.count() as f32).min(1.0 - f32::EPSILON),
+            self.pdf(index),
+            index,
+        )
+    }
+
+    pub fn pdf(&self, index: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise-constant distribution over [0, 1)², sampled by picking a row from the
+/// marginal distribution and then a column from that row's conditional distribution.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` holds `width * height` weights in row-major order.
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|row| row.integral).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Returns a point `(x, y)` in the unit square and its density.
+    pub fn sample(&self, u: f32, v: f32) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample(v);
+        let (x, pdf_x, _) = self.conditional[row].sample(u);
+
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let row = ((y * self.marginal.count() as f32) as usize).min(self.marginal.count() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((x * conditional.count() as f32) as usize).min(conditional.count() - 1);
+
+        if self.marginal.integral > 0.0 {
+            conditional.func[column] / self.marginal.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Unit direction around +Z with density cos(theta) / pi, matching `PDF::Cosine`.
 fn random_cosine_direction() -> Vec3 {
-    let mut rng = rand::thread_rng();
-    let r1 = rng.gen::<f32>();
-    let r2 = rng.gen::<f32>();
+    let r1 = random_distribution();
+    let r2 = random_distribution();
     let z = (1.0 - r2).sqrt();
     let phi = 2.0 * std::f32::consts::PI * r1;
-    let x = phi.cos() * 2.0 * r2.sqrt();
-    let y = phi.sin() * 2.0 * r2.sqrt();
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
     Vec3::new(x, y, z)
 }
-
-fn from_spherical_uv(u: f32, v: f32) -> Vec3 {
-    let phi = u * 2.0 * std::f32::consts::PI;
-    let theta = (v * 0.5 + 0.5) * std::f32::consts::PI;
-    let sin_theta = theta.sin();
-    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), theta.cos())
-}
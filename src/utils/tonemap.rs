@@ -0,0 +1,80 @@
+use super::{Color, BLACK, WHITE};
+
+/// Curve used to compress linear radiance into the displayable [0, 1] range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Operator {
+    /// Hard clip at 1.0, what the renderer has always done.
+    #[default]
+    Clamp,
+    /// `c / (1 + c)` per channel.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic reference rendering transform.
+    Aces,
+}
+
+/// Display transform from a linear framebuffer to 8-bit sRGB: scale by the exposure,
+/// apply the operator, then encode with the sRGB transfer function.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMap {
+    pub operator: Operator,
+    /// Exposure adjustment in stops, every stop doubles the brightness.
+    pub exposure: f32,
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        ToneMap::new(Operator::Clamp)
+    }
+}
+
+impl ToneMap {
+    pub fn new(operator: Operator) -> Self {
+        ToneMap {
+            operator,
+            exposure: 0.0,
+        }
+    }
+
+    pub fn exposure(mut self, stops: f32) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    /// Maps linear radiance to a display-referred, sRGB-encoded color in [0, 1].
+    pub fn apply(&self, color: Color) -> Color {
+        let color = color.max(BLACK) * self.exposure.exp2();
+        let mapped = match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => color / (WHITE + color),
+            Operator::Aces => {
+                (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14)
+            }
+        };
+
+        let mapped = mapped.clamp(BLACK, WHITE);
+        Color::new(
+            srgb_oetf(mapped.x),
+            srgb_oetf(mapped.y),
+            srgb_oetf(mapped.z),
+        )
+    }
+}
+
+/// The sRGB opto-electronic transfer function, linear light to encoded value.
+pub fn srgb_oetf(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The sRGB electro-optical transfer function, encoded value to linear light, the
+/// inverse of `srgb_oetf`.
+pub fn srgb_eotf(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -1,20 +1,33 @@
 use std::time::Instant;
 
 use glam::Vec3;
-use image::{imageops::flip_vertical, RgbImage};
 use indicatif::ProgressBar;
-use rayon::{
-    iter::{IndexedParallelIterator, ParallelIterator},
-    slice::ParallelSliceMut,
-};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 
 use super::{physics::Ray, world::World};
 use crate::{
-    random::{random_distribution, random_in_unit_disk},
-    utils::{bvh::BvhTree, sampling::PDF, RenderedImage, Vec3Extension, BLACK},
+    integrators::integrator::{Integrator, Pass},
+    materials::material::Material,
+    random::{self, random_distribution, random_in_unit_disk},
+    utils::{
+        aov::{Aov, AovSample},
+        bvh::BvhTree,
+        denoise::Denoiser,
+        framebuffer::{Framebuffer, SplatBuffer},
+        BLACK,
+    },
 };
 use indicatif::ProgressStyle;
 
+/// A point on the lens joined to a point in the scene, see `Camera::lens_sample`.
+pub struct LensSample {
+    pub point: Vec3,
+    /// Index of the pixel that sees the scene point through `point`.
+    pub pixel: usize,
+    /// Solid angle density of camera rays from `point` towards the scene point.
+    pub pdf: f32,
+}
+
 pub struct Camera {
     origin: Vec3,
     lower_left_corner: Vec3,
@@ -70,56 +83,202 @@ impl Camera {
         )
     }
 
-    pub fn render_threaded(&self, world: &mut World) -> RenderedImage {
+    /// Height of an image `width` pixels wide.
+    pub fn height(&self, width: u32) -> u32 {
+        (width as f32 / self.aspect_ratio) as u32
+    }
+
+    /// Solid angle density of camera rays in `direction` from a given lens point, over
+    /// all the pixels of a `width` by `height` image. Images less than 2 pixels wide or
+    /// high can't be rendered and have a density of 0.
+    pub fn direction_pdf(&self, direction: Vec3, width: u32, height: u32) -> f32 {
+        let cos_theta = direction.normalize().dot(-self.w);
+        if cos_theta <= 0.0 || width < 2 || height < 2 {
+            return 0.0;
+        }
+
+        // Pixels are sampled up to a pixel past `horizontal` and `vertical`.
+        let (width, height) = (width as f32, height as f32);
+        let focus_dist = (self.lower_left_corner - self.origin).dot(-self.w);
+        let film_area = self.horizontal.length() * self.vertical.length() * width * height
+            / ((width - 1.0) * (height - 1.0));
+        focus_dist.powi(2) / (film_area * cos_theta.powi(3))
+    }
+
+    /// Picks a point on the lens and finds the pixel of a `width` by `height` image
+    /// that sees `point` through it, if any. Images less than 2 pixels wide or high have
+    /// none.
+    pub fn lens_sample(&self, point: Vec3, width: u32, height: u32) -> Option<LensSample> {
+        if width < 2 || height < 2 {
+            return None;
+        }
+
+        let rd = self.lens_radius * random_in_unit_disk();
+        let lens = self.origin + self.u * rd.x + self.v * rd.y;
+        let direction = point - lens;
+        let depth = direction.dot(-self.w);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        // Where the ray crosses the plane in focus, in the coordinates of `get_ray`.
+        let focus_dist = (self.lower_left_corner - self.origin).dot(-self.w);
+        let on_focus_plane = lens + direction * (focus_dist / depth) - self.lower_left_corner;
+        let u = on_focus_plane.dot(self.horizontal) / self.horizontal.length_squared();
+        let v = on_focus_plane.dot(self.vertical) / self.vertical.length_squared();
+        let column = (u * (width - 1) as f32).floor();
+        let row = (v * (height - 1) as f32).floor();
+        if column < 0.0 || column >= width as f32 || row < 0.0 || row >= height as f32 {
+            return None;
+        }
+
+        Some(LensSample {
+            point: lens,
+            pixel: ((height - 1 - row as u32) * width + column as u32) as usize,
+            pdf: self.direction_pdf(direction, width, height),
+        })
+    }
+
+    /// First-hit data for the AOV passes, all zero when the ray escapes.
+    fn aov_sample(&self, ray: &Ray, world: &World, world_objects: &BvhTree) -> AovSample {
+        match world_objects.hit(ray, 0.001, f32::MAX) {
+            Some(hit) => AovSample {
+                albedo: hit.material.albedo(hit.uv, hit.point),
+                normal: hit.normal,
+                depth: (hit.point - self.origin).dot(-self.w),
+                position: hit.point,
+                uv: hit.uv,
+                object_id: world.object_ids()[hit.object],
+                material_id: world.material_ids()[hit.object],
+            },
+            None => AovSample::default(),
+        }
+    }
+
+    pub fn render_threaded(&self, world: &World) -> Framebuffer {
         let width = world.width;
         let samples_per_pixel = world.samples_per_pixel;
-        let max_depth = world.max_depth;
-        let world_objects = BvhTree::new(&mut world.objects);
-        let light_objects = &world.lights;
+        let bounces = world.bounces;
+        let seed = world.seed;
+
+        if let Some(seed) = seed {
+            random::seed(seed);
+        }
+        let t0 = Instant::now();
+        let world_objects = BvhTree::with_options(world.objects(), world.bvh);
+        let build_time = t0.elapsed();
+
+        let height = self.height(width);
+        // Pixels are mapped to the film by dividing by the width and height less one.
+        assert!(
+            width > 1 && height > 1,
+            "Can't render a {}x{} image, it needs at least 2x2 pixels",
+            width,
+            height
+        );
+        assert!(samples_per_pixel > 0, "Can't render 0 samples per pixel");
+        let splats = SplatBuffer::new(width, height);
 
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let mut img = Framebuffer::new(width, height)
+            .with_tone_map(world.tone_map)
+            .with_aovs(&world.aovs);
+        if world.denoiser.is_some() {
+            img = img.with_aovs(&Denoiser::FEATURES);
+        }
+        let aovs: Vec<Aov> = img.aovs().collect();
 
-        let mut img = RgbImage::new(width, height);
+        // Integrators that rebuild data between passes take one sample per pixel in
+        // each, everything else renders in a single pass.
+        let passes = world.integrator.passes(samples_per_pixel).max(1);
+        let samples_per_pass = samples_per_pixel / passes;
 
-        let bar = ProgressBar::new((height * width) as u64 + 1);
+        let bar = ProgressBar::new((height * width * passes) as u64 + 1);
         bar.set_style(
             ProgressStyle::default_bar()
                 .template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>7}/{len:7} pixel"),
         );
 
+        println!("{}", world_objects);
+        println!("Built BVH in {:?}", build_time);
         println!(
-            "Rendering {}x{} at {} samples per pixel with a max depth of {}",
-            width, height, samples_per_pixel, max_depth
+            "Rendering {}x{} at {} samples per pixel with up to {} diffuse, {} specular and {} transmission bounces",
+            width, height, samples_per_pixel, bounces.diffuse, bounces.specular, bounces.transmission
         );
         let t1 = Instant::now();
 
-        img.par_chunks_mut(3).enumerate().for_each(|(i, slab)| {
-            let mut final_color = BLACK;
+        let mut pixels = vec![(BLACK, vec![BLACK; aovs.len()]); (width * height) as usize];
+        for index in 0..passes {
+            let pass = Pass {
+                camera: self,
+                splats: &splats,
+                photons: world.integrator.prepare(index, world, &world_objects),
+            };
+
+            pixels
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, (final_color, final_aovs))| {
+                    if let Some(seed) = seed {
+                        random::seed(
+                            seed.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                                ^ i as u64
+                                ^ (index as u64) << 32,
+                        );
+                    }
+                    let i = i as u32;
+
+                    (0..samples_per_pass).for_each(|_| {
+                        let u = (random_distribution() + (i % width) as f32) / (width - 1) as f32;
+                        let v = (random_distribution() + (height - 1 - i / width) as f32)
+                            / (height - 1) as f32;
+
+                        let r = self.get_ray(u, v);
 
-            (0..samples_per_pixel).for_each(|_| {
-                let u = (random_distribution() + (i as u32 % width) as f32) / (width - 1) as f32;
-                let v = (random_distribution() + (i as u32 / width) as f32) / (height - 1) as f32;
+                        let color = world.integrator.sample(&r, world, &world_objects, &pass);
+                        if color.is_finite() {
+                            *final_color += color.max(Vec3::ZERO);
+                        }
+                    });
 
-                let r = self.get_ray(u, v);
+                    // The passes only need the first hit through the center of each pixel,
+                    // traced once after the samples so it doesn't shift their random numbers.
+                    if index == 0 && !aovs.is_empty() {
+                        let u = (0.5 + (i % width) as f32) / (width - 1) as f32;
+                        let v = (0.5 + (height - 1 - i / width) as f32) / (height - 1) as f32;
+                        let aov_sample =
+                            self.aov_sample(&self.get_ray(u, v), world, &world_objects);
+                        for (value, aov) in final_aovs.iter_mut().zip(&aovs) {
+                            *value = aov_sample.get(*aov);
+                        }
+                    }
 
-                final_color = final_color
-                    + r.color(&world_objects, &light_objects, &world.skybox, max_depth)
-                        .max(Vec3::ZERO)
-                        .min(Vec3::ONE);
-            });
-            slab.copy_from_slice(
-                &(final_color / samples_per_pixel as f32)
-                    .powf(0.5)
-                    .to_slice_u8(),
-            );
+                    bar.inc(1);
+                });
+        }
 
-            bar.inc(1);
-        });
+        let samples_per_pixel = passes * samples_per_pass;
+        let pixels = pixels
+            .into_iter()
+            .map(|(color, values)| (color / samples_per_pixel as f32, values));
 
-        img = flip_vertical(&img);
+        for (i, (color, values)) in pixels.enumerate() {
+            img.pixels_mut()[i] = color + splats.get(i) / samples_per_pixel as f32;
+            for (aov, value) in aovs.iter().zip(values) {
+                if let Some(buffer) = img.aov_mut(*aov) {
+                    buffer[i] = value;
+                }
+            }
+        }
 
         bar.finish();
-        println!("Took {:?}", t1.elapsed());
+        println!("Rendered in {:?}", t1.elapsed());
+
+        if let Some(denoiser) = &world.denoiser {
+            let t2 = Instant::now();
+            denoiser.denoise(&mut img);
+            img.retain_aovs(&world.aovs);
+            println!("Denoised in {:?}", t2.elapsed());
+        }
         img
     }
 }
@@ -1,14 +1,16 @@
 use std::f32::consts::PI;
 
 use glam::Vec3;
-use rand::prelude::SliceRandom;
 
 use crate::{
-    objects::object::ObjectType, random::random_sphere_distribution, utils::sampling::PdfReady,
+    objects::object::ObjectType,
+    random::{random_int, random_sphere_distribution},
+    utils::sampling::PdfReady,
 };
 
 pub mod camera;
 pub mod physics;
+pub mod skybox;
 pub mod world;
 
 pub type WorldObjects = Vec<ObjectType>;
@@ -19,24 +21,24 @@ impl PdfReady for WorldLights {
         let n = self.len();
 
         if n == 0 {
-            return 0.25 / PI;
+            0.25 / PI
         } else {
             let mut sum = 0.0_f32;
             for light in self {
                 sum += light.pdf_value(o, v)
             }
 
-            return sum / (n as f32);
+            sum / (n as f32)
         }
     }
 
     fn random(&self, o: Vec3) -> Vec3 {
         let n = self.len();
 
-        return if n == 0 {
+        if n == 0 {
             random_sphere_distribution().normalize()
         } else {
-            self.choose(&mut rand::thread_rng()).unwrap().random(o)
-        };
+            self[random_int(0, n as u32) as usize].random(o)
+        }
     }
 }
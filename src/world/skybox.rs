@@ -0,0 +1,138 @@
+use std::{f32::consts::PI, path::Path};
+
+use glam::Vec3;
+use image::ImageResult;
+
+use crate::{
+    materials::texture::PixelMap,
+    random::{random_distribution, random_sphere_distribution},
+    utils::{sampling::Distribution2D, Color},
+};
+
+/// The environment seen by rays that leave the scene. Image skyboxes are
+/// equirectangular maps with +Y as the up axis.
+pub struct Skybox {
+    pub map: PixelMap,
+    pub intensity: f32,
+    sin_rotation: f32,
+    cos_rotation: f32,
+    sampler: Option<Distribution2D>,
+}
+
+impl Skybox {
+    pub fn new(map: PixelMap) -> Self {
+        let sampler = if map.width > 0 && map.height > 0 {
+            Some(luminance_distribution(&map))
+        } else {
+            None
+        };
+
+        Skybox {
+            map,
+            intensity: 1.0,
+            sin_rotation: 0.0,
+            cos_rotation: 1.0,
+            sampler,
+        }
+    }
+
+    /// Loads an environment map from disk. OpenEXR and Radiance HDR maps are kept as
+    /// linear float radiance, 8-bit formats are normalized to [0, 1].
+    pub fn load(path: &Path) -> ImageResult<Self> {
+        Ok(Skybox::new(PixelMap::load(path)?))
+    }
+
+    /// Scales the radiance of the whole environment.
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Rotates the environment around the up axis by `angle` degrees.
+    pub fn rotation(mut self, angle: f32) -> Self {
+        let radians = angle.to_radians();
+        self.sin_rotation = radians.sin();
+        self.cos_rotation = radians.cos();
+        self
+    }
+
+    /// Maps a world space direction into the unrotated frame of the map.
+    pub fn to_local(&self, dir: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_rotation * dir.x + self.sin_rotation * dir.z,
+            dir.y,
+            -self.sin_rotation * dir.x + self.cos_rotation * dir.z,
+        )
+    }
+
+    /// Maps a direction in the frame of the map back into world space.
+    pub fn to_world(&self, dir: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_rotation * dir.x - self.sin_rotation * dir.z,
+            dir.y,
+            self.sin_rotation * dir.x + self.cos_rotation * dir.z,
+        )
+    }
+
+    pub fn dir_color(&self, dir: Vec3) -> Color {
+        self.intensity * self.map.dir_color(self.to_local(dir))
+    }
+
+    /// Whether the skybox is an image that can be importance sampled. Uniform color
+    /// skyboxes are left to BSDF sampling.
+    pub fn is_sampled(&self) -> bool {
+        self.sampler.is_some()
+    }
+
+    /// Solid angle density of `random` for the direction `dir`.
+    pub fn pdf_value(&self, dir: Vec3) -> f32 {
+        let sampler = match &self.sampler {
+            Some(sampler) => sampler,
+            None => return 0.25 / PI,
+        };
+
+        let (u, v) = self.map.surface_uv(self.to_local(dir.normalize()));
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        sampler.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// Picks a direction with probability proportional to the luminance of the map.
+    pub fn random(&self) -> Vec3 {
+        let sampler = match &self.sampler {
+            Some(sampler) => sampler,
+            None => return random_sphere_distribution(),
+        };
+
+        let ((u, y), _) = sampler.sample(random_distribution(), random_distribution());
+        self.to_world(self.map.uv_direction((u, 1.0 - y)))
+    }
+}
+
+/// Builds a distribution over the rows and columns of an equirectangular map. Each
+/// pixel is weighted by its luminance and by sin(theta) to account for the smaller
+/// solid angle covered by pixels towards the poles.
+fn luminance_distribution(map: &PixelMap) -> Distribution2D {
+    let (width, height) = (map.width, map.height);
+    let mut func = Vec::with_capacity((width * height) as usize);
+
+    for j in 0..height {
+        let sin_theta = (PI * (j as f32 + 0.5) / height as f32).sin();
+        for i in 0..width {
+            let pixel = map.get_pixel((i, j));
+            let luminance = 0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z;
+            func.push(luminance.max(0.0) * sin_theta);
+        }
+    }
+
+    Distribution2D::new(&func, width as usize, height as usize)
+}
+
+impl From<PixelMap> for Skybox {
+    fn from(map: PixelMap) -> Self {
+        Skybox::new(map)
+    }
+}
@@ -3,37 +3,84 @@ use std::path::Path;
 use glam::Vec3;
 
 use crate::{
-    materials::{material::MaterialType, texture::PixelMap},
+    integrators::{
+        integrator::{Bounces, IntegratorType},
+        path::Heuristic,
+    },
+    materials::material::{Material, MaterialType},
+    media::medium::MediumType,
     objects::{
-        obj::{load_obj, load_obj_spec},
-        object::ObjectType,
+        obj::{load_obj, load_obj_mtl, MtlError},
+        object::{Geometry, ObjectType},
         rotated::{Axis, Rotated},
     },
-    utils::{Color, RenderedImage},
+    random::random_int,
+    utils::{
+        aov::Aov, bvh::BvhOptions, denoise::Denoiser, framebuffer::Framebuffer, sampling::PdfReady,
+        tonemap::ToneMap, Color,
+    },
 };
 
-use super::{camera::Camera, WorldLights, WorldObjects};
+use super::{
+    camera::Camera,
+    physics::{Intersection, Ray},
+    skybox::Skybox,
+    WorldLights, WorldObjects,
+};
 
+/// Objects and lights are only added through the `add` methods, which keep the
+/// per-object IDs and light indices lined up with them.
 pub struct World {
-    pub objects: WorldObjects,
-    pub lights: WorldLights,
+    objects: WorldObjects,
+    /// Per-object IDs starting at 1, every triangle of a mesh shares the mesh's ID.
+    object_ids: Vec<u32>,
+    /// Per-object index into `materials`, plus one.
+    material_ids: Vec<u32>,
+    /// Materials in the order they were added, one per call to an `add` method and
+    /// one per material of an MTL library, told apart by where they came from rather
+    /// than by comparing them.
+    materials: Vec<MaterialType>,
+    lights: WorldLights,
+    /// Per-object index into `lights` for objects added with `add_light`.
+    light_indices: Vec<Option<usize>>,
     pub background: Color,
     pub width: u32,
     pub samples_per_pixel: u32,
-    pub max_depth: u32,
-    pub skybox: PixelMap,
+    pub bounces: Bounces,
+    pub skybox: Skybox,
+    pub seed: Option<u64>,
+    pub tone_map: ToneMap,
+    pub aovs: Vec<Aov>,
+    pub denoiser: Option<Denoiser>,
+    pub bvh: BvhOptions,
+    pub integrator: IntegratorType,
+    pub heuristic: Heuristic,
+    /// Medium filling all of space outside objects, which the camera sits in.
+    pub atmosphere: Option<MediumType>,
 }
 
 impl World {
-    pub fn new(skybox: PixelMap) -> Self {
+    pub fn new(skybox: impl Into<Skybox>) -> Self {
         World {
             objects: vec![],
+            object_ids: vec![],
+            material_ids: vec![],
+            materials: vec![],
             lights: vec![],
+            light_indices: vec![],
             background: Color::new(0.3, 0.3, 0.35),
             width: 800,
             samples_per_pixel: 128,
-            max_depth: 50,
-            skybox,
+            bounces: Bounces::default(),
+            skybox: skybox.into(),
+            seed: None,
+            tone_map: ToneMap::default(),
+            aovs: vec![],
+            denoiser: None,
+            bvh: BvhOptions::default(),
+            integrator: IntegratorType::default(),
+            heuristic: Heuristic::default(),
+            atmosphere: None,
         }
     }
     pub fn background(mut self, color: Color) -> Self {
@@ -48,13 +95,87 @@ impl World {
         self.samples_per_pixel = samples_per_pixel;
         self
     }
+    pub fn bounces(mut self, bounces: Bounces) -> Self {
+        self.bounces = bounces;
+        self
+    }
+    /// Limits every kind of bounce to `max_depth`, keeping the Russian roulette setting.
     pub fn max_depth(mut self, max_depth: u32) -> Self {
-        self.max_depth = max_depth;
+        self.bounces = Bounces {
+            roulette: self.bounces.roulette,
+            ..Bounces::all(max_depth)
+        };
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+    pub fn aovs(mut self, aovs: &[Aov]) -> Self {
+        self.aovs = aovs.to_vec();
+        self
+    }
+    pub fn denoise(mut self, denoiser: Denoiser) -> Self {
+        self.denoiser = Some(denoiser);
+        self
+    }
+    pub fn bvh(mut self, options: BvhOptions) -> Self {
+        self.bvh = options;
+        self
+    }
+    pub fn integrator(mut self, integrator: IntegratorType) -> Self {
+        self.integrator = integrator;
+        self
+    }
+    /// Multiple importance sampling weights used by the `Path` integrator.
+    pub fn heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+    /// Fills the space around objects with `medium`. It reaches to infinity, so
+    /// everything far enough away, the skybox included, fades into it.
+    pub fn atmosphere(mut self, medium: MediumType) -> Self {
+        self.atmosphere = Some(medium);
         self
     }
+    pub fn objects(&self) -> &WorldObjects {
+        &self.objects
+    }
+    /// ID of every object, in the order of `objects`.
+    pub fn object_ids(&self) -> &[u32] {
+        &self.object_ids
+    }
+    /// Material ID of every object, in the order of `objects`.
+    pub fn material_ids(&self) -> &[u32] {
+        &self.material_ids
+    }
+    /// Materials by ID minus one.
+    pub fn materials(&self) -> &[MaterialType] {
+        &self.materials
+    }
+    pub fn lights(&self) -> &WorldLights {
+        &self.lights
+    }
+    /// Index into `lights` of the object at `object`, if it was added as a light.
+    pub fn light_index(&self, object: usize) -> Option<usize> {
+        self.light_indices[object]
+    }
+    /// Whether anything in the scene absorbs or scatters light between surfaces.
+    pub fn has_media(&self) -> bool {
+        self.atmosphere.is_some()
+            || self
+                .materials
+                .iter()
+                .any(|material| material.medium().is_some() || material.is_interface())
+    }
 
     pub fn add(&mut self, object: ObjectType) {
-        self.objects.push(object);
+        let material_id = self.material_id(object.material());
+        self.push(object, self.next_object_id(), material_id);
     }
 
     pub fn add_object_rot(
@@ -65,24 +186,111 @@ impl World {
         axis: Axis,
         angle: f32,
         material: MaterialType,
-    ) {
-        load_obj(path, origin, scale, material)
+    ) -> Result<(), tobj::LoadError> {
+        let (object_id, material_id) = (self.next_object_id(), self.material_id(&material));
+        load_obj(path, origin, scale, material)?
             .into_iter()
-            .for_each(|tri| self.objects.push(Rotated::new(axis, tri, angle)));
+            .for_each(|tri| self.push(Rotated::new(axis, tri, angle), object_id, material_id));
+        Ok(())
     }
 
-    pub fn add_object(&mut self, path: &Path, origin: Vec3, scale: f32, material: MaterialType) {
-        load_obj(path, origin, scale, material)
+    pub fn add_object(
+        &mut self,
+        path: &Path,
+        origin: Vec3,
+        scale: f32,
+        material: MaterialType,
+    ) -> Result<(), tobj::LoadError> {
+        let (object_id, material_id) = (self.next_object_id(), self.material_id(&material));
+        load_obj(path, origin, scale, material)?
             .into_iter()
-            .for_each(|tri| self.objects.push(tri));
+            .for_each(|tri| self.push(tri, object_id, material_id));
+        Ok(())
+    }
+
+    /// Adds a mesh with the materials of its MTL library, see `load_obj_mtl`, turned
+    /// by `rotation` around an axis if given.
+    pub fn add_object_mtl(
+        &mut self,
+        path: &Path,
+        origin: Vec3,
+        scale: f32,
+        rotation: Option<(Axis, f32)>,
+    ) -> Result<(), MtlError> {
+        let object_id = self.next_object_id();
+        let mesh = load_obj_mtl(path, origin, scale)?;
+        let material_ids: Vec<u32> = mesh.materials.iter().map(|m| self.material_id(m)).collect();
+        for (tri, material) in mesh.triangles {
+            let tri = match rotation {
+                Some((axis, angle)) => Rotated::new(axis, tri, angle),
+                None => tri,
+            };
+            self.push(tri, object_id, material_ids[material]);
+        }
+        Ok(())
     }
 
     pub fn add_light(&mut self, object: ObjectType) {
-        self.objects.push(object.clone());
+        self.add(object.clone());
+        if let Some(index) = self.light_indices.last_mut() {
+            *index = Some(self.lights.len());
+        }
         self.lights.push(object);
     }
 
-    pub fn render(mut self, camera: Camera) -> RenderedImage {
-        camera.render_threaded(&mut self)
+    /// A point spread uniformly over the area of a random light, with the light's
+    /// material there and the density of picking it by area. Photons and light paths
+    /// start from these.
+    pub fn random_light_point(&self) -> Option<(Intersection<'_>, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let light = &self.lights[random_int(0, self.lights.len() as u32) as usize];
+        let (point, normal) = light.random_point();
+        let pdf = 1.0 / (self.lights.len() as f32 * light.area());
+        // Look the point up again for its texture coordinates and material.
+        light
+            .intersects(&Ray::new(point + normal, -normal), 0.5, 1.5)
+            .map(|mut hit| {
+                hit.point = point;
+                hit.normal = normal;
+                (hit, pdf)
+            })
+    }
+
+    /// Medium a ray leaving the surface at `hit` along `direction` travels through.
+    /// Crossing a boundary with a medium leads into it when heading inside and back
+    /// out into the atmosphere otherwise, so media can't be nested.
+    pub fn medium_beyond<'a>(
+        &'a self,
+        hit: &Intersection<'a>,
+        direction: Vec3,
+    ) -> Option<&'a MediumType> {
+        if direction.dot(hit.outward_normal) < 0.0 {
+            hit.material.medium()
+        } else {
+            self.atmosphere.as_ref()
+        }
+    }
+
+    fn next_object_id(&self) -> u32 {
+        self.object_ids.last().map_or(1, |id| id + 1)
+    }
+
+    fn material_id(&mut self, material: &MaterialType) -> u32 {
+        self.materials.push(material.clone());
+        self.materials.len() as u32
+    }
+
+    fn push(&mut self, object: ObjectType, object_id: u32, material_id: u32) {
+        self.objects.push(object);
+        self.object_ids.push(object_id);
+        self.material_ids.push(material_id);
+        self.light_indices.push(None);
+    }
+
+    pub fn render(self, camera: Camera) -> Framebuffer {
+        camera.render_threaded(&self)
     }
 }
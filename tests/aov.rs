@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use glam::vec3;
+use tracer::{
+    materials::{emissivediffuse::EmissiveDiffuse, lambertian::Lambertian},
+    objects::sphere::Sphere,
+    utils::framebuffer::aov_path,
+    Aov, Camera, Color, PixelMap, SolidColor, World,
+};
+
+fn camera() -> Camera {
+    Camera::new(
+        vec3(0.0, 0.0, 5.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        40.0,
+        1.0,
+        0.0,
+        5.0,
+    )
+}
+
+fn world() -> World {
+    let mut world = World::new(PixelMap::from_color(Color::splat(0.5)))
+        .width(9)
+        .samples_per_pixel(4)
+        .max_depth(2)
+        .seed(1);
+    world.add(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        Lambertian::new(SolidColor::new(Color::new(0.8, 0.2, 0.1), None)),
+    ));
+    world.add_light(Sphere::new(
+        vec3(2.0, 3.0, 2.0),
+        0.5,
+        EmissiveDiffuse::new(SolidColor::new(Color::splat(4.0), None)),
+    ));
+
+    world
+}
+
+#[test]
+fn aovs_hold_first_hit_data() {
+    let image = world().aovs(&Aov::ALL).render(camera());
+    let center = 4 * 9 + 4;
+
+    assert_eq!(image.aovs().collect::<Vec<_>>(), Aov::ALL);
+    assert_eq!(
+        image.aov(Aov::Albedo).unwrap()[center],
+        Color::new(0.8, 0.2, 0.1)
+    );
+    assert!(image.aov(Aov::Normal).unwrap()[center].z > 0.9);
+    assert!((image.aov(Aov::Depth).unwrap()[center].x - 4.0).abs() < 0.1);
+    assert!((image.aov(Aov::Position).unwrap()[center].z - 1.0).abs() < 0.1);
+    assert_eq!(image.aov(Aov::ObjectId).unwrap()[center], Color::ONE);
+    assert_eq!(image.aov(Aov::MaterialId).unwrap()[center], Color::ONE);
+
+    for aov in Aov::ALL {
+        assert_eq!(image.aov(aov).unwrap()[0], Color::ZERO, "{:?}", aov);
+    }
+}
+
+#[test]
+fn aovs_do_not_change_the_beauty_pass() {
+    let beauty = world().render(camera());
+    let with_aovs = world().aovs(&[Aov::Albedo, Aov::Depth]).render(camera());
+
+    assert_eq!(beauty.pixels(), with_aovs.pixels());
+    assert!(beauty.aov(Aov::Albedo).is_none());
+}
+
+#[test]
+fn meshes_share_an_object_id_and_each_added_material_gets_its_own() {
+    let mut world = world();
+    let matte = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    world
+        .add_object(
+            Path::new("objs/monke.obj"),
+            vec3(0.0, 0.0, 0.0),
+            1.0,
+            matte.clone(),
+        )
+        .unwrap();
+    world.add(Sphere::new(vec3(0.0, 2.0, 0.0), 0.5, matte));
+
+    let triangles = world.objects().len() - 3;
+    assert_eq!(world.object_ids()[..2], [1, 2]);
+    assert!(world.object_ids()[2..2 + triangles]
+        .iter()
+        .all(|&id| id == 3));
+    assert_eq!(world.object_ids().last(), Some(&4));
+    assert_eq!(world.material_ids()[..3], [1, 2, 3]);
+    assert!(world.material_ids()[2..2 + triangles]
+        .iter()
+        .all(|&id| id == 3));
+    // An equal material added again is told apart by the call that added it.
+    assert_eq!(world.material_ids().last(), Some(&4));
+    assert_eq!(world.materials().len(), 4);
+}
+
+#[test]
+fn exr_output_stores_aovs_as_layers() {
+    let path = std::env::temp_dir().join("tracer-aovs.exr");
+    world()
+        .aovs(&[Aov::Normal, Aov::Depth, Aov::ObjectId])
+        .render(camera())
+        .save(&path)
+        .unwrap();
+
+    let meta = exr::meta::MetaData::read_from_file(&path, false).unwrap();
+    let channels: Vec<String> = meta.headers[0]
+        .channels
+        .list
+        .iter()
+        .map(|channel| channel.name.to_string())
+        .collect();
+
+    assert_eq!(
+        channels,
+        [
+            "B",
+            "G",
+            "R",
+            "depth.Z",
+            "normal.X",
+            "normal.Y",
+            "normal.Z",
+            "object_id.id"
+        ]
+    );
+}
+
+#[test]
+fn other_formats_write_aovs_next_to_the_output() {
+    let path = std::env::temp_dir().join("tracer-aovs.png");
+    world()
+        .aovs(&[Aov::Albedo, Aov::MaterialId])
+        .render(camera())
+        .save(&path)
+        .unwrap();
+
+    let albedo = aov_path(&path, Aov::Albedo);
+    assert_eq!(albedo.file_name().unwrap(), "tracer-aovs.albedo.png");
+    assert_eq!(image::open(albedo).unwrap().width(), 9);
+    assert!(aov_path(&path, Aov::MaterialId).exists());
+}
@@ -0,0 +1,285 @@
+use std::{fs::File, path::Path, thread};
+
+use glam::{vec3, Vec3};
+use image::{codecs::hdr::HdrEncoder, DynamicImage, Rgb, RgbImage};
+use tracer::{
+    materials::{emissivediffuse::EmissiveDiffuse, lambertian::Lambertian, texture::Texture},
+    objects::sphere::Sphere,
+    utils::tonemap,
+    Camera, CheckerBoard, Color, Image, Operator, PixelMap, Skybox, SolidColor, ToneMap, World,
+};
+
+fn camera(aspect_ratio: f32) -> Camera {
+    Camera::new(
+        vec3(0.0, 0.0, 5.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        5.0,
+    )
+}
+
+fn lit_world() -> World {
+    let mut world = World::new(PixelMap::from_color(Color::ZERO))
+        .width(24)
+        .samples_per_pixel(4)
+        .max_depth(4);
+
+    world.add(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        Lambertian::new(CheckerBoard::new(Color::ZERO, Color::ONE, 4.0)),
+    ));
+    world.add_light(Sphere::new(
+        vec3(2.0, 3.0, 2.0),
+        0.5,
+        EmissiveDiffuse::new(SolidColor::new(Color::splat(4.0), None)),
+    ));
+
+    world
+}
+
+#[test]
+fn renders_at_requested_size() {
+    let image = lit_world().render(camera(2.0));
+
+    assert_eq!(image.dimensions(), (24, 12));
+}
+
+#[test]
+fn empty_world_shows_skybox() {
+    let image = World::new(PixelMap::from_color(Color::new(0.25, 0.25, 0.25)))
+        .width(8)
+        .samples_per_pixel(2)
+        .render(camera(1.0));
+
+    for pixel in image.pixels() {
+        assert_eq!(*pixel, Color::splat(0.25));
+    }
+    for pixel in image.to_rgb8().pixels() {
+        assert_eq!(pixel.0, [137, 137, 137]);
+    }
+}
+
+#[test]
+fn emitters_are_visible() {
+    let mut world = World::new(PixelMap::from_color(Color::ZERO))
+        .width(8)
+        .samples_per_pixel(2);
+    world.add_light(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        EmissiveDiffuse::new(SolidColor::new(Color::ONE, None)),
+    ));
+
+    let image = world.render(camera(1.0));
+
+    assert_eq!(image.get_pixel(4, 4), Color::ONE);
+    assert_eq!(image.get_pixel(0, 0), Color::ZERO);
+}
+
+#[test]
+fn bright_emitters_are_not_clamped() {
+    let mut world = World::new(PixelMap::from_color(Color::ZERO))
+        .width(8)
+        .samples_per_pixel(2);
+    world.add_light(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        EmissiveDiffuse::new(SolidColor::new(Color::new(8.0, 4.0, 0.5), None)),
+    ));
+
+    let image = world.render(camera(1.0));
+
+    assert_eq!(image.get_pixel(4, 4), Color::new(8.0, 4.0, 0.5));
+    assert_eq!(image.to_rgb8().get_pixel(4, 4).0, [255, 255, 188]);
+
+    let image = image.with_tone_map(ToneMap::new(Operator::Reinhard).exposure(-1.0));
+    assert_eq!(image.to_rgb8().get_pixel(4, 4).0, [232, 214, 124]);
+}
+
+#[test]
+fn tone_map_operators_stay_in_display_range() {
+    let highlight = Color::new(1000.0, 10.0, 1.0);
+
+    for operator in [Operator::Clamp, Operator::Reinhard, Operator::Aces] {
+        let mapped = ToneMap::new(operator).apply(highlight);
+        assert!(mapped.min_element() >= 0.0 && mapped.max_element() <= 1.0);
+        assert_eq!(ToneMap::new(operator).apply(Color::ZERO), Color::ZERO);
+    }
+    assert!(ToneMap::new(Operator::Reinhard).apply(highlight).x < 1.0);
+    assert!(ToneMap::new(Operator::Aces).apply(Color::splat(0.5)).x < 1.0);
+}
+
+#[test]
+fn exposure_is_measured_in_stops() {
+    let tone_map = ToneMap::default().exposure(2.0);
+
+    assert_eq!(
+        tone_map.apply(Color::splat(0.125)),
+        ToneMap::default().apply(Color::splat(0.5))
+    );
+    assert!((tonemap::srgb_oetf(0.5) - 0.735_357).abs() < 1e-5);
+}
+
+#[test]
+fn framebuffers_round_trip_through_hdr_formats() {
+    let image = lit_world().seed(3).render(camera(1.0));
+
+    for extension in ["exr", "hdr"] {
+        let path = std::env::temp_dir().join(format!("tracer-framebuffer.{}", extension));
+        image.save(&path).unwrap();
+        let loaded = PixelMap::load(&path).unwrap();
+
+        assert_eq!((loaded.width, loaded.height), image.dimensions());
+        for (x, y) in (0..loaded.height).flat_map(|y| (0..loaded.width).map(move |x| (x, y))) {
+            let (pixel, expected) = (loaded.get_pixel((x, y)), image.get_pixel(x, y));
+            let error = (pixel - expected).abs().max_element();
+            assert!(error <= expected.max_element() / 64.0 + 1e-6);
+        }
+    }
+}
+
+#[test]
+fn seeded_renders_are_reproducible() {
+    let first = lit_world().seed(11).render(camera(1.0));
+    let second = lit_world().seed(11).render(camera(1.0));
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn image_textures_can_be_used_by_materials() {
+    let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])));
+    let mut world = World::new(PixelMap::from_color(Color::ZERO))
+        .width(8)
+        .samples_per_pixel(2);
+    world.add_light(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        EmissiveDiffuse::new(Image::new(red, None)),
+    ));
+
+    let image = world.render(camera(1.0));
+
+    assert_eq!(image.get_pixel(4, 4), Color::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn worlds_can_be_built_concurrently() {
+    let renders: Vec<_> = (0..4)
+        .map(|_| thread::spawn(|| lit_world().seed(5).render(camera(1.0))))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+
+    assert!(renders.windows(2).all(|pair| pair[0] == pair[1]));
+}
+
+#[test]
+fn hdr_image_textures_keep_their_range() {
+    let path = std::env::temp_dir().join("tracer-hdr-texture.exr");
+    let bright = image::Rgb32FImage::from_pixel(2, 2, Rgb([4.0, 2.0, 0.5]));
+    DynamicImage::ImageRgb32F(bright).save(&path).unwrap();
+
+    let texture = Image::load(&path, None).unwrap();
+
+    assert_eq!(
+        texture.get_color_uv((0.5, 0.5), Vec3::ZERO),
+        Color::new(4.0, 2.0, 0.5)
+    );
+}
+
+#[test]
+fn ldr_image_textures_are_normalized() {
+    let texture = Image::load(
+        Path::new("images/bricks.jpeg"),
+        Some(Path::new("images/bumpmap.jpeg")),
+    )
+    .unwrap();
+    let color = texture.get_color_uv((0.5, 0.5), Vec3::ZERO);
+
+    assert!(color.min_element() >= 0.0 && color.max_element() <= 1.0);
+}
+
+#[test]
+fn eight_bit_color_maps_are_decoded_from_srgb() {
+    let grey = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128, 128, 128])));
+    let path = std::env::temp_dir().join("tracer-srgb-texture.png");
+    grey.save(&path).unwrap();
+    let linear = tonemap::srgb_eotf(128.0 / 255.0);
+    assert!((linear - 0.215_861).abs() < 1e-5);
+
+    for texture in [Image::new(grey, None), Image::load(&path, None).unwrap()] {
+        let color = texture.get_color_uv((0.5, 0.5), Vec3::ZERO);
+        assert!((color - Color::splat(linear)).abs().max_element() < 1e-6);
+    }
+    // Bump maps hold directions rather than colors and are kept as stored.
+    let bump = PixelMap::load(&path).unwrap();
+    assert_eq!(bump.get_pixel((0, 0)), Color::splat(128.0 / 255.0));
+}
+
+#[test]
+fn hdr_skyboxes_keep_their_range() {
+    let path = std::env::temp_dir().join("tracer-hdr-skybox.exr");
+    let sky = image::Rgb32FImage::from_pixel(8, 4, Rgb([12.0, 6.0, 3.0]));
+    DynamicImage::ImageRgb32F(sky).save(&path).unwrap();
+
+    let skybox = Skybox::load(&path).unwrap().intensity(0.5);
+
+    assert_eq!(
+        skybox.dir_color(vec3(0.3, 0.8, -0.2)),
+        Color::new(6.0, 3.0, 1.5)
+    );
+}
+
+#[test]
+fn radiance_hdr_skyboxes_keep_their_range() {
+    // Powers of two times 8-bit mantissas survive the shared exponent of RGBE exactly.
+    let path = std::env::temp_dir().join("tracer-hdr-skybox.hdr");
+    let pixels = vec![Rgb([12.0f32, 6.0, 3.0]); 8 * 4];
+    HdrEncoder::new(File::create(&path).unwrap())
+        .encode(&pixels, 8, 4)
+        .unwrap();
+
+    let skybox = Skybox::load(&path).unwrap().intensity(0.5);
+
+    assert_eq!(
+        skybox.dir_color(vec3(0.3, 0.8, -0.2)),
+        Color::new(6.0, 3.0, 1.5)
+    );
+}
+
+#[test]
+fn skybox_rotation_turns_around_the_up_axis() {
+    let mut map = RgbImage::new(4, 2);
+    for (x, _, pixel) in map.enumerate_pixels_mut() {
+        *pixel = Rgb([x as u8 * 60, 0, 0]);
+    }
+    let map = PixelMap::from_image(DynamicImage::ImageRgb8(map));
+    let unrotated = map.dir_color(vec3(1.0, 0.0, 0.0));
+
+    let skybox = Skybox::new(map).rotation(90.0);
+
+    assert_eq!(
+        skybox.dir_color(skybox.to_world(vec3(1.0, 0.0, 0.0))),
+        unrotated
+    );
+    assert_ne!(skybox.dir_color(vec3(1.0, 0.0, 0.0)), unrotated);
+}
+
+#[test]
+fn images_under_two_pixels_have_no_camera_density() {
+    let camera = camera(1.0);
+    let ahead = vec3(0.0, 0.0, -1.0);
+    assert!(camera.direction_pdf(ahead, 16, 16) > 0.0);
+    assert!(camera.lens_sample(Vec3::ZERO, 16, 16).is_some());
+
+    for (width, height) in [(1, 16), (16, 1), (0, 0)] {
+        assert_eq!(camera.direction_pdf(ahead, width, height), 0.0);
+        assert!(camera.lens_sample(Vec3::ZERO, width, height).is_none());
+    }
+}
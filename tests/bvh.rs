@@ -0,0 +1,298 @@
+use std::path::Path;
+
+use glam::{vec3, Vec3};
+use tracer::{
+    materials::lambertian::Lambertian,
+    objects::{
+        obj::load_obj,
+        object::{Geometry, ObjectType},
+        plane::{Plane, PlaneType},
+        rotated::{Axis, Rotated},
+        sphere::Sphere,
+    },
+    random::{random_distribution, random_float, seed},
+    utils::bvh::{BvhOptions, BvhTree, SplitMethod},
+    world::physics::Ray,
+    Color, SolidColor,
+};
+
+fn scene() -> Vec<ObjectType> {
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let mut objects = load_obj(
+        Path::new("objs/monke.obj"),
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        material.clone(),
+    )
+    .unwrap();
+
+    seed(3);
+    for _ in 0..50 {
+        let center = vec3(
+            random_float(-4.0, 4.0),
+            random_float(-4.0, 4.0),
+            random_float(-4.0, 4.0),
+        );
+        objects.push(Sphere::new(
+            center,
+            random_float(0.05, 0.4),
+            material.clone(),
+        ));
+    }
+
+    objects
+}
+
+fn rays(count: usize) -> Vec<Ray> {
+    seed(7);
+    (0..count)
+        .map(|_| {
+            let origin = vec3(0.0, 0.0, 8.0);
+            let target = vec3(
+                random_float(-4.0, 4.0),
+                random_float(-4.0, 4.0),
+                random_distribution() - 0.5,
+            );
+            Ray::new(origin, target - origin)
+        })
+        .collect()
+}
+
+/// Closest hit by testing every object, for comparison with the tree.
+fn brute_force(objects: &[ObjectType], ray: &Ray) -> Option<(usize, f32)> {
+    objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| {
+            object
+                .intersects(ray, 0.001, f32::MAX)
+                .map(|hit| (index, hit.distance))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+fn assert_matches_brute_force(objects: &[ObjectType], options: BvhOptions) {
+    let tree = BvhTree::with_options(objects, options);
+
+    let mut hits = 0;
+    for ray in rays(2000) {
+        let expected = brute_force(objects, &ray);
+        let actual = tree
+            .hit(&ray, 0.001, f32::MAX)
+            .map(|hit| (hit.object, hit.distance));
+        assert_eq!(actual, expected, "{:?}", options);
+        let baseline = tree
+            .hit_recursive(&ray, 0.001, f32::MAX)
+            .map(|hit| (hit.object, hit.distance));
+        assert_eq!(baseline, expected, "{:?}", options);
+        hits += actual.is_some() as usize;
+    }
+    assert!(hits > 200);
+}
+
+#[test]
+fn trees_find_the_closest_hit() {
+    let objects = scene();
+
+    for max_leaf_size in [1, 4, 16] {
+        for split in [SplitMethod::Median, SplitMethod::Sah { bins: 8 }] {
+            let options = BvhOptions::default()
+                .split(split)
+                .max_leaf_size(max_leaf_size);
+            assert_matches_brute_force(&objects, options);
+        }
+    }
+}
+
+#[test]
+fn leaves_respect_the_maximum_size() {
+    let objects = scene();
+
+    for split in [SplitMethod::Median, SplitMethod::Sah { bins: 16 }] {
+        for max_leaf_size in [1, 3, 8] {
+            let options = BvhOptions::default()
+                .split(split)
+                .max_leaf_size(max_leaf_size);
+            let stats = BvhTree::with_options(&objects, options).stats();
+
+            assert_eq!(stats.primitives, objects.len());
+            assert!(stats.largest_leaf <= max_leaf_size);
+            assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+        }
+    }
+}
+
+#[test]
+fn sah_builds_cheaper_trees_than_median_splits() {
+    let objects = scene();
+    let median =
+        BvhTree::with_options(&objects, BvhOptions::default().split(SplitMethod::Median)).stats();
+    let sah = BvhTree::new(&objects).stats();
+
+    assert!(sah.sah_cost < median.sah_cost, "{:?} {:?}", sah, median);
+}
+
+#[test]
+fn coincident_objects_are_still_split() {
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let objects: Vec<_> = (0..10)
+        .map(|_| Sphere::new(Vec3::ZERO, 1.0, material.clone()))
+        .collect();
+
+    let stats = BvhTree::with_options(&objects, BvhOptions::default().max_leaf_size(2)).stats();
+
+    assert!(stats.largest_leaf <= 2);
+}
+
+#[test]
+fn display_reports_the_tree_statistics() {
+    let objects = scene();
+    let tree = BvhTree::new(&objects);
+    let stats = tree.stats();
+
+    let report = tree.to_string();
+    assert!(report.contains(&format!("{} hitables", objects.len())));
+    assert!(report.contains(&format!("{} nodes", stats.nodes)));
+    assert!(report.contains(&format!("depth {}", stats.depth)));
+    assert!(report.contains(&format!("SAH cost {:.2}", stats.sah_cost)));
+    assert_eq!(BvhTree::new(&[]).stats().nodes, 0);
+}
+
+#[test]
+fn unbalanced_scenes_stay_within_the_traversal_stack() {
+    // Exponentially spaced spheres, split very unevenly by the SAH.
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let objects: Vec<_> = (0..150)
+        .map(|i| Sphere::new(vec3(1.3f32.powi(i), 0.0, 0.0), 0.5, material.clone()))
+        .collect();
+
+    let tree = BvhTree::with_options(&objects, BvhOptions::default().max_leaf_size(1));
+    assert!(tree.stats().depth <= 64, "{}", tree);
+
+    for x in [-1.0, 3.0, 1e10, 1e15] {
+        for direction in [Vec3::X, -Vec3::X] {
+            let ray = Ray::new(vec3(x, 0.0, 0.0), direction);
+            let expected = brute_force(&objects, &ray);
+            let actual = tree
+                .hit(&ray, 0.001, f32::MAX)
+                .map(|hit| (hit.object, hit.distance));
+            assert_eq!(actual, expected);
+        }
+    }
+}
+
+#[test]
+fn large_meshes_are_built_in_parallel() {
+    // Large enough for the top of the tree to be split across threads.
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let objects = load_obj(
+        Path::new("objs/bunny.obj"),
+        vec3(0.0, -2.0, 0.0),
+        25.0,
+        material,
+    )
+    .unwrap();
+    assert!(objects.len() > 4096);
+
+    for split in [SplitMethod::Median, SplitMethod::Sah { bins: 16 }] {
+        let tree = BvhTree::with_options(&objects, BvhOptions::default().split(split));
+        let stats = tree.stats();
+        assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+
+        let mut hits = 0;
+        for ray in rays(300) {
+            let expected = brute_force(&objects, &ray);
+            let actual = tree
+                .hit(&ray, 0.001, f32::MAX)
+                .map(|hit| (hit.object, hit.distance));
+            assert_eq!(actual, expected);
+            hits += actual.is_some() as usize;
+        }
+        assert!(hits > 50, "{}", hits);
+    }
+}
+
+#[test]
+fn median_splits_build_the_same_tree_every_time() {
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    let objects = load_obj(
+        Path::new("objs/bunny.obj"),
+        vec3(0.0, -2.0, 0.0),
+        25.0,
+        material,
+    )
+    .unwrap();
+    let options = BvhOptions::default().split(SplitMethod::Median);
+
+    let first = BvhTree::with_options(&objects, options);
+    for i in 0..3 {
+        seed(100 + i);
+        let tree = BvhTree::with_options(&objects, options);
+        assert_eq!(tree.stats(), first.stats());
+        for ray in rays(100) {
+            assert_eq!(
+                tree.hit_cost(&ray, 0.001, f32::MAX),
+                first.hit_cost(&ray, 0.001, f32::MAX)
+            );
+        }
+    }
+}
+
+#[test]
+fn occlusion_agrees_with_the_closest_hit() {
+    let mut objects = scene();
+    let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+    objects.push(Plane::new(
+        PlaneType::XY,
+        -5.0,
+        5.0,
+        -5.0,
+        5.0,
+        -4.5,
+        material.clone(),
+    ));
+    objects.push(Rotated::new(
+        Axis::Y,
+        Plane::new(PlaneType::YZ, -1.0, 1.0, -1.0, 1.0, 3.0, material),
+        30.0,
+    ));
+    let tree = BvhTree::new(&objects);
+
+    let mut blocked = 0;
+    for (i, ray) in rays(2000).iter().enumerate() {
+        let t_max = [f32::MAX, 0.5, 0.8, 1.0][i % 4];
+        let hit = tree.hit(ray, 0.001, t_max);
+        assert_eq!(tree.occluded(ray, 0.001, t_max), hit.is_some());
+
+        if let Some(hit) = hit {
+            let object = &objects[hit.object];
+            assert_eq!(object.hit_distance(ray, 0.001, t_max), Some(hit.distance));
+            blocked += 1;
+        }
+    }
+    assert!(blocked > 200 && blocked < 1800, "{}", blocked);
+}
+
+#[test]
+fn hit_cost_counts_the_work_of_a_query() {
+    let objects = scene();
+    let tree = BvhTree::new(&objects);
+    let stats = tree.stats();
+
+    let mut objects_tested = 0;
+    for ray in rays(500) {
+        let cost = tree.hit_cost(&ray, 0.001, f32::MAX);
+        assert!(cost.nodes >= 1 && cost.nodes <= stats.nodes);
+        if tree.hit(&ray, 0.001, f32::MAX).is_some() {
+            assert!(cost.objects >= 1);
+        }
+        objects_tested += cost.objects;
+    }
+
+    // The tree skips most of the objects for most rays.
+    assert!(
+        objects_tested < 500 * objects.len() / 10,
+        "{}",
+        objects_tested
+    );
+}
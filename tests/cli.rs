@@ -0,0 +1,104 @@
+use std::{fs, path::PathBuf, process::Command};
+
+const SCENE: &str = r#"
+[render]
+width = 16
+samples_per_pixel = 2
+
+[skybox]
+color = [0.1, 0.2, 0.3]
+
+[camera]
+look_from = [0.0, 0.0, 5.0]
+look_at = [0.0, 0.0, 0.0]
+vfov = 40.0
+aspect_ratio = 2.0
+"#;
+
+/// Writes `source` to a scene file of its own in the temporary directory.
+fn scene(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tracer-cli-{}.toml", name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn tracer() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_tracer"))
+}
+
+#[test]
+fn flags_override_the_scene_file() {
+    let output = std::env::temp_dir().join("tracer-cli-override.png");
+    let status = tracer()
+        .arg(scene("override", SCENE))
+        .args(["--width", "4", "--samples-per-pixel", "1", "--no-denoise"])
+        .arg("--output")
+        .arg(&output)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(image::image_dimensions(&output).unwrap(), (4, 2));
+}
+
+#[test]
+fn missing_assets_exit_with_an_error() {
+    let source = SCENE.replace("color = [0.1, 0.2, 0.3]", "image = \"missing.exr\"");
+    let output = tracer().arg(scene("missing", &source)).output().unwrap();
+
+    assert!(!output.status.success());
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        message.contains("could not load") && message.contains("missing.exr"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn images_too_small_to_render_are_rejected() {
+    for args in [["--width", "1"], ["--samples-per-pixel", "0"]] {
+        let output = tracer()
+            .arg(scene("small", SCENE))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", args);
+    }
+
+    // Wide enough on its own, but only one pixel high at the scene's aspect ratio.
+    let output = tracer()
+        .arg(scene(
+            "flat",
+            &SCENE.replace("aspect_ratio = 2.0", "aspect_ratio = 3.0"),
+        ))
+        .args(["--width", "4"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(message.contains("needs at least 2"), "{}", message);
+}
+
+#[test]
+fn photon_settings_need_the_photon_mapper() {
+    let output = tracer()
+        .arg(scene("photons", SCENE))
+        .args(["--width", "4", "--photons", "1000"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(message.contains("photon-mapping"), "{}", message);
+
+    let output = std::env::temp_dir().join("tracer-cli-photons.png");
+    let status = tracer()
+        .arg(scene("photon-mapping", SCENE))
+        .args(["--width", "4", "--samples-per-pixel", "1"])
+        .args(["--integrator", "ppm", "--photons", "1000"])
+        .arg("--output")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -0,0 +1,10 @@
+use tracer::{Color, Framebuffer};
+
+/// Average color of all the pixels of `image`.
+pub fn mean(image: &Framebuffer) -> Color {
+    image
+        .pixels()
+        .iter()
+        .fold(Color::ZERO, |sum, pixel| sum + *pixel)
+        / image.pixels().len() as f32
+}
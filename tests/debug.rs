@@ -0,0 +1,170 @@
+use glam::{vec3, Vec3};
+use tracer::{
+    integrators::debug::{Albedo, AmbientOcclusion, BvhCost, Depth, Normals},
+    materials::{interface::Interface, lambertian::Lambertian},
+    media::homogeneous::Homogeneous,
+    objects::{
+        plane::{Plane, PlaneType},
+        sphere::Sphere,
+    },
+    Camera, Color, PixelMap, Skybox, SolidColor, World,
+};
+
+fn world() -> World {
+    World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(5)
+        .samples_per_pixel(4)
+        .seed(1)
+}
+
+/// Looks down the Z axis at a unit sphere that fills the whole view.
+fn sphere_world() -> World {
+    let mut world = world();
+    world.add(Sphere::new(
+        Vec3::ZERO,
+        1.0,
+        Lambertian::new(SolidColor::new(Color::new(0.8, 0.4, 0.2), None)),
+    ));
+    world
+}
+
+fn close_up() -> Camera {
+    Camera::new(vec3(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y, 4.0, 1.0, 0.0, 5.0)
+}
+
+#[test]
+fn normals_albedo_and_depth_describe_the_first_hit() {
+    for pixel in sphere_world()
+        .integrator(Normals::new())
+        .render(close_up())
+        .pixels()
+    {
+        assert!(pixel.z > 0.95, "{:?}", pixel);
+        assert!((pixel.x - 0.5).abs() < 0.1 && (pixel.y - 0.5).abs() < 0.1);
+    }
+
+    for pixel in sphere_world()
+        .integrator(Albedo::new())
+        .render(close_up())
+        .pixels()
+    {
+        assert_eq!(*pixel, Color::new(0.8, 0.4, 0.2));
+    }
+
+    for pixel in sphere_world()
+        .integrator(Depth::new(8.0))
+        .render(close_up())
+        .pixels()
+    {
+        assert!((pixel.x - 0.5).abs() < 0.03, "{:?}", pixel);
+    }
+}
+
+#[test]
+fn debug_views_are_black_where_nothing_is_hit() {
+    for integrator in [Normals::new(), Albedo::new(), Depth::new(8.0)] {
+        let image = world().integrator(integrator).render(close_up());
+        assert!(image.pixels().iter().all(|pixel| *pixel == Color::ZERO));
+    }
+}
+
+#[test]
+fn ambient_occlusion_darkens_enclosed_surfaces() {
+    // A floor under a ceiling one unit above it, seen from in between.
+    let render = |distance| {
+        let mut world = world().integrator(AmbientOcclusion::new(distance, 32));
+        let material = Lambertian::new(SolidColor::new(Color::splat(0.5), None));
+        for y in [0.0, 1.0] {
+            world.add(Plane::new(
+                PlaneType::ZX,
+                -1000.0,
+                1000.0,
+                -1000.0,
+                1000.0,
+                y,
+                material.clone(),
+            ));
+        }
+        let camera = Camera::new(
+            vec3(0.0, 0.5, 0.0),
+            Vec3::ZERO,
+            Vec3::Z,
+            40.0,
+            1.0,
+            0.0,
+            0.5,
+        );
+        world.render(camera)
+    };
+
+    // Nothing is within reach of short rays, long ones all hit the ceiling.
+    assert!(render(0.5)
+        .pixels()
+        .iter()
+        .all(|pixel| *pixel == Color::ONE));
+    assert!(render(100.0)
+        .pixels()
+        .iter()
+        .all(|pixel| *pixel == Color::ZERO));
+}
+
+#[test]
+fn bvh_cost_grows_with_the_objects_tested() {
+    let cost = |world: World| world.integrator(BvhCost::new(20)).render(close_up());
+
+    // An empty scene costs nothing and shows up blue.
+    for pixel in cost(world()).pixels() {
+        assert_eq!(*pixel, vec3(0.0, 0.0, 1.0));
+    }
+
+    // A pile of coincident spheres has to be tested in full.
+    let mut crowded = world();
+    for _ in 0..50 {
+        crowded.add(Sphere::new(
+            Vec3::ZERO,
+            1.0,
+            Lambertian::new(SolidColor::new(Color::ONE, None)),
+        ));
+    }
+    for pixel in cost(crowded).pixels() {
+        assert_eq!(*pixel, vec3(1.0, 0.0, 0.0));
+    }
+}
+
+#[test]
+fn ambient_occlusion_sees_through_media_boundaries() {
+    // Looks down through the boundary of an empty medium onto an open floor.
+    let render = |boundary: bool| {
+        let mut world = world().integrator(AmbientOcclusion::new(2.0, 32));
+        world.add(Plane::new(
+            PlaneType::ZX,
+            -1000.0,
+            1000.0,
+            -1000.0,
+            1000.0,
+            0.0,
+            Lambertian::new(SolidColor::new(Color::splat(0.5), None)),
+        ));
+        if boundary {
+            world.add(Sphere::new(
+                vec3(0.0, 0.5, 0.0),
+                1.0,
+                Interface::new(Homogeneous::new(Color::ZERO, Color::ZERO, 0.0)),
+            ));
+        }
+        let camera = Camera::new(
+            vec3(0.0, 3.0, 0.0),
+            Vec3::ZERO,
+            Vec3::Z,
+            20.0,
+            1.0,
+            0.0,
+            3.0,
+        );
+        world.render(camera)
+    };
+
+    let image = render(true);
+    assert!(image.pixels().iter().all(|pixel| *pixel == Color::ONE));
+    assert_eq!(image.pixels(), render(false).pixels());
+}
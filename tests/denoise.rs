@@ -0,0 +1,123 @@
+use glam::vec3;
+use tracer::{
+    materials::{emissivediffuse::EmissiveDiffuse, lambertian::Lambertian},
+    objects::sphere::Sphere,
+    Aov, Camera, Color, Denoiser, Framebuffer, PixelMap, SolidColor, World,
+};
+
+const SIZE: u32 = 32;
+
+/// A framebuffer whose left and right halves face different ways and have different
+/// brightness, with deterministic noise on top.
+fn noisy_halves() -> Framebuffer {
+    let mut image = Framebuffer::new(SIZE, SIZE).with_aovs(&[Aov::Albedo, Aov::Normal]);
+    let left = |i: usize| (i as u32 % SIZE) < SIZE / 2;
+
+    for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+        let noise = ((i * 7919) % 13) as f32 / 13.0 - 0.5;
+        let base = if left(i) { 0.2 } else { 0.8 };
+        *pixel = Color::splat(base + 0.3 * noise);
+    }
+    image.aov_mut(Aov::Albedo).unwrap().fill(Color::splat(0.5));
+    for (i, normal) in image.aov_mut(Aov::Normal).unwrap().iter_mut().enumerate() {
+        *normal = if left(i) { Color::X } else { Color::Z };
+    }
+
+    image
+}
+
+fn spread(values: impl Iterator<Item = f32>, mean: f32) -> f32 {
+    values.map(|v| (v - mean).abs()).fold(0.0, f32::max)
+}
+
+#[test]
+fn denoising_smooths_noise_but_keeps_feature_edges() {
+    let noisy = noisy_halves();
+    let mut denoised = noisy.clone();
+    Denoiser::default().denoise(&mut denoised);
+
+    let column = |image: &Framebuffer, x: u32| {
+        (2..SIZE - 2)
+            .map(|y| image.get_pixel(x, y).x)
+            .collect::<Vec<_>>()
+    };
+
+    for (x, mean) in [(SIZE / 2 - 1, 0.2), (SIZE / 2, 0.8), (4, 0.2), (27, 0.8)] {
+        let before = spread(column(&noisy, x).into_iter(), mean);
+        let after = spread(column(&denoised, x).into_iter(), mean);
+        assert!(
+            after < before / 2.0,
+            "column {}: {} -> {}",
+            x,
+            before,
+            after
+        );
+    }
+}
+
+#[test]
+fn zero_sigmas_leave_the_image_unchanged() {
+    let noisy = noisy_halves();
+    let mut denoised = noisy.clone();
+    Denoiser::default()
+        .sigma_spatial(0.0)
+        .sigma_color(0.0)
+        .sigma_albedo(0.0)
+        .sigma_normal(0.0)
+        .denoise(&mut denoised);
+
+    // Without any spread no neighbour counts, rather than every pixel turning NaN.
+    for (before, after) in noisy.pixels().iter().zip(denoised.pixels()) {
+        assert!(
+            (*before - *after).abs().max_element() < 1e-6,
+            "{} vs {}",
+            before,
+            after
+        );
+    }
+}
+
+#[test]
+fn denoised_renders_only_keep_requested_passes() {
+    let world = || {
+        let mut world = World::new(PixelMap::from_color(Color::splat(0.2)))
+            .width(12)
+            .samples_per_pixel(2)
+            .max_depth(3)
+            .seed(4);
+        world.add(Sphere::new(
+            vec3(0.0, 0.0, 0.0),
+            1.0,
+            Lambertian::new(SolidColor::new(Color::splat(0.6), None)),
+        ));
+        world.add_light(Sphere::new(
+            vec3(1.0, 2.0, 2.0),
+            0.3,
+            EmissiveDiffuse::new(SolidColor::new(Color::splat(8.0), None)),
+        ));
+        world
+    };
+    let camera = || {
+        Camera::new(
+            vec3(0.0, 0.0, 5.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            5.0,
+        )
+    };
+
+    let noisy = world().render(camera());
+    let denoised = world().denoise(Denoiser::default()).render(camera());
+    assert_ne!(noisy.pixels(), denoised.pixels());
+    assert_eq!(denoised.aovs().count(), 0);
+
+    let with_depth = world()
+        .aovs(&[Aov::Depth])
+        .denoise(Denoiser::default())
+        .render(camera());
+    assert_eq!(with_depth.aovs().collect::<Vec<_>>(), [Aov::Depth]);
+    assert_eq!(with_depth.pixels(), denoised.pixels());
+}
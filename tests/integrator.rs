@@ -0,0 +1,354 @@
+mod common;
+
+use std::f32::consts::{FRAC_1_PI, PI};
+
+use common::mean;
+use glam::{vec3, Vec3};
+use image::{DynamicImage, Rgb32FImage};
+use tracer::{
+    integrators::{bidirectional::Bidirectional, mixture::Mixture, path::PathTracer},
+    materials::{
+        dielectric::Dielectric, emissivediffuse::EmissiveDiffuse, interface::Interface,
+        lambertian::Lambertian,
+    },
+    media::homogeneous::Homogeneous,
+    objects::{
+        plane::{Plane, PlaneType},
+        sphere::Sphere,
+    },
+    utils::sampling::PDF,
+    Bounces, Camera, Color, Framebuffer, Heuristic, PixelMap, Skybox, SolidColor, World,
+};
+
+fn camera() -> Camera {
+    Camera::new(
+        vec3(0.0, 1.0, 5.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        40.0,
+        1.0,
+        0.0,
+        5.0,
+    )
+}
+
+/// A diffuse sphere on a diffuse floor, lit by a small bright sphere.
+fn lit_scene(skybox: Skybox) -> World {
+    let mut world = World::new(skybox).width(10).max_depth(4).seed(11);
+    world.add(Sphere::new(
+        vec3(0.0, 0.0, 0.0),
+        1.0,
+        Lambertian::new(SolidColor::new(Color::new(0.7, 0.5, 0.3), None)),
+    ));
+    world.add(Plane::new(
+        PlaneType::ZX,
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        -1.0,
+        Lambertian::new(SolidColor::new(Color::splat(0.5), None)),
+    ));
+    world.add_light(Sphere::new(
+        vec3(2.0, 3.0, 1.0),
+        0.3,
+        EmissiveDiffuse::new(SolidColor::new(Color::splat(40.0), None)),
+    ));
+
+    world
+}
+
+/// An environment that is dark except for a bright patch above the scene.
+fn sun_skybox() -> Skybox {
+    let mut image = Rgb32FImage::from_pixel(32, 16, image::Rgb([0.05, 0.05, 0.1]));
+    for x in 12..16 {
+        for y in 3..5 {
+            image.put_pixel(x, y, image::Rgb([60.0, 50.0, 40.0]));
+        }
+    }
+
+    Skybox::new(PixelMap::from_image_hdr(DynamicImage::ImageRgb32F(image)))
+}
+
+/// Root mean square difference of two renders, relative to the mean of `reference`.
+fn relative_error(image: &Framebuffer, reference: &Framebuffer) -> f32 {
+    let squared: f32 = image
+        .pixels()
+        .iter()
+        .zip(reference.pixels())
+        .map(|(a, b)| (*a - *b).length_squared())
+        .sum();
+    let count = image.pixels().len() as f32;
+
+    (squared / count).sqrt() / (mean(reference).length() / 3f32.sqrt())
+}
+
+fn assert_converges_with_less_noise(world: impl Fn() -> World) {
+    let reference = world()
+        .integrator(Mixture::new())
+        .samples_per_pixel(1024)
+        .render(camera());
+    let mixture = world()
+        .integrator(Mixture::new())
+        .samples_per_pixel(32)
+        .render(camera());
+    let path = world()
+        .integrator(PathTracer::new())
+        .samples_per_pixel(32)
+        .render(camera());
+    let converged = world()
+        .integrator(PathTracer::new())
+        .samples_per_pixel(1024)
+        .render(camera());
+
+    let difference = (mean(&converged) - mean(&reference)).abs() / mean(&reference);
+    assert!(difference.max_element() < 0.03, "{:?}", difference);
+
+    let (mixture_error, path_error) = (
+        relative_error(&mixture, &reference),
+        relative_error(&path, &reference),
+    );
+    assert!(
+        path_error < 0.7 * mixture_error,
+        "{} vs {}",
+        path_error,
+        mixture_error
+    );
+}
+
+#[test]
+fn light_sampling_converges_with_less_noise() {
+    assert_converges_with_less_noise(|| lit_scene(Skybox::new(PixelMap::from_color(Color::ZERO))));
+}
+
+#[test]
+fn environment_sampling_converges_with_less_noise() {
+    assert_converges_with_less_noise(|| lit_scene(sun_skybox()));
+}
+
+#[test]
+fn both_heuristics_converge_to_the_same_image() {
+    let render = |heuristic| {
+        lit_scene(sun_skybox())
+            .heuristic(heuristic)
+            .samples_per_pixel(256)
+            .render(camera())
+    };
+    let (balance, power) = (render(Heuristic::Balance), render(Heuristic::Power));
+
+    let difference = (mean(&balance) - mean(&power)).abs() / mean(&power);
+    assert!(difference.max_element() < 0.03, "{:?}", difference);
+}
+
+#[test]
+fn heuristic_weights_sum_to_one() {
+    for heuristic in [Heuristic::Balance, Heuristic::Power] {
+        for (a, b) in [(1.0, 1.0), (0.2, 5.0), (3.0, 0.0)] {
+            let sum = heuristic.weight(a, b) + heuristic.weight(b, a);
+            assert!((sum - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(heuristic.weight(0.0, 0.0), 0.0);
+    }
+    assert!(Heuristic::Power.weight(2.0, 1.0) > Heuristic::Balance.weight(2.0, 1.0));
+}
+
+#[test]
+fn both_integrators_match_the_analytic_irradiance() {
+    // A white floor lit only by `sun_skybox` reflects the cosine weighted average of
+    // the environment over the upper hemisphere, integrated numerically here.
+    let (width, height) = (32, 16);
+    let mut expected = 0.0;
+    for row in 0..height {
+        let radiance = |column| {
+            if (12..16).contains(&column) && (3..5).contains(&row) {
+                60.0
+            } else {
+                0.05
+            }
+        };
+        let steps = 200;
+        let projected_area: f32 = (0..steps)
+            .map(|k| {
+                let theta = (row as f32 + (k as f32 + 0.5) / steps as f32) * PI / height as f32;
+                (theta.cos().max(0.0) * theta.sin()) * PI / (height * steps) as f32
+            })
+            .sum();
+        for column in 0..width {
+            expected += radiance(column) * projected_area * 2.0 * PI / width as f32 / PI;
+        }
+    }
+
+    for integrator in [Mixture::new(), PathTracer::new()] {
+        let mut world = World::new(sun_skybox())
+            .width(4)
+            .max_depth(2)
+            .samples_per_pixel(4096)
+            .seed(5)
+            .integrator(integrator);
+        world.add(Plane::new(
+            PlaneType::ZX,
+            -100.0,
+            100.0,
+            -100.0,
+            100.0,
+            -1.0,
+            Lambertian::new(SolidColor::new(Color::ONE, None)),
+        ));
+        let camera = Camera::new(
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            10.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+
+        let red = mean(&world.render(camera)).x;
+        assert!(
+            (red - expected).abs() < 0.03 * expected,
+            "{:?}: {} vs {}",
+            integrator,
+            red,
+            expected
+        );
+    }
+}
+
+#[test]
+fn cosine_pdf_matches_its_samples() {
+    let pdf = PDF::cosine(Vec3::Y);
+
+    assert!((pdf.value(Vec3::Y) - FRAC_1_PI).abs() < 1e-6);
+    assert_eq!(pdf.value(-Vec3::Y), 0.0);
+    assert_eq!(pdf.value(vec3(1.0, -0.1, 0.0)), 0.0);
+
+    // The mean cosine of a cosine weighted hemisphere is 2/3.
+    let count = 20000;
+    let mut cosine = 0.0;
+    for _ in 0..count {
+        let direction = pdf.generate();
+        assert!((direction.length() - 1.0).abs() < 1e-4);
+        cosine += direction.y / count as f32;
+    }
+    assert!((cosine - 2.0 / 3.0).abs() < 0.01, "{}", cosine);
+}
+
+#[test]
+fn russian_roulette_keeps_the_mean() {
+    let render = |roulette| {
+        let world = lit_scene(sun_skybox());
+        let bounces = Bounces {
+            roulette,
+            ..world.bounces
+        };
+        world
+            .bounces(bounces)
+            .samples_per_pixel(512)
+            .render(camera())
+    };
+    let (always, never) = (render(0), render(u32::MAX));
+
+    let difference = (mean(&always) - mean(&never)).abs() / mean(&never);
+    assert!(difference.max_element() < 0.03, "{:?}", difference);
+}
+
+#[test]
+fn deep_dielectric_paths_are_followed_to_the_end() {
+    // A block of glass balls that rays wander through for hundreds of bounces. Glass
+    // absorbs nothing, so without limits every pixel shows the sky unchanged.
+    let sky = Color::new(0.2, 0.4, 0.8);
+    let mut world = World::new(Skybox::new(PixelMap::from_color(sky)))
+        .width(4)
+        .samples_per_pixel(4)
+        .bounces(Bounces {
+            specular: u32::MAX,
+            transmission: u32::MAX,
+            roulette: u32::MAX,
+            ..Bounces::default()
+        })
+        .seed(3);
+    for i in 0..1000 {
+        let center = vec3((i % 10) as f32, (i / 10 % 10) as f32, (i / 100) as f32);
+        world.add(Sphere::new(
+            center - Vec3::splat(4.5),
+            0.45,
+            Dielectric::new(1.5),
+        ));
+    }
+
+    let image = world.render(camera());
+    for pixel in image.pixels() {
+        assert!((*pixel - sky).abs().max_element() < 1e-4, "{:?}", pixel);
+    }
+}
+
+#[test]
+fn bidirectional_matches_the_path_tracer() {
+    let render = |integrator| {
+        lit_scene(sun_skybox())
+            .integrator(integrator)
+            .samples_per_pixel(512)
+            .render(camera())
+    };
+    let (path, bidirectional) = (render(PathTracer::new()), render(Bidirectional::new()));
+
+    let difference = (mean(&bidirectional) - mean(&path)).abs() / mean(&path);
+    assert!(difference.max_element() < 0.03, "{:?}", difference);
+}
+
+#[test]
+fn bidirectional_renders_caustics_with_less_noise() {
+    // Light focused on the floor by a glass ball can only be found by camera paths
+    // that refract through the ball and happen to hit the small light.
+    let render = |integrator, seed| {
+        let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ZERO)))
+            .width(16)
+            .max_depth(6)
+            .samples_per_pixel(128)
+            .seed(seed)
+            .integrator(integrator);
+        world.add(Sphere::new(vec3(0.0, 0.0, 0.0), 0.7, Dielectric::new(1.5)));
+        world.add(Plane::new(
+            PlaneType::ZX,
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            -1.0,
+            Lambertian::new(SolidColor::new(Color::splat(0.5), None)),
+        ));
+        world.add_light(Sphere::new(
+            vec3(2.0, 3.0, 0.0),
+            0.2,
+            EmissiveDiffuse::new(SolidColor::new(Color::splat(100.0), None)),
+        ));
+        world.render(camera())
+    };
+    let noise = |integrator| relative_error(&render(integrator, 1), &render(integrator, 2));
+
+    let (path, bidirectional) = (noise(PathTracer::new()), noise(Bidirectional::new()));
+    assert!(bidirectional < 0.5 * path, "{} vs {}", bidirectional, path);
+}
+
+#[test]
+fn bidirectional_joins_paths_through_media_boundaries() {
+    // An empty medium around the light lies between it and every lit surface.
+    let render = |boundary: bool| {
+        let mut world = lit_scene(Skybox::new(PixelMap::from_color(Color::ZERO)))
+            .integrator(Bidirectional::new())
+            .samples_per_pixel(256);
+        if boundary {
+            world.add(Sphere::new(
+                vec3(2.0, 3.0, 1.0),
+                1.0,
+                Interface::new(Homogeneous::new(Color::ZERO, Color::ZERO, 0.0)),
+            ));
+        }
+        world.render(camera())
+    };
+    let (open, bounded) = (render(false), render(true));
+
+    let difference = (mean(&bounded) - mean(&open)).abs() / mean(&open);
+    assert!(difference.max_element() < 0.03, "{:?}", difference);
+}
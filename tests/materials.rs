@@ -0,0 +1,332 @@
+mod common;
+
+use std::f32::consts::PI;
+
+use common::mean;
+use glam::{vec3, Vec3};
+use image::{DynamicImage, Rgb, Rgb32FImage};
+use tracer::{
+    integrators::{bidirectional::Bidirectional, path::PathTracer},
+    materials::{
+        conductor::Conductor,
+        dielectric::Dielectric,
+        material::{Material, MaterialType, ScatterType},
+        microfacet::{alpha, Bsdf, Layers},
+        principled::Principled,
+    },
+    objects::sphere::Sphere,
+    random::{random_sphere_distribution, seed},
+    world::physics::{Intersection, Ray},
+    Camera, Color, Image, IntegratorType, PixelMap, Skybox, SolidColor, World,
+};
+
+/// Light seen straight through a glass ball of `radius` in front of a white sky.
+fn through_ball(radius: f32, glass: MaterialType, integrator: IntegratorType) -> Color {
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(4)
+        .samples_per_pixel(4)
+        .seed(1)
+        .integrator(integrator);
+    world.add(Sphere::new(Vec3::ZERO, radius, glass));
+    let camera = Camera::new(vec3(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y, 0.5, 1.0, 0.0, 5.0);
+
+    mean(&world.render(camera))
+}
+
+/// Rough metal and glass seen from a few angles, the glass from both sides. Glass
+/// carries importance so that, like metal, it never scatters more than it receives.
+fn rough_surfaces() -> Vec<Bsdf> {
+    let mut surfaces = vec![];
+    for roughness in [0.5, 0.75, 1.0] {
+        let alpha = alpha(roughness).unwrap();
+        for angle in [0.1f32, 0.8, 1.3] {
+            let wo = vec3(angle.sin(), 0.0, angle.cos());
+            surfaces.push(Bsdf::conductor(Vec3::Z, wo, alpha, Color::ONE));
+            for wo in [wo, -wo] {
+                surfaces.push(Bsdf::dielectric(Vec3::Z, wo, alpha, 1.5, Color::ONE).adjoint());
+            }
+        }
+    }
+    surfaces
+}
+
+/// Direction a ray going straight down leaves a flat glass surface facing up.
+fn refracted(glass: &MaterialType) -> Vec3 {
+    let ray = Ray::new(vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0));
+    let hit = Intersection::new(1.0, Vec3::ZERO, Vec3::Y, Vec3::Y, glass, (0.5, 0.5));
+    // Straight down, at most 4% of the light is reflected.
+    loop {
+        if let Some(ScatterType::Specular { specular, .. }) = glass.scatter(&ray, &hit) {
+            if specular.direction.y < 0.0 {
+                return specular.direction.normalize();
+            }
+        }
+    }
+}
+
+#[test]
+fn thick_glass_absorbs_more_than_thin_glass() {
+    // An index of 1 doesn't bend or reflect rays, so only absorption is left.
+    let color = Color::new(0.9, 0.6, 0.3);
+    let glass = || Dielectric::glass(1.0).absorption(color, 0.5).into();
+
+    let thin = through_ball(0.5, glass(), PathTracer::new());
+    let thick = through_ball(1.0, glass(), PathTracer::new());
+    let expected = |distance: f32| {
+        Color::new(
+            color.x.powf(0.5 * distance),
+            color.y.powf(0.5 * distance),
+            color.z.powf(0.5 * distance),
+        )
+    };
+    assert!(
+        (thin - expected(1.0)).abs().max_element() < 0.01,
+        "{}",
+        thin
+    );
+    assert!(
+        (thick - expected(2.0)).abs().max_element() < 0.01,
+        "{}",
+        thick
+    );
+    assert_eq!(
+        through_ball(1.0, Dielectric::new(1.0), PathTracer::new()),
+        Color::ONE
+    );
+}
+
+#[test]
+fn glass_refracts_through_its_normal_map() {
+    let bump = Rgb32FImage::from_pixel(1, 1, Rgb([0.5, 0.0, 0.0]));
+    let texture = SolidColor::new(
+        Color::ONE,
+        Some(PixelMap::decode(DynamicImage::ImageRgb32F(bump))),
+    );
+
+    let flat = refracted(&Dielectric::new(1.5));
+    let bumped = refracted(&Dielectric::glass(1.5).normal_map(texture).into());
+    assert!(flat.abs_diff_eq(-Vec3::Y, 1e-5), "{}", flat);
+    // The normal leans over along x, so the ray no longer goes straight down.
+    assert!(bumped.x.abs() > 0.05, "{}", bumped);
+}
+
+#[test]
+fn microfacet_samples_follow_their_density() {
+    seed(3);
+    let n = 100_000;
+    for bsdf in rough_surfaces() {
+        // Integrals over the sphere from uniform directions, with density 1 / 4π.
+        let (mut pdf, mut uniform) = (0.0, Color::ZERO);
+        for _ in 0..n {
+            let wi = random_sphere_distribution();
+            pdf += 4.0 * PI * bsdf.pdf(wi) / n as f32;
+            uniform += 4.0 * PI * bsdf.eval(wi) / n as f32;
+        }
+        // The same integral of the BSDF from its own samples.
+        let mut sampled = Color::ZERO;
+        for _ in 0..n {
+            if let Some(wi) = bsdf.sample() {
+                sampled += bsdf.eval(wi) / (bsdf.pdf(wi) * n as f32);
+            }
+        }
+
+        // Samples reflected through the surface are lost, half of them for the
+        // roughest metal seen head on, so the density integrates to at most 1.
+        assert!(pdf > 0.45 && pdf < 1.03, "{} for {:?}", pdf, bsdf);
+        assert!(
+            (sampled - uniform).abs().max_element() < 0.06,
+            "{} vs {} for {:?}",
+            sampled,
+            uniform,
+            bsdf
+        );
+        assert!(sampled.max_element() < 1.01, "{} for {:?}", sampled, bsdf);
+    }
+}
+
+#[test]
+fn rough_metal_keeps_most_of_the_light_in_a_furnace() {
+    // A white conductor in a white sky only loses the light that single scattering
+    // off the microfacets misses.
+    let metal = Conductor::new(SolidColor::new(Color::ONE, None), 0.5);
+    let seen = through_ball(1.0, metal, PathTracer::new());
+    assert!(
+        seen.max_element() <= 1.0 && seen.min_element() > 0.9,
+        "{}",
+        seen
+    );
+}
+
+#[test]
+fn frosted_glass_ball_lets_a_white_sky_through() {
+    // Light entering the ball is squeezed and spread out again as it leaves, so only
+    // what the microfacets lose is missing, whichever way the paths are traced.
+    for integrator in [PathTracer::new(), Bidirectional::new()] {
+        let seen = through_ball(1.0, Dielectric::glass(1.5).frosted(0.5).into(), integrator);
+        assert!(
+            seen.max_element() < 1.05 && seen.min_element() > 0.8,
+            "{} with {:?}",
+            seen,
+            integrator
+        );
+    }
+}
+
+#[test]
+fn frosted_glass_spreads_the_light_it_lets_through() {
+    seed(4);
+    let ray = Ray::new(vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0));
+    let clear = Dielectric::new(1.5);
+    let hit = Intersection::new(1.0, Vec3::ZERO, Vec3::Y, Vec3::Y, &clear, (0.5, 0.5));
+    assert!(matches!(
+        clear.scatter(&ray, &hit),
+        Some(ScatterType::Specular { .. })
+    ));
+
+    let frosted: MaterialType = Dielectric::glass(1.5).frosted(0.5).into();
+    let hit = Intersection::new(1.0, Vec3::ZERO, Vec3::Y, Vec3::Y, &frosted, (0.5, 0.5));
+    let Some(ScatterType::Microfacet { bsdf }) = frosted.scatter(&ray, &hit) else {
+        panic!("frosted glass should scatter through its microfacets");
+    };
+    let directions: Vec<Vec3> = (0..1000).filter_map(|_| bsdf.sample()).collect();
+    let through: Vec<&Vec3> = directions.iter().filter(|w| w.y < 0.0).collect();
+    let spread = through.iter().map(|w| w.x.abs()).sum::<f32>() / through.len() as f32;
+
+    assert!(through.len() > 900, "{}", through.len());
+    assert!(spread > 0.05, "{}", spread);
+}
+
+/// Principled surfaces from plastic to glass, seen from above and below.
+fn principled_surfaces() -> Vec<Bsdf> {
+    let plastic = Layers {
+        base_color: Color::new(0.8, 0.3, 0.2),
+        metallic: 0.0,
+        roughness: 0.6,
+        specular: 0.5,
+        specular_tint: 0.5,
+        sheen: 1.0,
+        clearcoat: 1.0,
+        transmission: 0.0,
+        ior: 1.45,
+    };
+    let half_metal = Layers {
+        metallic: 0.5,
+        roughness: 0.3,
+        ..plastic
+    };
+    let glass = Layers {
+        base_color: Color::ONE,
+        roughness: 0.5,
+        sheen: 0.0,
+        clearcoat: 0.0,
+        transmission: 1.0,
+        ..plastic
+    };
+
+    let mut surfaces = vec![];
+    for layers in [plastic, half_metal, glass] {
+        for angle in [0.2f32, 1.2] {
+            let wo = vec3(angle.sin(), 0.0, angle.cos());
+            for wo in [wo, -wo] {
+                surfaces.push(Bsdf::principled(Vec3::Z, wo, &layers).adjoint());
+            }
+        }
+    }
+    surfaces
+}
+
+#[test]
+fn principled_samples_follow_their_density() {
+    seed(5);
+    let n = 100_000;
+    for bsdf in principled_surfaces() {
+        let (mut pdf, mut uniform) = (0.0, Color::ZERO);
+        for _ in 0..n {
+            let wi = random_sphere_distribution();
+            pdf += 4.0 * PI * bsdf.pdf(wi) / n as f32;
+            uniform += 4.0 * PI * bsdf.eval(wi) / n as f32;
+        }
+        let mut sampled = Color::ZERO;
+        for _ in 0..n {
+            if let Some(wi) = bsdf.sample() {
+                let pdf = bsdf.pdf(wi);
+                if pdf > 0.0 {
+                    sampled += bsdf.eval(wi) / (pdf * n as f32);
+                }
+            }
+        }
+
+        assert!(pdf > 0.8 && pdf < 1.03, "{} for {:?}", pdf, bsdf);
+        assert!(
+            (sampled - uniform).abs().max_element() < 0.06,
+            "{} vs {} for {:?}",
+            sampled,
+            uniform,
+            bsdf
+        );
+    }
+}
+
+#[test]
+fn fully_metallic_principled_is_a_conductor() {
+    let layers = Layers {
+        base_color: Color::new(0.9, 0.6, 0.3),
+        metallic: 1.0,
+        roughness: 0.7,
+        specular: 0.5,
+        specular_tint: 0.0,
+        sheen: 1.0,
+        clearcoat: 0.0,
+        transmission: 1.0,
+        ior: 1.45,
+    };
+    let wo = vec3(0.6, 0.0, 0.8);
+    let principled = Bsdf::principled(Vec3::Z, wo, &layers);
+    let conductor = Bsdf::conductor(Vec3::Z, wo, alpha(0.7).unwrap(), layers.base_color);
+
+    seed(2);
+    for _ in 0..100 {
+        let wi = random_sphere_distribution();
+        let (f, expected) = (principled.eval(wi), conductor.eval(wi));
+        assert!(
+            (f - expected).abs().max_element() < 1e-5,
+            "{} vs {}",
+            f,
+            expected
+        );
+        assert!((principled.pdf(wi) - conductor.pdf(wi)).abs() < 1e-4 * (1.0 + conductor.pdf(wi)));
+    }
+}
+
+#[test]
+fn principled_parameters_follow_their_textures() {
+    // The left half of the texture is metal and the right half plastic.
+    let mut metallic = Rgb32FImage::new(2, 1);
+    metallic.put_pixel(0, 0, Rgb([1.0, 1.0, 1.0]));
+    let principled = Principled::new(SolidColor::new(Color::splat(0.5), None))
+        .metallic(Image::new(DynamicImage::ImageRgb32F(metallic), None))
+        .emission(SolidColor::new(Color::new(1.0, 0.5, 0.0), None));
+    let material: MaterialType = principled.clone().into();
+    let hit = |u: f32| Intersection::new(1.0, Vec3::ZERO, Vec3::Y, Vec3::Y, &material, (u, 0.5));
+
+    assert_eq!(principled.layers(&hit(0.25)).metallic, 1.0);
+    assert_eq!(principled.layers(&hit(0.75)).metallic, 0.0);
+    assert_eq!(principled.layers(&hit(0.75)).roughness, 0.5);
+    assert_eq!(
+        material.emitted((0.5, 0.5), Vec3::ZERO),
+        Color::new(1.0, 0.5, 0.0)
+    );
+}
+
+#[test]
+fn white_principled_ball_stays_within_a_furnace() {
+    // Diffuse and specular are added up without trading energy between them, so a
+    // white ball may get slightly brighter than the sky, but not much.
+    let white = Principled::new(SolidColor::new(Color::ONE, None)).into();
+    let seen = through_ball(1.0, white, PathTracer::new());
+    assert!(
+        seen.min_element() > 0.9 && seen.max_element() < 1.1,
+        "{}",
+        seen
+    );
+}
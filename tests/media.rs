@@ -0,0 +1,318 @@
+mod common;
+
+use std::f32::consts::PI;
+
+use common::mean;
+use glam::{vec3, Vec3};
+use tracer::{
+    materials::{emissivediffuse::EmissiveDiffuse, interface::Interface},
+    media::{
+        grid::{blackbody, Grid, VoxelGrid},
+        homogeneous::Homogeneous,
+        medium::{Medium, MediumSample},
+        phase::HenyeyGreenstein,
+    },
+    objects::sphere::Sphere,
+    random::{random_float, seed},
+    utils::{aabb::Aabb, sampling::PDF},
+    world::physics::Ray,
+    Camera, Color, PixelMap, Skybox, SolidColor, World,
+};
+
+/// A camera five units from the origin that only sees a narrow cone around it.
+fn narrow_camera() -> Camera {
+    Camera::new(
+        vec3(0.0, 0.0, 5.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        1.0,
+        1.0,
+        0.0,
+        5.0,
+    )
+}
+
+fn assert_close(color: Color, expected: Color, tolerance: f32) {
+    assert!(
+        (color - expected).abs().max_element() < tolerance * expected.max_element(),
+        "{} vs {}",
+        color,
+        expected
+    );
+}
+
+#[test]
+fn phase_function_is_normalized_with_mean_cosine_g() {
+    seed(3);
+    for g in [-0.6, 0.0, 0.3, 0.9] {
+        let phase = HenyeyGreenstein::new(g);
+        let samples = 200_000;
+
+        // Uniform directions over the sphere have density 1 / 4π.
+        let integral: f32 = (0..samples)
+            .map(|_| {
+                let z = random_float(-1.0, 1.0);
+                let phi = random_float(0.0, 2.0 * PI);
+                let r = (1.0 - z * z).sqrt();
+                phase.value(Vec3::Z, vec3(r * phi.cos(), r * phi.sin(), z)) * 4.0 * PI
+            })
+            .sum::<f32>()
+            / samples as f32;
+        let cosine: f32 = (0..samples)
+            .map(|_| PDF::phase(Vec3::Z, phase).generate().z)
+            .sum::<f32>()
+            / samples as f32;
+
+        // Strongly peaked lobes are hard to integrate with uniform samples.
+        assert!(
+            (integral - 1.0).abs() < 0.05 + 0.1 * g.abs(),
+            "{}",
+            integral
+        );
+        assert!((cosine - g).abs() < 0.01, "{} vs {}", cosine, g);
+    }
+}
+
+#[test]
+fn rays_that_pass_are_weighted_by_transmittance() {
+    seed(4);
+    let medium = Homogeneous::new(Color::new(0.1, 0.5, 1.0), Color::splat(0.2), 0.0);
+    let ray = Ray::new(Vec3::ZERO, vec3(0.0, 0.0, 2.0));
+    let samples = 100_000;
+
+    let passed = (0..samples)
+        .filter_map(|_| match medium.sample(&ray, 1.0) {
+            MediumSample::Passed { weight, .. } => Some(weight),
+            MediumSample::Scattered { distance, .. } => {
+                assert!(distance < 1.0);
+                None
+            }
+            MediumSample::Absorbed { .. } => unreachable!("homogeneous media scatter"),
+        })
+        .fold(Color::ZERO, |sum, weight| sum + weight)
+        / samples as f32;
+
+    // The ray covers two units of distance.
+    let expected = medium.transmittance(&ray, 1.0);
+    assert_close(expected, (-2.0 * Color::new(0.3, 0.7, 1.2)).exp(), 1e-5);
+    assert_close(passed, expected, 0.03);
+}
+
+#[test]
+fn absorbing_volumes_follow_beer_lambert() {
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(8)
+        .samples_per_pixel(256)
+        .seed(5);
+    let sigma_a = Color::new(0.1, 0.5, 1.0);
+    world.add(Sphere::new(
+        Vec3::ZERO,
+        1.0,
+        Interface::new(Homogeneous::new(sigma_a, Color::ZERO, 0.0)),
+    ));
+
+    // Every camera ray crosses the sphere close to its diameter.
+    let image = world.render(narrow_camera());
+    assert_close(mean(&image), (-2.0 * sigma_a).exp(), 0.04);
+}
+
+#[test]
+fn scattering_without_absorption_conserves_light() {
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(8)
+        .samples_per_pixel(64)
+        .max_depth(256)
+        .seed(6);
+    world.add(Sphere::new(
+        Vec3::ZERO,
+        1.0,
+        Interface::new(Homogeneous::new(Color::ZERO, Color::splat(3.0), 0.4)),
+    ));
+
+    // Under a uniform white sky, light scattered around inside the sphere all ends up
+    // leaving it again.
+    let image = world.render(narrow_camera());
+    assert_close(mean(&image), Color::ONE, 0.05);
+}
+
+#[test]
+fn lights_fade_into_the_atmosphere() {
+    let sigma_a = Color::new(0.05, 0.1, 0.2);
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ZERO)))
+        .width(8)
+        .samples_per_pixel(256)
+        .seed(7)
+        .atmosphere(Homogeneous::new(sigma_a, Color::ZERO, 0.0));
+    world.add_light(Sphere::new(
+        Vec3::ZERO,
+        1.0,
+        EmissiveDiffuse::new(SolidColor::new(Color::splat(4.0), None)),
+    ));
+
+    // The light's surface is four units from the camera.
+    let image = world.render(narrow_camera());
+    assert_close(mean(&image), 4.0 * (-4.0 * sigma_a).exp(), 0.04);
+}
+
+/// A grid over the box from -1 to 1, with densities from 0 at its back to 1 at its
+/// front along z, averaging 0.5 on the way through.
+fn ramp(sigma_a: Color, sigma_s: Color) -> Grid {
+    let bounds = Aabb {
+        min: Vec3::splat(-1.0),
+        max: Vec3::ONE,
+    };
+    Grid::new(
+        bounds,
+        VoxelGrid::new([1, 1, 2], vec![0.0, 1.0]),
+        sigma_a,
+        sigma_s,
+        0.0,
+    )
+}
+
+#[test]
+fn voxel_grids_interpolate_between_cell_centers() {
+    let grid = VoxelGrid::new([2, 1, 2], vec![0.0, 1.0, 2.0, 3.0]);
+
+    assert_eq!(grid.lookup(vec3(0.25, 0.5, 0.25)), 0.0);
+    assert_eq!(grid.lookup(vec3(0.75, 0.5, 0.75)), 3.0);
+    assert_eq!(grid.lookup(vec3(0.5, 0.5, 0.5)), 1.5);
+    assert_eq!(grid.lookup(vec3(0.0, 0.1, 0.9)), 2.0);
+    assert_eq!(grid.lookup(vec3(1.5, 0.5, 0.5)), 0.0);
+    assert_eq!(grid.max(), 3.0);
+}
+
+#[test]
+fn voxel_grids_load_from_raw_and_vol_files() {
+    let values = [0.0f32, 0.5, 1.0, 2.0, 4.0, 8.0];
+    let data: Vec<u8> = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let directory = std::env::temp_dir();
+
+    let raw = directory.join("tracer-voxels.raw");
+    std::fs::write(&raw, &data).unwrap();
+    let grid = VoxelGrid::load_raw(&raw, [3, 2, 1]).unwrap();
+    assert_eq!(grid, VoxelGrid::new([3, 2, 1], values.to_vec()));
+    assert!(VoxelGrid::load_raw(&raw, [2, 2, 2]).is_err());
+
+    let mut vol = b"VOL\x03".to_vec();
+    for int in [1i32, 3, 2, 1, 1] {
+        vol.extend(int.to_le_bytes());
+    }
+    for float in [-1.0f32, -2.0, -3.0, 1.0, 2.0, 3.0] {
+        vol.extend(float.to_le_bytes());
+    }
+    vol.extend(&data);
+    let path = directory.join("tracer-voxels.vol");
+    std::fs::write(&path, &vol).unwrap();
+    let (grid, bounds) = VoxelGrid::load_vol(&path).unwrap();
+    assert_eq!(grid, VoxelGrid::new([3, 2, 1], values.to_vec()));
+    assert_eq!(
+        bounds,
+        Aabb {
+            min: vec3(-1.0, -2.0, -3.0),
+            max: vec3(1.0, 2.0, 3.0),
+        }
+    );
+}
+
+#[test]
+fn grids_attenuate_by_their_integrated_density() {
+    seed(8);
+    let sigma_a = Color::new(0.2, 0.5, 1.0);
+    let grid = ramp(sigma_a, Color::ZERO);
+    let expected = (-sigma_a).exp();
+
+    // Ratio tracking of a shadow ray straight through the box.
+    let ray = Ray::new(vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, -1.0));
+    let samples = 20_000;
+    let transmittance = (0..samples)
+        .fold(Color::ZERO, |sum, _| sum + grid.transmittance(&ray, 10.0))
+        / samples as f32;
+    assert_close(transmittance, expected, 0.02);
+
+    // Delta tracking of camera rays.
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(8)
+        .samples_per_pixel(256)
+        .seed(8);
+    world.add(grid.volume());
+    assert_close(mean(&world.render(narrow_camera())), expected, 0.04);
+}
+
+#[test]
+fn grids_that_only_absorb_never_scatter() {
+    seed(9);
+    // The channel average of 0.9 rounds below it, so at full density there is
+    // no room left for null collisions.
+    let grid = Grid::new(
+        Aabb {
+            min: Vec3::splat(-1.0),
+            max: Vec3::ONE,
+        },
+        VoxelGrid::new([1, 1, 1], vec![1.0]),
+        Color::splat(0.9),
+        Color::ZERO,
+        0.0,
+    );
+    let ray = Ray::new(vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, -1.0));
+    for _ in 0..10_000 {
+        match grid.sample(&ray, 10.0) {
+            MediumSample::Passed { weight, .. } => assert!(weight.is_finite(), "{}", weight),
+            MediumSample::Scattered { .. } => panic!("the grid does not scatter"),
+            MediumSample::Absorbed { .. } => {}
+        }
+    }
+}
+
+#[test]
+fn scattering_grids_conserve_light() {
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ONE)))
+        .width(8)
+        .samples_per_pixel(64)
+        .max_depth(256)
+        .seed(9);
+    world.add(ramp(Color::ZERO, Color::new(1.0, 1.5, 2.0)).volume());
+
+    // Channels scatter differently but none of them loses light.
+    let image = world.render(narrow_camera());
+    assert_close(mean(&image), Color::ONE, 0.05);
+}
+
+#[test]
+fn grids_emit_in_proportion_to_absorption() {
+    let bounds = Aabb {
+        min: Vec3::splat(-1.0),
+        max: Vec3::ONE,
+    };
+    let color = Color::new(2.0, 1.0, 0.5);
+    let grid = Grid::new(
+        bounds,
+        VoxelGrid::new([1, 1, 1], vec![1.0]),
+        Color::splat(0.5),
+        Color::ZERO,
+        0.0,
+    )
+    .emission(VoxelGrid::new([1, 1, 1], vec![1.0]), color);
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ZERO)))
+        .width(8)
+        .samples_per_pixel(256)
+        .seed(10);
+    world.add(grid.volume());
+
+    // Light given off along two units of a medium that absorbs half of it per unit.
+    let image = world.render(narrow_camera());
+    assert_close(mean(&image), color * (1.0 - (-1.0f32).exp()), 0.04);
+}
+
+#[test]
+fn blackbodies_turn_from_red_to_blue_as_they_heat() {
+    let (warm, hot) = (blackbody(1500.0), blackbody(12000.0));
+
+    assert!(warm.x > warm.y && warm.y > warm.z, "{}", warm);
+    assert!(hot.z > hot.x, "{}", hot);
+    assert!(warm.max_element() <= 1.0 && hot.max_element() <= 1.0);
+    assert_eq!(blackbody(0.0), Color::ZERO);
+}
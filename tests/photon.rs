@@ -0,0 +1,126 @@
+mod common;
+
+use common::mean;
+use glam::{vec3, Vec3};
+use tracer::{
+    integrators::{
+        path::PathTracer,
+        photon::{Photon, PhotonMapper},
+    },
+    materials::{dielectric::Dielectric, emissivediffuse::EmissiveDiffuse, lambertian::Lambertian},
+    objects::{
+        plane::{Plane, PlaneType},
+        sphere::Sphere,
+    },
+    random::{random_float, seed},
+    utils::kdtree::KdTree,
+    Camera, Color, Framebuffer, IntegratorType, PixelMap, Skybox, SolidColor, World,
+};
+
+/// A glass ball on a diffuse floor that focuses a small light into a caustic.
+fn caustic_scene(integrator: IntegratorType, seed: u64) -> Framebuffer {
+    let mut world = World::new(Skybox::new(PixelMap::from_color(Color::ZERO)))
+        .width(16)
+        .max_depth(6)
+        .samples_per_pixel(64)
+        .seed(seed)
+        .integrator(integrator);
+    world.add(Sphere::new(vec3(0.0, 0.0, 0.0), 0.7, Dielectric::new(1.5)));
+    world.add(Plane::new(
+        PlaneType::ZX,
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        -1.0,
+        Lambertian::new(SolidColor::new(Color::splat(0.5), None)),
+    ));
+    world.add_light(Sphere::new(
+        vec3(2.0, 3.0, 0.0),
+        0.2,
+        EmissiveDiffuse::new(SolidColor::new(Color::splat(100.0), None)),
+    ));
+
+    world.render(Camera::new(
+        vec3(0.0, 1.0, 5.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        40.0,
+        1.0,
+        0.0,
+        5.0,
+    ))
+}
+
+/// Root mean square difference of two renders with different seeds, relative to
+/// their mean.
+fn noise(integrator: IntegratorType) -> f32 {
+    let (a, b) = (caustic_scene(integrator, 1), caustic_scene(integrator, 2));
+    let squared: f32 = a
+        .pixels()
+        .iter()
+        .zip(b.pixels())
+        .map(|(a, b)| (*a - *b).length_squared())
+        .sum();
+
+    (squared / a.pixels().len() as f32).sqrt() / (mean(&a).length() / 3f32.sqrt())
+}
+
+#[test]
+fn kd_tree_finds_every_point_within_the_radius() {
+    seed(5);
+    let photons: Vec<Photon> = (0..2000)
+        .map(|_| Photon {
+            position: vec3(
+                random_float(-1.0, 1.0),
+                random_float(-1.0, 1.0),
+                random_float(-1.0, 1.0),
+            ),
+            direction: Vec3::Y,
+            power: Color::ONE,
+        })
+        .collect();
+    let tree = KdTree::new(photons.clone());
+    assert_eq!(tree.len(), photons.len());
+
+    for (point, radius) in [
+        (Vec3::ZERO, 0.3),
+        (vec3(0.9, -0.9, 0.2), 0.5),
+        (Vec3::ONE, 0.01),
+    ] {
+        let mut found = vec![];
+        tree.within(point, radius, |photon| {
+            found.push(photon.position.to_array())
+        });
+        let mut expected: Vec<[f32; 3]> = photons
+            .iter()
+            .filter(|photon| photon.position.distance(point) < radius)
+            .map(|photon| photon.position.to_array())
+            .collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn gather_radius_shrinks_every_pass() {
+    let mapper = PhotonMapper {
+        photons: 1000,
+        radius: 0.5,
+    };
+
+    assert_eq!(mapper.radius(0), 0.5);
+    for pass in 0..100 {
+        assert!(mapper.radius(pass + 1) < mapper.radius(pass));
+    }
+    assert!(mapper.radius(100) > 0.0);
+}
+
+#[test]
+fn photon_mapping_renders_caustics_with_less_noise() {
+    let photons = PhotonMapper::new(2000, 0.2);
+    let (path, photon) = (noise(PathTracer::new()), noise(photons));
+
+    assert!(photon < 0.5 * path, "{} vs {}", photon, path);
+}
@@ -0,0 +1,98 @@
+use std::f32::consts::PI;
+
+use glam::{vec3, Vec3};
+use image::{DynamicImage, Rgb, Rgb32FImage};
+use tracer::{
+    materials::lambertian::Lambertian,
+    objects::{cube::Cube, object::Geometry},
+    random::{random_sphere_distribution, seed},
+    utils::sampling::{Distribution1D, PdfReady, PDF},
+    world::physics::Ray,
+    Color, PixelMap, Skybox, SolidColor,
+};
+
+/// A dim environment with a single very bright pixel just above the horizon.
+fn sun_skybox() -> Skybox {
+    let mut map = Rgb32FImage::from_pixel(64, 32, Rgb([0.1, 0.1, 0.1]));
+    map.put_pixel(40, 12, Rgb([5000.0, 5000.0, 5000.0]));
+
+    Skybox::new(PixelMap::decode(DynamicImage::ImageRgb32F(map))).rotation(30.0)
+}
+
+#[test]
+fn distribution_follows_its_weights() {
+    let distribution = Distribution1D::new(vec![1.0, 0.0, 3.0]);
+
+    assert_eq!(distribution.sample(0.1).2, 0);
+    assert_eq!(distribution.sample(0.3).2, 2);
+    assert_eq!(distribution.pdf(1), 0.0);
+    assert!((distribution.pdf(2) - 2.25).abs() < 1e-5);
+}
+
+#[test]
+fn environment_pdf_integrates_to_one() {
+    seed(1);
+    let skybox = sun_skybox();
+    let pdf = PDF::environment(&skybox);
+
+    let samples = 200_000;
+    let integral: f32 = (0..samples)
+        .map(|_| pdf.value(random_sphere_distribution()) * 4.0 * PI)
+        .sum::<f32>()
+        / samples as f32;
+
+    assert!((integral - 1.0).abs() < 0.1, "integral was {}", integral);
+}
+
+#[test]
+fn environment_samples_favour_bright_pixels() {
+    seed(2);
+    let skybox = sun_skybox();
+    let pdf = PDF::environment(&skybox);
+    let sun = skybox.map.get_pixel((40, 12));
+
+    let hits = (0..1000)
+        .filter(|_| skybox.map.dir_color(skybox.to_local(pdf.generate())) == sun)
+        .count();
+
+    assert!(hits > 900, "only {} samples hit the sun", hits);
+}
+
+#[test]
+fn environment_sample_densities_match() {
+    seed(3);
+    let skybox = sun_skybox();
+    let pdf = PDF::environment(&skybox);
+
+    for _ in 0..100 {
+        let direction: Vec3 = pdf.generate();
+        assert!((direction.length() - 1.0).abs() < 1e-4);
+        assert!(pdf.value(direction) > 0.0);
+    }
+}
+
+#[test]
+fn cube_light_pdf_integrates_to_one() {
+    seed(4);
+    let cube = Cube::new(
+        vec3(-1.0, -0.5, -1.0),
+        vec3(1.0, 0.5, 2.0),
+        Lambertian::new(SolidColor::new(Color::ONE, None)),
+    );
+    let origin = vec3(0.5, 2.0, 3.0);
+
+    let samples = 200_000;
+    let integral: f32 = (0..samples)
+        .map(|_| cube.pdf_value(origin, random_sphere_distribution().normalize()) * 4.0 * PI)
+        .sum::<f32>()
+        / samples as f32;
+    assert!((integral - 1.0).abs() < 0.05, "integral was {}", integral);
+
+    // Sampled directions all point at the cube, and its normals face out of it.
+    for _ in 0..100 {
+        let ray = Ray::new(origin, cube.random(origin));
+        let hit = cube.intersects(&ray, 0.001, f32::MAX).unwrap();
+        assert!(ray.direction.dot(hit.outward_normal) < 0.0);
+        assert_eq!(hit.outward_normal.length(), 1.0);
+    }
+}
@@ -0,0 +1,493 @@
+use std::path::Path;
+
+use glam::Vec3;
+use image::{DynamicImage, Rgb, RgbImage};
+use tracer::{
+    integrators::{
+        debug::AmbientOcclusion, mixture::Mixture, path::PathTracer, photon::PhotonMapper,
+    },
+    load_scene,
+    materials::{
+        conductor::Conductor,
+        dielectric::Dielectric,
+        interface::Interface,
+        material::{Material, MaterialType},
+        principled::Principled,
+        texture::Texture,
+    },
+    media::{homogeneous::Homogeneous, medium::MediumType},
+    objects::obj::load_obj_mtl,
+    parse_scene,
+    utils::tonemap,
+    Bounces, Color, Heuristic, IntegratorType, Operator, SceneError, SolidColor, ToneMap,
+};
+
+const MINIMAL: &str = r#"
+[render]
+width = 16
+samples_per_pixel = 2
+
+[skybox]
+color = [0.1, 0.2, 0.3]
+
+[camera]
+look_from = [0.0, 0.0, 5.0]
+look_at = [0.0, 0.0, 0.0]
+vfov = 40.0
+aspect_ratio = 2.0
+
+[textures.grey]
+type = "solid"
+color = [0.5, 0.5, 0.5]
+
+[materials.matte]
+type = "lambertian"
+texture = "grey"
+
+[[spheres]]
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "matte"
+"#;
+
+fn parse(source: &str) -> Result<(tracer::World, tracer::Camera), SceneError> {
+    parse_scene(source, Path::new("scenes/test.toml"))
+}
+
+#[test]
+fn builds_world_from_description() {
+    let (world, camera) = parse(MINIMAL).unwrap();
+
+    assert_eq!(world.width, 16);
+    assert_eq!(world.samples_per_pixel, 2);
+    assert_eq!(world.objects().len(), 1);
+    assert!(world.lights().is_empty());
+    assert_eq!(world.tone_map, ToneMap::default());
+    assert_eq!(world.render(camera).dimensions(), (16, 8));
+}
+
+#[test]
+fn render_settings_select_the_tone_map() {
+    let source = MINIMAL.replace(
+        "samples_per_pixel = 2",
+        "samples_per_pixel = 2\ntone_map = \"aces\"\nexposure = -0.5",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+
+    assert_eq!(world.tone_map, ToneMap::new(Operator::Aces).exposure(-0.5));
+}
+
+#[test]
+fn render_settings_select_the_integrator() {
+    let (world, _camera) = parse(MINIMAL).unwrap();
+    assert_eq!(
+        (world.integrator, world.heuristic),
+        (PathTracer::new(), Heuristic::Power)
+    );
+
+    let source = MINIMAL.replace(
+        "samples_per_pixel = 2",
+        "samples_per_pixel = 2\nintegrator = \"mixture\"\nmis = \"balance\"",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    assert_eq!(
+        (world.integrator, world.heuristic),
+        (Mixture::new(), Heuristic::Balance)
+    );
+
+    let source = MINIMAL.replace(
+        "samples_per_pixel = 2",
+        "samples_per_pixel = 2\nintegrator = \"ambient_occlusion\"",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    assert_eq!(
+        world.integrator,
+        IntegratorType::AmbientOcclusion(AmbientOcclusion::default())
+    );
+
+    let source = MINIMAL.replace(
+        "samples_per_pixel = 2",
+        "samples_per_pixel = 2\nintegrator = \"photon_mapping\"\nphotons = 5000\nphoton_radius = 0.25",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    assert_eq!(world.integrator, PhotonMapper::new(5000, 0.25));
+}
+
+#[test]
+fn render_settings_limit_each_kind_of_bounce() {
+    let (world, _camera) = parse(MINIMAL).unwrap();
+    assert_eq!(world.bounces, Bounces::default());
+
+    let source = MINIMAL.replace(
+        "samples_per_pixel = 2",
+        "samples_per_pixel = 2\nmax_depth = 5\ntransmission_bounces = 12",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    assert_eq!(
+        (
+            world.bounces.diffuse,
+            world.bounces.specular,
+            world.bounces.transmission
+        ),
+        (5, 5, 12)
+    );
+}
+
+#[test]
+fn media_fill_interfaces_glass_and_the_atmosphere() {
+    let source = MINIMAL
+        .replace(
+            "[render]",
+            "atmosphere = \"haze\"\n\n[render]",
+        )
+        .replace(
+            "[skybox]",
+            "[media.haze]\nsigma_s = [0.01, 0.01, 0.01]\n\n\
+             [media.smoke]\nsigma_a = [0.2, 0.2, 0.2]\nsigma_s = [1.0, 1.0, 1.0]\ng = 0.5\n\n[skybox]",
+        )
+        .replace(
+            "type = \"lambertian\"\ntexture = \"grey\"",
+            "type = \"interface\"\nmedium = \"smoke\"",
+        );
+    let (world, _camera) = parse(&source).unwrap();
+
+    assert_eq!(
+        world.atmosphere,
+        Some(Homogeneous::new(Vec3::ZERO, Vec3::splat(0.01), 0.0))
+    );
+    assert_eq!(
+        world.materials()[0],
+        Interface::new(Homogeneous::new(Vec3::splat(0.2), Vec3::ONE, 0.5))
+    );
+
+    let source = source.replace("type = \"interface\"", "type = \"lambertian\"");
+    let message = parse(&source).err().unwrap().to_string();
+    assert!(message.contains("can't hold a medium"), "{}", message);
+}
+
+#[test]
+fn dielectrics_absorb_and_follow_normal_maps() {
+    let source = MINIMAL.replace(
+        "type = \"lambertian\"",
+        "type = \"dielectric\"\nior = 1.5\nabsorption = [0.5, 0.8, 1.0]\ndensity = 2.0",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+
+    let glass = Dielectric::glass(1.5)
+        .absorption(Vec3::new(0.5, 0.8, 1.0), 2.0)
+        .normal_map(SolidColor::new(Vec3::splat(0.5), None));
+    assert_eq!(world.materials()[0], glass.into());
+}
+
+#[test]
+fn conductors_and_frosted_glass_take_a_roughness() {
+    let source = MINIMAL.replace(
+        "type = \"lambertian\"",
+        "type = \"conductor\"\nroughness = 0.4",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    assert_eq!(
+        world.materials()[0],
+        Conductor::new(SolidColor::new(Vec3::splat(0.5), None), 0.4)
+    );
+
+    let source = MINIMAL.replace(
+        "type = \"lambertian\"",
+        "type = \"dielectric\"\nior = 1.5\nroughness = 0.3",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    let glass = Dielectric::glass(1.5)
+        .frosted(0.3)
+        .normal_map(SolidColor::new(Vec3::splat(0.5), None));
+    assert_eq!(world.materials()[0], glass.into());
+}
+
+#[test]
+fn principled_parameters_take_numbers_colors_and_textures() {
+    let source = MINIMAL.replace(
+        "type = \"lambertian\"\ntexture = \"grey\"",
+        "type = \"principled\"\nmetallic = 1\nroughness = \"grey\"\nemission = [1.0, 0.5, 0.0]\nior = 1.5",
+    );
+    let (world, _camera) = parse(&source).unwrap();
+    let solid = |color: Vec3| SolidColor::new(color, None);
+    let principled = Principled::new(solid(Vec3::splat(0.8)))
+        .metallic(solid(Vec3::ONE))
+        .roughness(solid(Vec3::splat(0.5)))
+        .emission(solid(Vec3::new(1.0, 0.5, 0.0)))
+        .ior(1.5);
+    assert_eq!(world.materials()[0], principled.into());
+
+    let source = MINIMAL.replace(
+        "type = \"lambertian\"",
+        "type = \"conductor\"\nroughness = \"grey\"",
+    );
+    let message = parse(&source).err().unwrap().to_string();
+    assert!(
+        message.contains("material `matte` takes a number for `roughness`"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn meshes_without_a_material_use_their_mtl_library() {
+    let directory = std::env::temp_dir();
+    std::fs::write(
+        directory.join("tracer-scene-mesh.mtl"),
+        "newmtl gold\nKd 1.0 0.8 0.2\nKs 0.5 0.5 0.5\nPm 1.0\nPr 0.3\nKe 0.0 0.0 0.0\n",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("tracer-scene-mesh.obj"),
+        "mtllib tracer-scene-mesh.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+         v 2 0 0\nv 3 0 0\nv 3 1 0\nv 2 1 0\n\
+         f 1 2 3\nusemtl gold\nf 2 4 3\nf 5 6 7 8\n",
+    )
+    .unwrap();
+    let source = format!(
+        "{}\n[[meshes]]\npath = \"tracer-scene-mesh.obj\"\n",
+        MINIMAL
+    );
+
+    let (world, _camera) = parse_scene(&source, &directory.join("scene.toml")).unwrap();
+    // The quad is split into two triangles.
+    assert_eq!(world.objects().len(), 5);
+    let solid = |color: Vec3| SolidColor::new(color, None);
+    let gold = Principled::new(solid(Vec3::new(1.0, 0.8, 0.2)))
+        .metallic(solid(Vec3::ONE))
+        .roughness(solid(Vec3::splat(0.3)))
+        .specular(solid(Vec3::splat(0.5)))
+        .sheen(solid(Vec3::ZERO))
+        .clearcoat(solid(Vec3::ZERO))
+        .transmission(solid(Vec3::ZERO))
+        .emission(solid(Vec3::ZERO));
+    let grey = Principled::new(solid(Vec3::splat(0.8)));
+    assert_eq!(world.materials()[1], gold.into());
+    assert_eq!(world.materials()[2], grey.into());
+    // The face before `usemtl` has no material and falls back on grey.
+    let mut ids = world.material_ids()[1..].to_vec();
+    ids.sort();
+    assert_eq!(ids, vec![2, 2, 2, 3]);
+}
+
+#[test]
+fn mtl_color_maps_are_decoded_from_srgb_and_parameter_maps_kept_linear() {
+    let directory = std::env::temp_dir();
+    let grey = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128, 128, 128])));
+    grey.save(directory.join("tracer-mtl-grey.png")).unwrap();
+    std::fs::write(
+        directory.join("tracer-mtl-maps.mtl"),
+        "newmtl painted\nmap_Kd tracer-mtl-grey.png\nmap_Pr tracer-mtl-grey.png\n\
+         map_Ke tracer-mtl-grey.png\nmap_Ks tracer-mtl-grey.png\nmap_d tracer-mtl-grey.png\n\
+         illum 4\nmap_Bump -bm 1.0 tracer-mtl-grey.png\n",
+    )
+    .unwrap();
+    let path = directory.join("tracer-mtl-maps.obj");
+    std::fs::write(
+        &path,
+        "mtllib tracer-mtl-maps.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n\
+         usemtl painted\nf 1/1 2/2 3/3\n",
+    )
+    .unwrap();
+
+    let mesh = load_obj_mtl(&path, Vec3::ZERO, 1.0).unwrap();
+    let principled = match &mesh.materials[0] {
+        MaterialType::Principled(principled) => principled,
+        material => panic!("{:?}", material),
+    };
+    let uv = (0.5, 0.5);
+    let close = |a: Color, b: Color| (a - b).abs().max_element() < 1e-6;
+    let srgb = Color::splat(tonemap::srgb_eotf(128.0 / 255.0));
+    assert!(close(
+        principled.base_color.get_color_uv(uv, Vec3::ZERO),
+        srgb
+    ));
+    assert!(close(
+        principled.emission.get_color_uv(uv, Vec3::ZERO),
+        srgb
+    ));
+    let linear = Color::splat(128.0 / 255.0);
+    assert!(close(
+        principled.roughness.get_color_uv(uv, Vec3::ZERO),
+        linear
+    ));
+    assert!(close(
+        principled.specular.get_color_uv(uv, Vec3::ZERO),
+        linear
+    ));
+    // Glass transmits what the dissolve map doesn't cover.
+    assert!(close(
+        principled.transmission.get_color_uv(uv, Vec3::ZERO),
+        Color::ONE - linear
+    ));
+    // The bump map is left out, so it doesn't bend the normal.
+    assert_eq!(principled.base_color.adjusted_normal(uv, Vec3::Z), Vec3::Z);
+}
+
+#[test]
+fn mtl_libraries_and_textures_that_cant_be_read_fail_the_mesh() {
+    let directory = std::env::temp_dir();
+    std::fs::write(
+        directory.join("tracer-mtl-missing-texture.mtl"),
+        "newmtl painted\nmap_Kd tracer-mtl-missing.png\n",
+    )
+    .unwrap();
+    for (name, library, error) in [
+        (
+            "tracer-mtl-no-library",
+            "tracer-mtl-missing.mtl",
+            "MTL library",
+        ),
+        (
+            "tracer-mtl-no-texture",
+            "tracer-mtl-missing-texture.mtl",
+            "tracer-mtl-missing.png of material painted",
+        ),
+    ] {
+        let path = directory.join(format!("{}.obj", name));
+        std::fs::write(
+            &path,
+            format!("mtllib {}\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", library),
+        )
+        .unwrap();
+        let source = format!("{}\n[[meshes]]\npath = \"{}.obj\"\n", MINIMAL, name);
+
+        match parse_scene(&source, &directory.join("scene.toml")) {
+            Err(SceneError::Asset {
+                path: asset,
+                message,
+            }) => {
+                assert_eq!(asset, path);
+                assert!(message.contains(error), "{}", message);
+            }
+            other => panic!("expected a missing asset, got {:?}", other.err()),
+        }
+    }
+}
+
+#[test]
+fn volumes_are_filled_with_grid_media() {
+    let directory = std::env::temp_dir();
+    let density: Vec<u8> = [0.5f32, 1.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    std::fs::write(directory.join("tracer-scene-density.raw"), density).unwrap();
+    let source = format!(
+        "{}\n[media.cloud]\nsigma_s = [1.0, 1.0, 1.0]\ndensity = \"tracer-scene-density.raw\"\n\
+         resolution = [2, 1, 1]\nbounds = [[-1.0, 0.0, -1.0], [1.0, 1.0, 1.0]]\n\n\
+         [[volumes]]\nmedium = \"cloud\"\n",
+        MINIMAL
+    );
+
+    let (world, _camera) = parse_scene(&source, &directory.join("scene.toml")).unwrap();
+    assert_eq!(world.objects().len(), 2);
+    match world.materials()[1].medium() {
+        Some(MediumType::Grid(grid)) => {
+            assert_eq!(grid.bounds.min, Vec3::new(-1.0, 0.0, -1.0));
+            assert_eq!(grid.density.values, vec![0.5, 1.0]);
+        }
+        other => panic!("expected a grid medium, got {:?}", other),
+    }
+
+    let source = source.replace("resolution = [2, 1, 1]\n", "");
+    let message = parse_scene(&source, &directory.join("scene.toml"))
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        message.contains("missing field `resolution`"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn loads_example_scene() {
+    let (world, _camera) = load_scene(Path::new("scenes/spheres.toml")).unwrap();
+
+    assert_eq!(world.objects().len(), 5);
+    assert_eq!(world.lights().len(), 1);
+}
+
+#[test]
+fn syntax_errors_report_the_line() {
+    let source = MINIMAL.replace("vfov = 40.0", "vfov = \"wide\"");
+
+    match parse(&source) {
+        Err(SceneError::Parse { source, .. }) => {
+            let message = source.to_string();
+            assert!(message.contains("line 12"), "{}", message);
+        }
+        other => panic!("expected a parse error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let source = MINIMAL.replace("radius = 1.0", "radius = 1.0\nradiu = 2.0");
+
+    let message = parse(&source).err().unwrap().to_string();
+    assert!(message.contains("unknown field `radiu`"), "{}", message);
+}
+
+#[test]
+fn unknown_references_report_line_and_column() {
+    let source = MINIMAL.replace("material = \"matte\"", "material = \"shiny\"");
+
+    match parse(&source) {
+        Err(SceneError::Invalid {
+            line,
+            column,
+            message,
+            ..
+        }) => {
+            assert_eq!((line, column), (26, 12));
+            assert_eq!(message, "unknown material `shiny`");
+        }
+        other => panic!("expected an invalid scene, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn images_too_small_to_render_are_rejected() {
+    for (from, to, message) in [
+        (
+            "width = 16",
+            "width = 1",
+            "`width` must be at least 2 pixels",
+        ),
+        (
+            "samples_per_pixel = 2",
+            "samples_per_pixel = 0",
+            "`samples_per_pixel` must be at least 1",
+        ),
+        (
+            "aspect_ratio = 2.0",
+            "aspect_ratio = 10.0",
+            "makes a 16 pixel wide image less than 2 pixels high",
+        ),
+    ] {
+        match parse(&MINIMAL.replace(from, to)) {
+            Err(err @ SceneError::Invalid { .. }) => {
+                assert!(err.to_string().contains(message), "{}", err);
+            }
+            other => panic!("expected an invalid scene, got {:?}", other.err()),
+        }
+    }
+}
+
+#[test]
+fn missing_assets_are_errors() {
+    let source = format!(
+        "{}\n[[meshes]]\npath = \"missing.obj\"\nmaterial = \"matte\"\n",
+        MINIMAL
+    );
+
+    match parse(&source) {
+        Err(SceneError::Asset { path, .. }) => {
+            assert_eq!(path, Path::new("scenes/missing.obj"));
+        }
+        other => panic!("expected a missing asset, got {:?}", other.err()),
+    }
+}